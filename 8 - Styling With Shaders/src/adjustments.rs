@@ -0,0 +1,310 @@
+use crate::{
+    config::{self, ConfigError, Table, Value},
+    decode::DecodedImage,
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// How much temperature and tint at their extremes scale the color channels
+/// they act on.
+const WHITE_BALANCE_STRENGTH: f32 = 0.3;
+/// The weights of red, green and blue in the luma of a color.
+const LUMA_WEIGHTS: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Adjustment {
+    Exposure,
+    Contrast,
+    Saturation,
+    Temperature,
+    Tint,
+    Vibrance,
+}
+
+impl Adjustment {
+    pub const ALL: [Self; 6] = [
+        Self::Exposure,
+        Self::Contrast,
+        Self::Saturation,
+        Self::Temperature,
+        Self::Tint,
+        Self::Vibrance,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Exposure => "exposure",
+            Self::Contrast => "contrast",
+            Self::Saturation => "saturation",
+            Self::Temperature => "temperature",
+            Self::Tint => "tint",
+            Self::Vibrance => "vibrance",
+        }
+    }
+}
+
+/// Tone and color adjustments to an image. The slideshow shows them in a
+/// shader as they are made, and keeps them in a recipe next to the image
+/// rather than in the image itself, until it is saved with them.
+///
+/// Exposure is in stops, from -3 to 3, and the others run from -1 to 1.
+/// Positive temperatures are warmer and positive tints more magenta.
+/// Vibrance is like saturation, but mostly leaves saturated colors alone.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Adjustments {
+    pub exposure: f32,
+    pub contrast: f32,
+    pub saturation: f32,
+    pub temperature: f32,
+    pub tint: f32,
+    pub vibrance: f32,
+}
+
+impl Adjustments {
+    /// Reads the recipe of the image at `path`, if it has one.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let mut adjustments = Self::default();
+        let recipe_path = recipe_path(path);
+        if !recipe_path.is_file() {
+            return Ok(adjustments);
+        }
+
+        let table = config::read_table(&recipe_path)?;
+        for adjustment in Adjustment::ALL {
+            if let Some(value) =
+                table.get(adjustment.name()).and_then(config::as_number)
+            {
+                adjustments.set(adjustment, value as f32);
+            }
+        }
+        Ok(adjustments)
+    }
+
+    /// Writes the recipe of the image at `path`, or removes it if there is
+    /// nothing to adjust.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let recipe_path = recipe_path(path);
+        if self.is_identity() {
+            return match fs::remove_file(&recipe_path) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                    Err(error)
+                }
+                _ => Ok(()),
+            };
+        }
+
+        let mut table = Table::new();
+        for adjustment in Adjustment::ALL {
+            table.insert(
+                adjustment.name().into(),
+                Value::Float(self.get(adjustment) as f64),
+            );
+        }
+        config::write_table(&recipe_path, &table)
+    }
+
+    pub fn get(&self, adjustment: Adjustment) -> f32 {
+        match adjustment {
+            Adjustment::Exposure => self.exposure,
+            Adjustment::Contrast => self.contrast,
+            Adjustment::Saturation => self.saturation,
+            Adjustment::Temperature => self.temperature,
+            Adjustment::Tint => self.tint,
+            Adjustment::Vibrance => self.vibrance,
+        }
+    }
+
+    pub fn set(&mut self, adjustment: Adjustment, value: f32) {
+        let field = match adjustment {
+            Adjustment::Exposure => &mut self.exposure,
+            Adjustment::Contrast => &mut self.contrast,
+            Adjustment::Saturation => &mut self.saturation,
+            Adjustment::Temperature => &mut self.temperature,
+            Adjustment::Tint => &mut self.tint,
+            Adjustment::Vibrance => &mut self.vibrance,
+        };
+        *field = value;
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Adjusts a color with channels from 0 to 1. This is the reference for
+    /// the shader of the slideshow image, and takes the same steps.
+    pub fn apply_to_color(&self, [r, g, b]: [f32; 3]) -> [f32; 3] {
+        let gain = 2.0f32.powf(self.exposure);
+        let rgb = [
+            r * (1.0 + WHITE_BALANCE_STRENGTH * self.temperature),
+            g * (1.0 - WHITE_BALANCE_STRENGTH * self.tint),
+            b * (1.0 - WHITE_BALANCE_STRENGTH * self.temperature),
+        ]
+        .map(|c| (c * gain - 0.5) * (1.0 + self.contrast) + 0.5);
+        let rgb = saturate(rgb, 1.0 + self.saturation);
+        let max = rgb[0].max(rgb[1]).max(rgb[2]);
+        let min = rgb[0].min(rgb[1]).min(rgb[2]);
+        let chroma = (max - min).clamp(0.0, 1.0);
+        let rgb = saturate(rgb, 1.0 + self.vibrance * (1.0 - chroma));
+        rgb.map(|c| c.clamp(0.0, 1.0))
+    }
+
+    pub fn apply(&self, image: &mut DecodedImage) {
+        if self.is_identity() {
+            return;
+        }
+        for pixel in &mut image.pixels {
+            let [b, g, r, a] = pixel.to_le_bytes();
+            let [r, g, b] = self
+                .apply_to_color([r, g, b].map(|c| c as f32 / 255.0))
+                .map(|c| (c * 255.0).round() as u8);
+            *pixel = u32::from_le_bytes([b, g, r, a]);
+        }
+    }
+}
+
+/// Moves each channel away from the luma of the color by `amount`, or
+/// towards it if `amount` is less than 1.
+fn saturate(rgb: [f32; 3], amount: f32) -> [f32; 3] {
+    let luma = rgb[0] * LUMA_WEIGHTS[0]
+        + rgb[1] * LUMA_WEIGHTS[1]
+        + rgb[2] * LUMA_WEIGHTS[2];
+    rgb.map(|c| luma + (c - luma) * amount)
+}
+
+/// The recipe of `IMG_1234.jpg` is `IMG_1234.jpg.recipe`, next to it.
+fn recipe_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".recipe");
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mix(x: [f32; 3], y: [f32; 3], a: f32) -> [f32; 3] {
+        [0, 1, 2].map(|i| x[i] * (1.0 - a) + y[i] * a)
+    }
+
+    fn dot(x: [f32; 3], y: [f32; 3]) -> f32 {
+        x[0] * y[0] + x[1] * y[1] + x[2] * y[2]
+    }
+
+    /// A transcription of the `pixel` function of the slideshow image in
+    /// `app.rs`, line for line. Nothing runs the shader itself here, so this
+    /// only catches `apply_to_color` drifting from the transcription, and a
+    /// change to the shader has to be copied here by hand.
+    fn shader(adjustments: &Adjustments, color: [f32; 3]) -> [f32; 3] {
+        let a = adjustments;
+        let white_balance = [
+            1.0 + 0.3 * a.temperature,
+            1.0 - 0.3 * a.tint,
+            1.0 - 0.3 * a.temperature,
+        ];
+        let mut rgb = [0, 1, 2].map(|i| color[i] * white_balance[i]);
+        rgb = rgb.map(|c| c * 2.0f32.powf(a.exposure));
+        rgb = rgb.map(|c| (c - 0.5) * (1.0 + a.contrast) + 0.5);
+        let mut luma = dot(rgb, [0.2126, 0.7152, 0.0722]);
+        rgb = mix([luma; 3], rgb, 1.0 + a.saturation);
+        let chroma =
+            rgb[0].max(rgb[1]).max(rgb[2]) - rgb[0].min(rgb[1]).min(rgb[2]);
+        luma = dot(rgb, [0.2126, 0.7152, 0.0722]);
+        rgb = mix(
+            [luma; 3],
+            rgb,
+            1.0 + a.vibrance * (1.0 - chroma.clamp(0.0, 1.0)),
+        );
+        rgb.map(|c| c.clamp(0.0, 1.0))
+    }
+
+    fn samples() -> Vec<Adjustments> {
+        vec![
+            Adjustments::default(),
+            Adjustments {
+                exposure: 1.5,
+                contrast: 0.4,
+                saturation: -0.3,
+                temperature: 0.8,
+                tint: -0.5,
+                vibrance: 0.6,
+            },
+            Adjustments {
+                exposure: -2.0,
+                contrast: -0.7,
+                saturation: 1.0,
+                temperature: -1.0,
+                tint: 1.0,
+                vibrance: -1.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn matches_transcribed_shader() {
+        for adjustments in samples() {
+            for r in 0..=4 {
+                for g in 0..=4 {
+                    for b in 0..=4 {
+                        let color = [r, g, b].map(|c| c as f32 / 4.0);
+                        let expected = shader(&adjustments, color);
+                        let actual = adjustments.apply_to_color(color);
+                        for (actual, expected) in actual.iter().zip(expected) {
+                            assert!(
+                                (actual - expected).abs() < 1e-5,
+                                "{:?} of {:?}: {:?} != {:?}",
+                                adjustments,
+                                color,
+                                actual,
+                                expected
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn exports_what_the_shader_shows() {
+        let pixels = vec![0xff_20_80_e0, 0x80_ff_00_40, 0xff_00_00_00];
+        for adjustments in samples() {
+            let mut image = DecodedImage {
+                width: 3,
+                height: 1,
+                pixels: pixels.clone(),
+            };
+            adjustments.apply(&mut image);
+            for (x, pixel) in pixels.iter().enumerate() {
+                let [b, g, r, a] = pixel.to_le_bytes();
+                let color = [r, g, b].map(|c| c as f32 / 255.0);
+                let [r, g, b] = shader(&adjustments, color)
+                    .map(|c| (c * 255.0).round() as u8);
+                assert_eq!(image.rgba(x, 0), Some([r, g, b, a]));
+            }
+        }
+    }
+
+    #[test]
+    fn adjusts_in_the_expected_direction() {
+        let grey = [0.25, 0.25, 0.25];
+        let brighter = Adjustments {
+            exposure: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(brighter.apply_to_color(grey), [0.5, 0.5, 0.5]);
+        let warmer = Adjustments {
+            temperature: 1.0,
+            ..Default::default()
+        };
+        let [r, _, b] = warmer.apply_to_color(grey);
+        assert!(r > b);
+        let desaturated = Adjustments {
+            saturation: -1.0,
+            ..Default::default()
+        };
+        let [r, g, b] = desaturated.apply_to_color([1.0, 0.0, 0.0]);
+        assert!(r == g && g == b);
+    }
+}
@@ -1,5 +1,34 @@
+use crate::{
+    adjustments::{Adjustment, Adjustments},
+    config::Config,
+    decode::{self, DecodeAction, DecodedImage},
+    decode_pool::{DecodePool, JobKey, JobKind, Priority},
+    edit::{self, AspectRatio, Edit, EditAction, SaveTarget},
+    file_ops::{self, Change, Effect, FileOp, FileOpAction},
+    filter::Filter,
+    folder_dialog::{self, FolderDialogAction},
+    grid_layout::{self, GridLayout, GridRow},
+    image_info::{ImageInfoAction, ImageInfos, ImageSize},
+    metadata::{Metadata, MetadataAction},
+    orientation::Orientation,
+    rating::{self, ColorLabel, Flag, Rating, Ratings},
+    recent_folders::RecentFolders,
+    scan,
+    selection::Selection,
+    settings::Settings,
+    sort::{Grouping, SortKey, SortOptions, SortOrder},
+    tags::{self, Collection, TagStore},
+    texture_cache::TextureCache,
+    thumbnails::{ThumbnailAction, Thumbnails},
+    watcher::{DirWatcher, DirWatcherAction},
+    zoom::{Viewport, Zoom, ZoomMode},
+};
 use makepad_widgets::*;
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
 live_design! {
     use link::widgets::*;
@@ -17,51 +46,179 @@ live_design! {
         height: Fit,
         align: {
             x: 1.0,
+            y: 0.5,
         },
+        spacing: 10,
 
+        search = <TextInput> {
+            width: 240,
+            empty_text: "Search, e.g. ext:png width>4000",
+        }
+        collection = <TextInput> {
+            width: 200,
+            empty_text: "Collection, e.g. beach, -family",
+        }
+        collection_button = <MenuBarButton> {
+            text: "Show",
+        }
+        sort_key = <DropDown> {
+            width: 140,
+        }
+        sort_order = <DropDown> {
+            width: 120,
+        }
+        grouping = <DropDown> {
+            width: 120,
+        }
+        grid_layout = <DropDown> {
+            width: 120,
+        }
+        zoom_mode = <DropDown> {
+            width: 120,
+        }
+        thumbnail_size = <Slider> {
+            width: 200,
+            text: "Size",
+            min: 64.0,
+            max: 512.0,
+            default: 256.0,
+            precision: 0,
+        }
+        recent_folders = <DropDown> {
+            width: 300,
+        }
+        open_folder_button = <MenuBarButton> {
+            text: "Open Folder…",
+        }
         button = <MenuBarButton> {}
     }
 
-    ImageGridItem = <View> {
+    TagSuggestionButton = <MenuBarButton> {
+        visible: false,
+        text: "",
+    }
+
+    FileOpsBar = <View> {
+        width: Fill,
+        height: Fit,
+        align: {
+            y: 0.5,
+        },
+        spacing: 10,
+
+        status = <Label> {
+            width: Fill,
+            text: "",
+        }
+        trash_button = <MenuBarButton> {
+            text: "Trash",
+        }
+        move_button = <MenuBarButton> {
+            text: "Move To…",
+        }
+        copy_button = <MenuBarButton> {
+            text: "Copy To…",
+        }
+        rename_template = <TextInput> {
+            width: 160,
+            text: "{date}_{index}",
+        }
+        rename_button = <MenuBarButton> {
+            text: "Rename",
+        }
+        undo_button = <MenuBarButton> {
+            text: "Undo",
+        }
+        compare_button = <MenuBarButton> {
+            text: "Compare",
+        }
+        tag_input = <TextInput> {
+            width: 160,
+            empty_text: "Tags, e.g. beach, 2025",
+        }
+        tag_suggestions = <View> {
+            width: Fit,
+            height: Fit,
+            spacing: 5,
+
+            tag_suggestion_0 = <TagSuggestionButton> {}
+            tag_suggestion_1 = <TagSuggestionButton> {}
+            tag_suggestion_2 = <TagSuggestionButton> {}
+        }
+        tag_button = <MenuBarButton> {
+            text: "Tag",
+        }
+        untag_button = <MenuBarButton> {
+            text: "Untag",
+        }
+    }
+
+    ImageGridItem = {{ImageGridItem}} {
         width: 256,
         height: 256,
+        flow: Overlay,
         align: {
             x: 0.5,
             y: 0.5,
         },
+        placeholder: (PLACEHOLDER),
 
-        <View> {
-            animator: {
-                hover = {
-                    default: off,
-                    
-                    off = {
-                        from: {
-                            all: Forward {
-                                duration: 0.1,
-                            },
-                        },
-                        apply: {
-                            width: 230,
-                            height: 230,
-                        },
-                        redraw: true,
-                    }
-    
-                    on = {
-                        from: {
-                            all: Forward {
-                                duration: 0.1,
-                            },
+        show_bg: true,
+        draw_bg: {
+            instance highlighted: 0.0,
+            instance focused: 0.0,
+            instance selected: 0.0,
+
+            fn pixel(self) -> vec4 {
+                let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+                sdf.box(
+                    2.0,
+                    2.0,
+                    self.rect_size.x - 4.0,
+                    self.rect_size.y - 4.0,
+                    4.0
+                );
+                sdf.fill_keep(vec4(0.29, 0.56, 0.89, 0.35 * self.selected));
+                let outline = mix(#4a90e2, #fff, self.focused);
+                outline.a *= max(self.highlighted, self.focused);
+                sdf.stroke(outline, 3.0);
+                return sdf.result;
+            }
+        },
+
+        animator: {
+            hover = {
+                default: off,
+
+                off = {
+                    from: {
+                        all: Forward {
+                            duration: 0.1,
                         },
-                        apply: {
-                            width: 256,
-                            height: 256,
+                    },
+                    apply: {
+                        hover: 0.0,
+                    },
+                    redraw: true,
+                }
+
+                on = {
+                    from: {
+                        all: Forward {
+                            duration: 0.1,
                         },
-                        redraw: true,
-                    }
+                    },
+                    apply: {
+                        hover: 1.0,
+                    },
+                    redraw: true,
                 }
             }
+        }
+
+        frame = <View> {
+            width: 230,
+            height: 230,
 
             image = <Image> {
                 width: Fill,
@@ -70,6 +227,41 @@ live_design! {
                 source: (PLACEHOLDER),
             }
         }
+        badges = <View> {
+            width: Fill,
+            height: Fill,
+
+            show_bg: true,
+            draw_bg: {
+                instance stars: 0.0,
+                instance label_color: vec4(0.0, 0.0, 0.0, 0.0),
+                instance picked: 0.0,
+                instance rejected: 0.0,
+
+                fn pixel(self) -> vec4 {
+                    let size = self.rect_size;
+                    let sdf = Sdf2d::viewport(self.pos * size);
+                    sdf.rect(0.0, 0.0, size.x, size.y);
+                    sdf.fill(vec4(0.0, 0.0, 0.0, 0.5 * self.rejected));
+                    if self.stars > 0.0 {
+                        // Only the pip closest to the pixel can cover it.
+                        let pip = clamp(
+                            floor((self.pos.x * size.x - 16.0) / 12.0 + 0.5),
+                            0.0,
+                            self.stars - 1.0
+                        );
+                        sdf.circle(16.0 + pip * 12.0, size.y - 16.0, 4.0);
+                        sdf.fill_keep(#fff);
+                        sdf.stroke(#0008, 1.0);
+                    }
+                    sdf.circle(size.x - 16.0, 16.0, 6.0);
+                    sdf.fill(self.label_color);
+                    sdf.circle(16.0, 16.0, 6.0);
+                    sdf.fill(vec4(0.3, 0.75, 0.3, self.picked));
+                    return sdf.result;
+                }
+            }
+        }
     }
 
     ImageGridRow = {{ImageGridRow}} {
@@ -89,7 +281,32 @@ live_design! {
         }
     }
 
+    GroupHeader = <View> {
+        width: Fill,
+        height: 40,
+        padding: {
+            left: 10,
+        },
+        align: {
+            y: 0.5,
+        },
+
+        title = <Label> {
+            text: "",
+        }
+    }
+
     ImageGrid = {{ImageGrid}} {
+        draw_rubber_band: {
+            fn pixel(self) -> vec4 {
+                let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+                sdf.rect(0.0, 0.0, self.rect_size.x, self.rect_size.y);
+                sdf.fill_keep(#4a90e233);
+                sdf.stroke(#4a90e2, 1.0);
+                return sdf.result;
+            }
+        },
+
         rows = <PortalList> {
             flow: Down,
 
@@ -102,6 +319,7 @@ live_design! {
             },
 
             Row = <ImageGridRow> {}
+            Header = <GroupHeader> {}
         }
     }
 
@@ -109,6 +327,7 @@ live_design! {
         flow: Down,
 
         menu_bar = <MenuBar> {}
+        file_ops_bar = <FileOpsBar> {}
         image_grid = <ImageGrid> {}
     }
 
@@ -204,16 +423,381 @@ live_design! {
         }
     }
 
+    MetadataPanel = <View> {
+        width: 320,
+        height: Fill,
+        padding: 15,
+        show_bg: true,
+        draw_bg: {
+            color: #000000b0,
+        },
+
+        fields = <Label> {
+            width: Fill,
+            text: "",
+            draw_text: {
+                wrap: Word,
+            },
+        }
+    }
+
+    PixelReadout = <View> {
+        width: Fit,
+        height: Fit,
+        padding: 15,
+        show_bg: true,
+        draw_bg: {
+            color: #000000b0,
+        },
+
+        value = <Label> {
+            text: "",
+        }
+    }
+
+    EditBarButton = <MenuBarButton> {
+        grab_key_focus: false,
+    }
+
+    EditBar = <View> {
+        width: Fit,
+        height: Fit,
+        padding: 10,
+        spacing: 10,
+        align: {
+            y: 0.5,
+        },
+        show_bg: true,
+        draw_bg: {
+            color: #000000b0,
+        },
+
+        rotate_left_button = <EditBarButton> {
+            text: "Rotate Left",
+        }
+        rotate_right_button = <EditBarButton> {
+            text: "Rotate Right",
+        }
+        flip_horizontal_button = <EditBarButton> {
+            text: "Flip Horizontal",
+        }
+        flip_vertical_button = <EditBarButton> {
+            text: "Flip Vertical",
+        }
+        aspect_ratio = <DropDown> {
+            width: 100,
+        }
+        reset_edit_button = <EditBarButton> {
+            text: "Reset",
+        }
+        save_edit_button = <EditBarButton> {
+            text: "Save",
+        }
+        save_edit_as_new_button = <EditBarButton> {
+            text: "Save as New",
+        }
+    }
+
+    AdjustmentSlider = <Slider> {
+        width: Fill,
+        min: -1.0,
+        max: 1.0,
+        default: 0.0,
+        precision: 2,
+    }
+
+    AdjustmentsPanel = <View> {
+        width: 280,
+        height: Fit,
+        flow: Down,
+        padding: 15,
+        spacing: 10,
+        show_bg: true,
+        draw_bg: {
+            color: #000000b0,
+        },
+
+        exposure = <AdjustmentSlider> {
+            text: "Exposure",
+            min: -3.0,
+            max: 3.0,
+        }
+        contrast = <AdjustmentSlider> {
+            text: "Contrast",
+        }
+        saturation = <AdjustmentSlider> {
+            text: "Saturation",
+        }
+        temperature = <AdjustmentSlider> {
+            text: "Temperature",
+        }
+        tint = <AdjustmentSlider> {
+            text: "Tint",
+        }
+        vibrance = <AdjustmentSlider> {
+            text: "Vibrance",
+        }
+        <View> {
+            width: Fill,
+            height: Fit,
+            spacing: 10,
+
+            reset_adjustments_button = <EditBarButton> {
+                text: "Reset",
+            }
+            export_button = <EditBarButton> {
+                text: "Export",
+            }
+        }
+    }
+
+    ZoomImage = {{ZoomImage}} {
+        width: Fill,
+        height: Fill,
+
+        draw_crop: {
+            instance crop_x: 0.0
+            instance crop_y: 0.0
+            instance crop_width: 0.0
+            instance crop_height: 0.0
+
+            fn pixel(self) -> vec4 {
+                let size = vec2(self.crop_width, self.crop_height);
+                // This fragment relative to the crop rectangle, in pixels.
+                let pos = self.pos * self.rect_size
+                    - vec2(self.crop_x, self.crop_y);
+                if pos.x < 0.0 || pos.y < 0.0
+                    || pos.x > size.x || pos.y > size.y
+                {
+                    return vec4(0.0, 0.0, 0.0, 0.6);
+                }
+                let edge_distance = min(
+                    min(pos.x, pos.y),
+                    min(size.x - pos.x, size.y - pos.y)
+                );
+                if edge_distance < 1.5 {
+                    return #fff;
+                }
+                let thirds_distance = min(
+                    abs(pos - size / 3.0),
+                    abs(pos - size * 2.0 / 3.0)
+                );
+                if min(thirds_distance.x, thirds_distance.y) < 0.5 {
+                    // Translucent white, premultiplied.
+                    return vec4(0.5, 0.5, 0.5, 0.5);
+                }
+                return vec4(0.0);
+            }
+        }
+
+        draw_loupe: {
+            texture image: texture2d
+            instance center_x: 0.0
+            instance center_y: 0.0
+            instance image_width: 1.0
+            instance image_height: 1.0
+            instance pixel_size: 16.0
+
+            fn pixel(self) -> vec4 {
+                let image_size = vec2(self.image_width, self.image_height);
+                let center = vec2(self.center_x, self.center_y);
+                // The point of the image under this fragment, in image
+                // pixels, with the point under the cursor in the middle.
+                let pos = center
+                    + (self.pos - 0.5) * self.rect_size / self.pixel_size;
+                let texel = floor(pos);
+
+                let color = #222;
+                if texel.x >= 0.0 && texel.y >= 0.0
+                    && texel.x < image_size.x && texel.y < image_size.y
+                {
+                    // Sampling the center of a texel reads it as is, so the
+                    // magnified pixels keep their hard edges.
+                    color = sample2d(self.image, (texel + 0.5) / image_size);
+                }
+
+                let edge = fract(pos) * self.pixel_size;
+                let edge_distance = min(
+                    min(edge.x, edge.y),
+                    min(self.pixel_size - edge.x, self.pixel_size - edge.y)
+                );
+                if edge_distance < 0.5 {
+                    color = mix(color, #000, 0.4);
+                }
+                let center_texel = floor(center);
+                if texel.x == center_texel.x && texel.y == center_texel.y
+                    && edge_distance < 2.0
+                {
+                    color = #fff;
+                }
+
+                let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+                sdf.box(
+                    1.0,
+                    1.0,
+                    self.rect_size.x - 2.0,
+                    self.rect_size.y - 2.0,
+                    4.0
+                );
+                sdf.fill_keep(vec4(color.rgb, 1.0));
+                sdf.stroke(#fff, 1.0);
+                return sdf.result;
+            }
+        }
+
+        image = <Image> {
+            width: Fill,
+            height: Fill,
+            fit: Biggest,
+            source: (PLACEHOLDER)
+
+            draw_bg: {
+                instance exposure: 0.0
+                instance contrast: 0.0
+                instance saturation: 0.0
+                instance temperature: 0.0
+                instance tint: 0.0
+                instance vibrance: 0.0
+
+                // `Adjustments::apply_to_color` does the same on the CPU,
+                // so this has to be kept in step with it, and with the copy
+                // of this function in its tests, as nothing compares them.
+                fn pixel(self) -> vec4 {
+                    let color = self.get_color();
+                    let rgb = color.rgb * vec3(
+                        1.0 + 0.3 * self.temperature,
+                        1.0 - 0.3 * self.tint,
+                        1.0 - 0.3 * self.temperature
+                    );
+                    rgb = rgb * pow(2.0, self.exposure);
+                    rgb = (rgb - 0.5) * (1.0 + self.contrast) + 0.5;
+                    let luma = dot(rgb, vec3(0.2126, 0.7152, 0.0722));
+                    rgb = mix(
+                        vec3(luma, luma, luma),
+                        rgb,
+                        1.0 + self.saturation
+                    );
+                    let chroma = max(max(rgb.r, rgb.g), rgb.b)
+                        - min(min(rgb.r, rgb.g), rgb.b);
+                    luma = dot(rgb, vec3(0.2126, 0.7152, 0.0722));
+                    rgb = mix(
+                        vec3(luma, luma, luma),
+                        rgb,
+                        1.0 + self.vibrance * (1.0 - clamp(chroma, 0.0, 1.0))
+                    );
+                    rgb = clamp(rgb, vec3(0.0, 0.0, 0.0), vec3(1.0, 1.0, 1.0));
+                    return Pal::premul(vec4(rgb, color.a * self.opacity));
+                }
+            }
+        }
+    }
+
     Slideshow = <View> {
         flow: Overlay,
 
+        zoom_image = <ZoomImage> {}
+        overlay = <SlideshowOverlay> {}
+        status = <Label> {
+            margin: 10,
+            text: "",
+        }
+        metadata_panel = <View> {
+            visible: false,
+            align: {
+                x: 1.0,
+            },
+            // Leave the right arrow button uncovered.
+            padding: {
+                right: 50,
+            },
+
+            <MetadataPanel> {}
+        }
+        adjustments_panel = <View> {
+            visible: false,
+            align: {
+                y: 0.5,
+            },
+            // Leave the left arrow button uncovered.
+            padding: {
+                left: 50,
+            },
+
+            <AdjustmentsPanel> {}
+        }
+        edit_bar = <View> {
+            visible: false,
+            height: Fit,
+            align: {
+                x: 0.5,
+            },
+            padding: 10,
+
+            <EditBar> {}
+        }
+        pixel_readout = <View> {
+            visible: false,
+            align: {
+                y: 1.0,
+            },
+            // Leave the left arrow button uncovered.
+            padding: {
+                left: 50,
+                bottom: 10,
+            },
+
+            <PixelReadout> {}
+        }
+    }
+
+    ComparePane = <View> {
+        flow: Overlay,
+
         image = <Image> {
             width: Fill,
             height: Fill,
             fit: Biggest,
             source: (PLACEHOLDER)
         }
-        overlay = <SlideshowOverlay> {}
+        caption = <Label> {
+            margin: 10,
+            text: "",
+        }
+    }
+
+    CompareView = {{CompareView}} {
+        width: Fill,
+        height: Fill,
+        flow: Overlay,
+
+        draw_divider: {
+            fn pixel(self) -> vec4 {
+                return #fff;
+            }
+        }
+
+        first = <ComparePane> {}
+        second = <ComparePane> {}
+    }
+
+    Compare = <View> {
+        flow: Overlay,
+
+        compare_view = <CompareView> {}
+        compare_overlay = <View> {
+            cursor: Arrow,
+            capture_overload: true,
+        }
+        <View> {
+            align: {
+                y: 1.0,
+            },
+
+            <Label> {
+                margin: 10,
+                text: "← → Other image   S Swap keeper   W Wipe   Enter Open keeper   Esc Back",
+            }
+        }
     }
 
     App = {{App}} {
@@ -225,6 +809,7 @@ live_design! {
 
                         image_browser = <ImageBrowser> {}
                         slideshow = <Slideshow> {}
+                        compare = <Compare> {}
                     }
                 }
             }
@@ -233,6 +818,25 @@ live_design! {
     }
 }
 
+/// What the folder picked in the folder dialog will be used for.
+#[derive(Clone, Debug, Default)]
+enum FolderDialogPurpose {
+    #[default]
+    OpenFolder,
+    MoveTo(Vec<PathBuf>),
+    CopyTo(Vec<PathBuf>),
+}
+
+/// Two images shown together, to pick the one to keep.
+struct Comparison {
+    paths: [PathBuf; 2],
+    /// Which of the two images is the keeper. The other one can be swapped
+    /// for the images around it.
+    keeper_idx: usize,
+    /// The page compare mode was opened from, to go back to.
+    return_page: LiveId,
+}
+
 #[derive(Live)]
 struct App {
     #[live]
@@ -240,49 +844,1445 @@ struct App {
     #[live]
     placeholder: LiveDependency,
     #[rust]
+    config: Config,
+    #[rust]
+    settings: Settings,
+    #[rust]
+    recent_folders: RecentFolders,
+    #[rust]
+    dir_watcher: Option<DirWatcher>,
+    #[rust]
+    textures: TextureCache,
+    #[rust]
+    pending_images: HashSet<PathBuf>,
+    #[rust]
+    folder_dialog_purpose: FolderDialogPurpose,
+    /// The changes made by the last file operation, for undo.
+    #[rust]
+    last_file_op: Option<Vec<Change>>,
+    #[rust]
+    metadata_panel_is_visible: bool,
+    /// The metadata last read for the panel, and the image it belongs to.
+    #[rust]
+    metadata: Option<(PathBuf, Result<Metadata, String>)>,
+    /// The zoom each image was left at, when the zoom is kept per image.
+    #[rust]
+    zooms: HashMap<PathBuf, Zoom>,
+    /// The image the slideshow is zoomed for.
+    #[rust]
+    zoomed_image_path: Option<PathBuf>,
+    #[rust]
+    loupe_is_visible: bool,
+    /// The pixels of the current image, kept while the loupe is shown so
+    /// the pixel under it can be read out, and while the image is edited
+    /// so it can be turned.
+    #[rust]
+    slideshow_pixels: Option<(PathBuf, DecodedImage)>,
+    #[rust]
+    hovered_pixel: Option<(usize, usize)>,
+    #[rust]
+    is_editing: bool,
+    /// The edit previewed on the current image, which is thrown away when
+    /// moving on to another image without saving it.
+    #[rust]
+    edit: Edit,
+    #[rust]
+    adjustments_panel_is_visible: bool,
+    /// The adjustments of the current image, from its recipe.
+    #[rust]
+    adjustments: Adjustments,
+    /// Adjustments that are shown but not yet written to the recipe of the
+    /// image they belong to, which happens once `recipe_timer` fires.
+    #[rust]
+    unsaved_recipe: Option<(PathBuf, Adjustments)>,
+    #[rust]
+    recipe_timer: Timer,
+    #[rust]
+    comparison: Option<Comparison>,
+    #[rust]
+    tags: TagStore,
+    /// The tags offered to complete the one being typed.
+    #[rust]
+    tag_suggestions: Vec<String>,
+    /// The collection shown instead of the image folder, if any.
+    #[rust]
+    collection: Option<Collection>,
+    #[rust]
     state: State,
 }
 
 impl App {
     fn load_image_paths(&mut self, cx: &mut Cx, dir: &Path) {
-        self.state.image_paths.clear();
+        let result = scan::scan_dir(dir, self.config.scan_options());
+        for error in &result.errors {
+            error!("Failed to scan {}", error);
+        }
+        if self.tags.reconcile(&result.image_paths)
+            && let Err(error) = self.tags.save()
+        {
+            error!("Failed to save tags: {}", error);
+        }
+        self.collection = None;
+        self.set_image_paths(cx, result.image_paths, result.ratings, dir);
 
-        for entry in dir.read_dir().unwrap() {
-            let path = entry.unwrap().path();
-            if path.is_file() {
-                self.state.image_paths.push(path);
+        self.dir_watcher = None;
+        match DirWatcher::new(cx, dir, self.config.scan_options()) {
+            Ok(dir_watcher) => self.dir_watcher = Some(dir_watcher),
+            Err(error) => {
+                error!("Failed to watch {}: {}", dir.display(), error)
             }
         }
 
         self.set_current_image(cx, 0);
     }
 
-    fn set_current_image(&mut self, cx: &mut Cx, image_idx: usize) {
-        self.state.current_image_idx = image_idx;
-
-        let image = self.ui.image(id!(slideshow.image));
-        if let Some(path) = self.state.image_paths.get(image_idx) {
-            image.load_image_file_by_path_async(cx, &path).unwrap();
+    /// Shows the images of a collection instead of those of the image
+    /// folder, or the folder again if the query is empty.
+    fn open_collection(&mut self, cx: &mut Cx, query: &str) {
+        let collection = Collection::parse(query);
+        if collection.is_empty() {
+            let dir = self.config.image_dir.clone();
+            self.load_image_paths(cx, &dir);
+            self.show_status(cx, "");
         } else {
-            let placeholder = self.placeholder.as_str();
-            image.load_image_dep_by_path(cx, placeholder).unwrap();
+            let image_paths = self.tags.query(&collection);
+            let mut ratings = Ratings::default();
+            for path in &image_paths {
+                match Rating::load(path) {
+                    Ok(rating) => ratings.set(path, rating),
+                    Err(error) => error!(
+                        "Failed to read rating of {}: {}",
+                        path.display(),
+                        error
+                    ),
+                }
+            }
+            let count = image_paths.len();
+            self.collection = Some(collection);
+            self.set_image_paths(cx, image_paths, ratings, Path::new(""));
+            self.dir_watcher = None;
+            self.set_current_image(cx, 0);
+            self.show_status(
+                cx,
+                &format!(
+                    "{} image{} in the collection",
+                    count,
+                    if count == 1 { "" } else { "s" }
+                ),
+            );
         }
 
+        self.ui
+            .portal_list(id!(image_grid.rows))
+            .set_first_id_and_scroll(0, 0.0);
+        self.ui
+            .page_flip(id!(page_flip))
+            .set_active_page(cx, live_id!(image_browser));
         self.ui.redraw(cx);
     }
 
-    fn go_to_previous_image(&mut self, cx: &mut Cx) {
-        if self.state.current_image_idx > 0 {
-            self.set_current_image(cx, self.state.current_image_idx - 1);
-        }
+    /// Replaces the images shown, either with those of a folder or with
+    /// those of a collection, in which case `image_dir` is empty.
+    fn set_image_paths(
+        &mut self,
+        cx: &mut Cx,
+        image_paths: Vec<PathBuf>,
+        ratings: Ratings,
+        image_dir: &Path,
+    ) {
+        self.state.image_paths = image_paths;
+        self.state.ratings = ratings;
+        self.state.image_dir = image_dir.to_path_buf();
+        self.state.highlighted_image_idx = None;
+        self.state.focused_image_idx = None;
+        self.state.selection.clear();
+        self.state.image_infos.clear();
+        self.state.image_infos.request(cx, &self.state.image_paths);
+        self.state.sort_images();
+        self.state.thumbnails.clear();
+        self.textures.clear();
+        self.state
+            .decode_pool
+            .cancel(|key| key.kind == JobKind::Image);
+        self.pending_images.clear();
+        self.zooms.clear();
     }
 
-    fn go_to_next_image(&mut self, cx: &mut Cx) {
-        if self.state.current_image_idx + 1 < self.state.num_images() {
-            self.set_current_image(cx, self.state.current_image_idx + 1);
+    /// Adds the tags typed in the tag input to the selected images, or
+    /// removes them.
+    fn tag_selection(&mut self, cx: &mut Cx, is_adding: bool) {
+        let tags = tags::parse_tags(&self.ui.text_input(id!(tag_input)).text());
+        let paths = self.state.selected_paths();
+        if tags.is_empty() || paths.is_empty() {
+            self.show_status(cx, "Select images and type tags to tag them");
+            return;
         }
-    }
-}
+
+        let mut status = format!(
+            "{} {} image{}",
+            if is_adding { "Tagged" } else { "Untagged" },
+            paths.len(),
+            if paths.len() == 1 { "" } else { "s" }
+        );
+        if is_adding {
+            if let Err(error) = self.tags.add_tags(&paths, &tags) {
+                status = format!("Failed to tag some images: {}", error);
+            }
+        } else {
+            self.tags.remove_tags(&paths, &tags);
+        }
+        if let Err(error) = self.tags.save() {
+            status = format!("Failed to save tags: {}", error);
+        }
+        self.show_status(cx, &status);
+        self.ui.text_input(id!(tag_input)).set_text(cx, "");
+        self.update_tag_suggestions(cx, "");
+    }
+
+    /// Offers the tags in use that complete the last one in `text`.
+    fn update_tag_suggestions(&mut self, cx: &mut Cx, text: &str) {
+        let prefix = text.rsplit(',').next().unwrap_or_default().trim();
+        self.tag_suggestions = if prefix.is_empty() {
+            Vec::new()
+        } else {
+            self.tags
+                .suggestions(prefix, TAG_SUGGESTION_BUTTONS.len())
+                .into_iter()
+                .filter(|tag| *tag != prefix)
+                .collect()
+        };
+        for (button_idx, button) in TAG_SUGGESTION_BUTTONS.iter().enumerate() {
+            let button = self.ui.button(button);
+            match self.tag_suggestions.get(button_idx) {
+                Some(tag) => {
+                    button.set_text(cx, tag);
+                    button.set_visible(cx, true);
+                }
+                None => button.set_visible(cx, false),
+            }
+        }
+        self.ui.redraw(cx);
+    }
+
+    /// Completes the last tag in the tag input with a suggestion.
+    fn accept_tag_suggestion(&mut self, cx: &mut Cx, suggestion_idx: usize) {
+        let Some(tag) = self.tag_suggestions.get(suggestion_idx).cloned()
+        else {
+            return;
+        };
+        let tag_input = self.ui.text_input(id!(tag_input));
+        let text = tag_input.text();
+        let mut tags = match text.rsplit_once(',') {
+            Some((done, _)) => format!("{}, ", done.trim_end()),
+            None => String::new(),
+        };
+        tags.push_str(&tag);
+        tag_input.set_text(cx, &tags);
+        self.update_tag_suggestions(cx, &tags);
+    }
+
+    fn set_current_image(&mut self, cx: &mut Cx, image_idx: usize) {
+        self.state.current_image_idx = image_idx;
+
+        if let Some(path) = self.state.visible_image_paths.get(image_idx) {
+            let path = path.clone();
+            self.load_slideshow_image(cx, &path);
+        } else {
+            self.show_slideshow_placeholder(cx);
+        }
+        self.update_metadata_panel(cx);
+
+        self.ui.redraw(cx);
+    }
+
+    fn toggle_metadata_panel(&mut self, cx: &mut Cx) {
+        self.metadata_panel_is_visible = !self.metadata_panel_is_visible;
+        self.ui
+            .view(id!(metadata_panel))
+            .set_visible(cx, self.metadata_panel_is_visible);
+        self.update_metadata_panel(cx);
+        self.ui.redraw(cx);
+    }
+
+    fn update_metadata_panel(&mut self, cx: &mut Cx) {
+        if !self.metadata_panel_is_visible {
+            return;
+        }
+        let text = match self.state.current_image_path().cloned() {
+            Some(path) => {
+                let name = path.file_name().unwrap_or_default();
+                let mut text = name.to_string_lossy().into_owned();
+                match &self.metadata {
+                    Some((metadata_path, result)) if *metadata_path == path => {
+                        match result {
+                            Ok(metadata) => {
+                                for (label, value) in metadata.fields() {
+                                    text +=
+                                        &format!("\n\n{}\n{}", label, value);
+                                }
+                            }
+                            Err(error) => {
+                                text += &format!(
+                                    "\n\nFailed to read metadata: {}",
+                                    error
+                                )
+                            }
+                        }
+                    }
+                    // Reading the header of an image can take a while, so it
+                    // is done on the decode pool, which posts a
+                    // `MetadataAction` once it is done.
+                    _ => {
+                        let key = JobKey {
+                            path: path.clone(),
+                            kind: JobKind::Metadata,
+                        };
+                        let job_path = path.clone();
+                        self.state
+                            .decode_pool
+                            .cancel(|key| key.kind == JobKind::Metadata);
+                        self.state.decode_pool.submit(
+                            cx,
+                            key,
+                            Priority::Current,
+                            move || Metadata::read_and_post(job_path),
+                        );
+                    }
+                }
+                let tags = self.tags.tags(&path);
+                if !tags.is_empty() {
+                    text += &format!("\n\nTags\n{}", tags.join(", "));
+                }
+                text
+            }
+            None => String::new(),
+        };
+        self.ui
+            .label(id!(metadata_panel.fields))
+            .set_text(cx, &text);
+    }
+
+    fn load_slideshow_image(&mut self, cx: &mut Cx, path: &Path) {
+        self.switch_zoom(cx, path);
+        if let Some((texture, size)) = self.textures.get(path) {
+            self.set_slideshow_texture(cx, texture, Some(size));
+        } else {
+            // Until the full image is decoded, show its thumbnail if we have
+            // one, at the size of the full image so the zoom carries over.
+            if let Some(thumbnail) = self.state.thumbnails.cached_texture(path)
+                && let Some(mut zoom_image) =
+                    self.ui.widget(id!(zoom_image)).borrow_mut::<ZoomImage>()
+            {
+                let size =
+                    self.state.image_infos.get(path).and_then(|info| info.size);
+                zoom_image.set_thumbnail(cx, thumbnail, size);
+            }
+            self.request_image(cx, path, Priority::Current);
+        }
+        self.request_slideshow_pixels(cx);
+        self.reset_edit(cx);
+        self.load_adjustments(cx, path);
+
+        self.prefetch_neighbours(cx);
+    }
+
+    /// Makes sure the pixels of the current image are at hand while the
+    /// loupe is shown or the image is edited. Only textures are cached, so
+    /// an image that was decoded before is decoded again.
+    fn request_slideshow_pixels(&mut self, cx: &mut Cx) {
+        let current_image_path = self.state.current_image_path().cloned();
+        if self
+            .slideshow_pixels
+            .as_ref()
+            .is_some_and(|(path, _)| Some(path) != current_image_path.as_ref())
+        {
+            self.slideshow_pixels = None;
+        }
+        if (self.loupe_is_visible || self.is_editing)
+            && self.slideshow_pixels.is_none()
+            && let Some(path) = current_image_path
+        {
+            self.request_image(cx, &path, Priority::Current);
+        }
+        self.update_pixel_readout(cx);
+    }
+
+    fn request_image(&mut self, cx: &mut Cx, path: &Path, priority: Priority) {
+        let key = JobKey {
+            path: path.to_path_buf(),
+            kind: JobKind::Image,
+        };
+        if self.pending_images.contains(path) {
+            // The image is already queued or being decoded, so at most it
+            // needs to be bumped ahead of the queue.
+            self.state.decode_pool.raise_priority(&key, priority);
+            return;
+        }
+
+        self.pending_images.insert(path.to_path_buf());
+        let path = path.to_path_buf();
+        self.state
+            .decode_pool
+            .submit(cx, key, priority, move || decode::decode_and_post(path));
+    }
+
+    fn prefetch_neighbours(&mut self, cx: &mut Cx) {
+        let window = self.prefetch_window();
+        let current_image_path = self.state.current_image_path().cloned();
+        let keep: HashSet<&PathBuf> =
+            window.iter().chain(current_image_path.iter()).collect();
+        let cancelled = self.state.decode_pool.cancel(|key| {
+            key.kind == JobKind::Image && !keep.contains(&key.path)
+        });
+        for key in cancelled {
+            self.pending_images.remove(&key.path);
+        }
+
+        for path in &window {
+            if !self.textures.contains(path) {
+                self.request_image(cx, path, Priority::Prefetch);
+            }
+        }
+    }
+
+    /// Returns the images around the current one that should be decoded
+    /// ahead of time, nearest first, alternating between the next and the
+    /// previous image.
+    fn prefetch_window(&self) -> Vec<PathBuf> {
+        let current_image_idx = self.state.current_image_idx;
+        let mut paths = Vec::new();
+        for offset in 1..=self.config.prefetch_window {
+            if let Some(path) = self
+                .state
+                .visible_image_paths
+                .get(current_image_idx + offset)
+            {
+                paths.push(path.clone());
+            }
+            if let Some(image_idx) = current_image_idx.checked_sub(offset)
+                && let Some(path) =
+                    self.state.visible_image_paths.get(image_idx)
+            {
+                paths.push(path.clone());
+            }
+        }
+        paths
+    }
+
+    fn show_slideshow_placeholder(&mut self, cx: &mut Cx) {
+        if let Some(mut zoom_image) =
+            self.ui.widget(id!(zoom_image)).borrow_mut::<ZoomImage>()
+        {
+            zoom_image.show_placeholder(cx, self.placeholder.as_str());
+        }
+    }
+
+    fn set_slideshow_texture(
+        &mut self,
+        cx: &mut Cx,
+        texture: Texture,
+        size: Option<ImageSize>,
+    ) {
+        if let Some(mut zoom_image) =
+            self.ui.widget(id!(zoom_image)).borrow_mut::<ZoomImage>()
+        {
+            zoom_image.set_texture(cx, texture, size);
+        }
+    }
+
+    fn toggle_loupe(&mut self, cx: &mut Cx) {
+        self.loupe_is_visible = !self.loupe_is_visible;
+        self.hovered_pixel = None;
+        if let Some(mut zoom_image) =
+            self.ui.widget(id!(zoom_image)).borrow_mut::<ZoomImage>()
+        {
+            zoom_image.set_loupe_visible(cx, self.loupe_is_visible);
+        }
+        self.ui
+            .view(id!(pixel_readout))
+            .set_visible(cx, self.loupe_is_visible);
+        if !self.loupe_is_visible && !self.is_editing {
+            self.slideshow_pixels = None;
+        }
+        self.request_slideshow_pixels(cx);
+        self.ui.redraw(cx);
+    }
+
+    /// The color of the pixel under the loupe, which is a pixel of the
+    /// image as it is turned by the edit. It is read from the decoded
+    /// image, so there is none while only the thumbnail is shown.
+    fn hovered_pixel_color(&self) -> Option<[u8; 4]> {
+        let (x, y) = self.hovered_pixel?;
+        let (path, pixels) = self.slideshow_pixels.as_ref()?;
+        if Some(path) != self.state.current_image_path() {
+            return None;
+        }
+        let orientation = self.edit.orientation;
+        let (width, height) = if orientation.swaps_dimensions() {
+            (pixels.height, pixels.width)
+        } else {
+            (pixels.width, pixels.height)
+        };
+        // The pixel may have been hovered before the image was turned.
+        if x >= width || y >= height {
+            return None;
+        }
+        let (x, y) =
+            orientation.source_pixel(x, y, pixels.width, pixels.height);
+        pixels.rgba(x, y)
+    }
+
+    /// The coordinates and color of the pixel under the loupe, as text.
+    fn hovered_pixel_text(&self) -> Option<String> {
+        let (x, y) = self.hovered_pixel?;
+        let [r, g, b, a] = self.hovered_pixel_color()?;
+        Some(format!(
+            "x {}, y {}\nRGBA {}, {}, {}, {}\n{}",
+            x,
+            y,
+            r,
+            g,
+            b,
+            a,
+            hex_color([r, g, b, a])
+        ))
+    }
+
+    fn update_pixel_readout(&mut self, cx: &mut Cx) {
+        if !self.loupe_is_visible {
+            return;
+        }
+        let text = match self.hovered_pixel {
+            Some(_) => self
+                .hovered_pixel_text()
+                .unwrap_or_else(|| "Loading pixels…".to_string()),
+            None => "Hover over the image".to_string(),
+        };
+        self.ui.label(id!(pixel_readout.value)).set_text(cx, &text);
+    }
+
+    fn copy_hovered_pixel(&mut self, cx: &mut Cx) {
+        let Some(rgba) = self.hovered_pixel_color() else {
+            return;
+        };
+        let hex = hex_color(rgba);
+        cx.copy_to_clipboard(&hex);
+        self.show_status(cx, &format!("Copied {}", hex));
+    }
+
+    /// Restores the zoom of the image at `path` when moving on to it,
+    /// keeping the zoom of the image before if the zoom is kept per image.
+    fn switch_zoom(&mut self, cx: &mut Cx, path: &Path) {
+        if self.zoomed_image_path.as_deref() == Some(path) {
+            return;
+        }
+        let zoom_image = self.ui.widget(id!(zoom_image));
+        let Some(mut zoom_image) = zoom_image.borrow_mut::<ZoomImage>() else {
+            return;
+        };
+        if self.settings.zoom_mode == ZoomMode::PerImage
+            && let Some(previous_path) = self.zoomed_image_path.take()
+        {
+            self.zooms.insert(previous_path, zoom_image.zoom);
+        }
+        let zoom = match self.settings.zoom_mode {
+            ZoomMode::Reset => Zoom::Fit,
+            ZoomMode::PerImage => {
+                self.zooms.get(path).copied().unwrap_or_default()
+            }
+        };
+        zoom_image.set_zoom(cx, zoom);
+        self.zoomed_image_path = Some(path.to_path_buf());
+    }
+
+    fn zoom_slideshow_image(&mut self, cx: &mut Cx, factor: Option<f64>) {
+        if let Some(mut zoom_image) =
+            self.ui.widget(id!(zoom_image)).borrow_mut::<ZoomImage>()
+        {
+            match factor {
+                Some(factor) => zoom_image.zoom_by(cx, factor),
+                None => zoom_image.set_zoom(cx, Zoom::Fit),
+            }
+        }
+    }
+
+    fn handle_decode_action(&mut self, cx: &mut Cx, action: &DecodeAction) {
+        let path = action.path();
+        self.pending_images.remove(path);
+
+        let current_image_path = self.state.current_image_path().cloned();
+        let is_current = current_image_path.as_deref() == Some(path);
+        let is_compared = self.comparison.as_ref().is_some_and(|comparison| {
+            comparison.paths.iter().any(|p| p == path)
+        });
+        if !is_current
+            && !is_compared
+            && !self.prefetch_window().iter().any(|p| p == path)
+        {
+            return;
+        }
+
+        match action {
+            DecodeAction::Decoded { image, .. } => {
+                let Some(decoded_image) = image.lock().unwrap().take() else {
+                    return;
+                };
+                let size = ImageSize {
+                    width: decoded_image.width,
+                    height: decoded_image.height,
+                };
+                if is_current && (self.loupe_is_visible || self.is_editing) {
+                    self.slideshow_pixels =
+                        Some((path.to_path_buf(), decoded_image.clone()));
+                    self.update_pixel_readout(cx);
+                }
+                let texture = decoded_image.into_texture(cx);
+                self.textures.insert(
+                    path.to_path_buf(),
+                    texture.clone(),
+                    size,
+                    current_image_path.as_deref(),
+                );
+                if is_compared {
+                    self.set_compared_texture(cx, path, &texture, size);
+                }
+                if is_current {
+                    self.set_slideshow_texture(cx, texture, Some(size));
+                    if self.is_editing {
+                        self.update_edit_preview(cx);
+                    }
+                    self.ui.redraw(cx);
+                }
+            }
+            DecodeAction::Failed { error, .. } => {
+                error!("Failed to decode {}: {}", path.display(), error);
+                if is_current {
+                    self.show_slideshow_placeholder(cx);
+                }
+            }
+        }
+    }
+
+    fn toggle_edit_bar(&mut self, cx: &mut Cx) {
+        self.is_editing = !self.is_editing;
+        self.ui.view(id!(edit_bar)).set_visible(cx, self.is_editing);
+        if !self.loupe_is_visible && !self.is_editing {
+            self.slideshow_pixels = None;
+        }
+        self.request_slideshow_pixels(cx);
+        self.reset_edit(cx);
+        self.ui.redraw(cx);
+    }
+
+    fn reset_edit(&mut self, cx: &mut Cx) {
+        self.edit = Edit::default();
+        self.update_edit_preview(cx);
+    }
+
+    fn turn_edited_image(&mut self, cx: &mut Cx, orientation: Orientation) {
+        if !self.is_editing {
+            return;
+        }
+        // The crop rectangle no longer fits the image once it is turned.
+        self.edit = Edit {
+            orientation: self.edit.orientation.then(orientation),
+            crop: None,
+        };
+        self.update_edit_preview(cx);
+        self.zoom_slideshow_image(cx, None);
+    }
+
+    /// Shows the current image turned by the edit, with the crop rectangle
+    /// over it while it is edited. The image is only turned once its pixels
+    /// are decoded, and can't be cropped until then.
+    fn update_edit_preview(&mut self, cx: &mut Cx) {
+        let Some(path) = self.state.current_image_path().cloned() else {
+            return;
+        };
+        let preview = if self.edit.orientation == Orientation::Normal {
+            self.textures.get(&path)
+        } else {
+            self.slideshow_pixels.as_ref().map(|(_, pixels)| {
+                let image = Edit {
+                    crop: None,
+                    ..self.edit
+                }
+                .apply(pixels.clone());
+                let size = ImageSize {
+                    width: image.width,
+                    height: image.height,
+                };
+                (image.into_texture(cx), size)
+            })
+        };
+        let is_ready = preview.is_some();
+        if let Some((texture, size)) = preview {
+            self.set_slideshow_texture(cx, texture, Some(size));
+        }
+        if let Some(mut zoom_image) =
+            self.ui.widget(id!(zoom_image)).borrow_mut::<ZoomImage>()
+        {
+            zoom_image.set_crop(cx, self.edit.crop);
+            zoom_image.set_crop_enabled(cx, self.is_editing && is_ready);
+        }
+    }
+
+    fn set_crop_ratio(&mut self, cx: &mut Cx, ratio: AspectRatio) {
+        if let Some(mut zoom_image) =
+            self.ui.widget(id!(zoom_image)).borrow_mut::<ZoomImage>()
+        {
+            self.edit.crop = zoom_image.set_crop_ratio(cx, ratio);
+        }
+    }
+
+    fn toggle_adjustments_panel(&mut self, cx: &mut Cx) {
+        self.adjustments_panel_is_visible = !self.adjustments_panel_is_visible;
+        self.ui
+            .view(id!(adjustments_panel))
+            .set_visible(cx, self.adjustments_panel_is_visible);
+        self.ui.redraw(cx);
+    }
+
+    fn adjustment_slider(&self, adjustment: Adjustment) -> SliderRef {
+        let id = match adjustment {
+            Adjustment::Exposure => id!(exposure),
+            Adjustment::Contrast => id!(contrast),
+            Adjustment::Saturation => id!(saturation),
+            Adjustment::Temperature => id!(temperature),
+            Adjustment::Tint => id!(tint),
+            Adjustment::Vibrance => id!(vibrance),
+        };
+        self.ui.slider(id)
+    }
+
+    fn load_adjustments(&mut self, cx: &mut Cx, path: &Path) {
+        self.save_recipe(cx);
+        let adjustments = Adjustments::load(path).unwrap_or_else(|error| {
+            error!("Failed to load adjustments: {}", error);
+            Adjustments::default()
+        });
+        self.show_adjustments(cx, adjustments);
+    }
+
+    /// Shows `adjustments` on the sliders and the slideshow image.
+    fn show_adjustments(&mut self, cx: &mut Cx, adjustments: Adjustments) {
+        self.adjustments = adjustments;
+        for adjustment in Adjustment::ALL {
+            self.adjustment_slider(adjustment)
+                .set_value(cx, adjustments.get(adjustment) as f64);
+        }
+        if let Some(mut zoom_image) =
+            self.ui.widget(id!(zoom_image)).borrow_mut::<ZoomImage>()
+        {
+            zoom_image.set_adjustments(cx, adjustments);
+        }
+    }
+
+    /// Shows `adjustments` and keeps them in the recipe of the current image.
+    /// The recipe is written once they stop changing, rather than on every
+    /// step of a slider being dragged.
+    fn set_adjustments(&mut self, cx: &mut Cx, adjustments: Adjustments) {
+        let Some(path) = self.state.current_image_path().cloned() else {
+            return;
+        };
+        self.show_adjustments(cx, adjustments);
+        self.unsaved_recipe = Some((path, adjustments));
+        cx.stop_timer(self.recipe_timer);
+        self.recipe_timer = cx.start_timeout(RECIPE_SAVE_DELAY);
+    }
+
+    /// Writes the adjustments left by `set_adjustments` to their recipe, if
+    /// that hasn't happened yet.
+    fn save_recipe(&mut self, cx: &mut Cx) {
+        cx.stop_timer(self.recipe_timer);
+        let Some((path, adjustments)) = self.unsaved_recipe.take() else {
+            return;
+        };
+        if let Err(error) = adjustments.save(&path) {
+            error!(
+                "Failed to save adjustments of {}: {}",
+                path.display(),
+                error
+            );
+        }
+    }
+
+    fn save_edit(&mut self, cx: &mut Cx, target: SaveTarget) {
+        self.save_recipe(cx);
+        let Some(path) = self.state.current_image_path().cloned() else {
+            return;
+        };
+        if self.edit.is_empty() && self.adjustments.is_identity() {
+            self.show_status(cx, "Nothing to save");
+            return;
+        }
+        edit::save(cx, path, self.edit, self.adjustments, target);
+        self.show_status(cx, "Saving…");
+    }
+
+    fn handle_edit_action(&mut self, cx: &mut Cx, action: &EditAction) {
+        let (to, is_tag_only) = match &action.result {
+            Ok(saved) => (saved.path.clone(), saved.is_tag_only),
+            Err(error) => {
+                let status = format!(
+                    "Failed to save {}: {}",
+                    action.path.display(),
+                    error
+                );
+                self.show_status(cx, &status);
+                return;
+            }
+        };
+        let is_current = self.state.current_image_path() == Some(&action.path);
+        if to == action.path {
+            // Everything known about the image was read from the file as it
+            // was before it was overwritten.
+            self.state.thumbnails.remove(&to);
+            self.textures.remove(&to);
+            self.state.image_infos.remove(&to);
+            self.metadata = None;
+            self.state.image_infos.request(cx, &self.state.image_paths);
+            if is_current {
+                self.slideshow_pixels = None;
+                self.load_slideshow_image(cx, &to);
+                self.zoom_slideshow_image(cx, None);
+            }
+        } else {
+            self.apply_file_effects(cx, &[Effect::Added(to.clone())]);
+            if is_current {
+                self.reset_edit(cx);
+            }
+        }
+        let name = to.file_name().unwrap_or_default().to_string_lossy();
+        let status = if is_tag_only {
+            format!("Saved {}, turned by its orientation tag only", name)
+        } else {
+            format!("Saved {}", name)
+        };
+        self.show_status(cx, &status);
+    }
+
+    fn compare_selection(&mut self, cx: &mut Cx) {
+        let paths = self.state.selected_paths();
+        let paths = match paths.as_slice() {
+            [first, second] => [first.clone(), second.clone()],
+            [path] => match self.neighbour_of(path) {
+                Some(neighbour) => [path.clone(), neighbour],
+                None => {
+                    self.show_status(cx, "No other image to compare with");
+                    return;
+                }
+            },
+            _ => {
+                self.show_status(cx, "Select two images to compare");
+                return;
+            }
+        };
+        self.open_compare(cx, paths, live_id!(image_browser));
+    }
+
+    fn compare_current_image(&mut self, cx: &mut Cx) {
+        let Some(path) = self.state.current_image_path().cloned() else {
+            return;
+        };
+        let Some(neighbour) = self.neighbour_of(&path) else {
+            self.show_status(cx, "No other image to compare with");
+            return;
+        };
+        self.open_compare(cx, [path, neighbour], live_id!(slideshow));
+    }
+
+    /// The image after the one at `path`, or the one before it if it is the
+    /// last.
+    fn neighbour_of(&self, path: &Path) -> Option<PathBuf> {
+        let image_idx = self.state.image_idx(path)?;
+        let paths = &self.state.visible_image_paths;
+        paths
+            .get(image_idx + 1)
+            .or_else(|| paths.get(image_idx.checked_sub(1)?))
+            .cloned()
+    }
+
+    fn open_compare(
+        &mut self,
+        cx: &mut Cx,
+        paths: [PathBuf; 2],
+        return_page: LiveId,
+    ) {
+        self.comparison = Some(Comparison {
+            paths,
+            keeper_idx: 0,
+            return_page,
+        });
+        if let Some(mut compare_view) = self
+            .ui
+            .widget(id!(compare_view))
+            .borrow_mut::<CompareView>()
+        {
+            compare_view.set_zoom(cx, Zoom::Fit);
+        }
+        self.show_compared_image(cx, 0);
+        self.show_compared_image(cx, 1);
+        self.update_compare_captions(cx);
+        self.ui
+            .page_flip(id!(page_flip))
+            .set_active_page(cx, live_id!(compare));
+        self.ui.view(id!(compare_overlay)).set_key_focus(cx);
+    }
+
+    /// Shows the image of a pane, or its thumbnail until it is decoded.
+    fn show_compared_image(&mut self, cx: &mut Cx, pane_idx: usize) {
+        let Some(comparison) = &self.comparison else {
+            return;
+        };
+        let path = comparison.paths[pane_idx].clone();
+        let image = match self.textures.get(&path) {
+            Some((texture, size)) => Some((texture, Some(size))),
+            None => {
+                self.request_image(cx, &path, Priority::Current);
+                self.state
+                    .thumbnails
+                    .cached_texture(&path)
+                    .map(|thumbnail| {
+                        let size = self
+                            .state
+                            .image_infos
+                            .get(&path)
+                            .and_then(|info| info.size);
+                        (thumbnail, size)
+                    })
+            }
+        };
+        let compare_view = self.ui.widget(id!(compare_view));
+        let Some(mut compare_view) = compare_view.borrow_mut::<CompareView>()
+        else {
+            return;
+        };
+        match image {
+            Some((texture, size)) => {
+                compare_view.set_image(cx, pane_idx, &texture, size)
+            }
+            None => compare_view.show_placeholder(
+                cx,
+                pane_idx,
+                self.placeholder.as_str(),
+            ),
+        }
+    }
+
+    fn set_compared_texture(
+        &mut self,
+        cx: &mut Cx,
+        path: &Path,
+        texture: &Texture,
+        size: ImageSize,
+    ) {
+        let Some(comparison) = &self.comparison else {
+            return;
+        };
+        let compare_view = self.ui.widget(id!(compare_view));
+        let Some(mut compare_view) = compare_view.borrow_mut::<CompareView>()
+        else {
+            return;
+        };
+        for (pane_idx, pane_path) in comparison.paths.iter().enumerate() {
+            if pane_path == path {
+                compare_view.set_image(cx, pane_idx, texture, Some(size));
+            }
+        }
+    }
+
+    fn update_compare_captions(&mut self, cx: &mut Cx) {
+        let Some(comparison) = &self.comparison else {
+            return;
+        };
+        let compare_view = self.ui.widget(id!(compare_view));
+        let Some(mut compare_view) = compare_view.borrow_mut::<CompareView>()
+        else {
+            return;
+        };
+        for (pane_idx, path) in comparison.paths.iter().enumerate() {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let caption = if pane_idx == comparison.keeper_idx {
+                format!("{} (keeper)", name)
+            } else {
+                name.into_owned()
+            };
+            compare_view.set_caption(cx, pane_idx, &caption);
+        }
+    }
+
+    fn swap_keeper(&mut self, cx: &mut Cx) {
+        if let Some(comparison) = &mut self.comparison {
+            comparison.keeper_idx = 1 - comparison.keeper_idx;
+        }
+        self.update_compare_captions(cx);
+    }
+
+    /// Swaps the image that is not the keeper for the one `offset` images
+    /// away from it, skipping the keeper.
+    fn step_challenger(&mut self, cx: &mut Cx, offset: isize) {
+        let Some(comparison) = &self.comparison else {
+            return;
+        };
+        let challenger_idx = 1 - comparison.keeper_idx;
+        let keeper_path = &comparison.paths[comparison.keeper_idx];
+        let paths = &self.state.visible_image_paths;
+        let Some(mut image_idx) =
+            self.state.image_idx(&comparison.paths[challenger_idx])
+        else {
+            return;
+        };
+        let path = loop {
+            let Some(next_image_idx) = image_idx
+                .checked_add_signed(offset)
+                .filter(|image_idx| *image_idx < paths.len())
+            else {
+                return;
+            };
+            image_idx = next_image_idx;
+            if paths[image_idx] != *keeper_path {
+                break paths[image_idx].clone();
+            }
+        };
+        if let Some(comparison) = &mut self.comparison {
+            comparison.paths[challenger_idx] = path;
+        }
+        self.show_compared_image(cx, challenger_idx);
+        self.update_compare_captions(cx);
+    }
+
+    /// Leaves compare mode for the page it was opened from, or for the
+    /// keeper in the slideshow if `open_keeper` is set.
+    fn close_compare(&mut self, cx: &mut Cx, open_keeper: bool) {
+        let Some(comparison) = self.comparison.take() else {
+            return;
+        };
+        let keeper_path = &comparison.paths[comparison.keeper_idx];
+        if open_keeper
+            && let Some(image_idx) = self.state.image_idx(keeper_path)
+        {
+            self.set_current_image(cx, image_idx);
+            self.open_slideshow(cx);
+        } else if comparison.return_page == live_id!(slideshow) {
+            self.open_slideshow(cx);
+        } else {
+            self.ui
+                .page_flip(id!(page_flip))
+                .set_active_page(cx, live_id!(image_browser));
+            cx.set_key_focus(self.ui.widget(id!(image_grid)).area());
+        }
+    }
+
+    fn zoom_compared_images(&mut self, cx: &mut Cx, factor: Option<f64>) {
+        if let Some(mut compare_view) = self
+            .ui
+            .widget(id!(compare_view))
+            .borrow_mut::<CompareView>()
+        {
+            match factor {
+                Some(factor) => compare_view.zoom_by(cx, factor),
+                None => compare_view.set_zoom(cx, Zoom::Fit),
+            }
+        }
+    }
+
+    fn toggle_compare_mode(&mut self, cx: &mut Cx) {
+        if let Some(mut compare_view) = self
+            .ui
+            .widget(id!(compare_view))
+            .borrow_mut::<CompareView>()
+        {
+            compare_view.toggle_mode(cx);
+        }
+    }
+
+    /// Changes the rating of the current image and writes it to its sidecar.
+    /// In the slideshow, keys 1 to 5 set stars and 6 to 9 the red, yellow,
+    /// green and blue labels, while P picks, X rejects and U unflags.
+    fn rate_current_image(
+        &mut self,
+        cx: &mut Cx,
+        change: impl FnOnce(Rating) -> Rating,
+    ) {
+        let Some(path) = self.state.current_image_path().cloned() else {
+            return;
+        };
+        let rating = change(self.state.ratings.get(&path));
+        if let Err(error) = rating.save(&path) {
+            let status = format!(
+                "Failed to save rating of {}: {}",
+                path.display(),
+                error
+            );
+            self.show_status(cx, &status);
+            return;
+        }
+        self.state.ratings.set(&path, rating);
+        self.show_status(cx, &rating.describe());
+        self.apply_rating_changes(cx);
+    }
+
+    /// Reads the ratings of the images whose sidecars changed behind the
+    /// app's back.
+    fn reload_ratings(
+        &mut self,
+        cx: &mut Cx,
+        sidecar_paths: &HashSet<PathBuf>,
+    ) {
+        let mut is_changed = false;
+        for path in &self.state.image_paths {
+            if !sidecar_paths.contains(&rating::sidecar_path(path)) {
+                continue;
+            }
+            match Rating::load(path) {
+                Ok(rating) if rating != self.state.ratings.get(path) => {
+                    self.state.ratings.set(path, rating);
+                    is_changed = true;
+                }
+                Ok(_) => {}
+                Err(error) => error!(
+                    "Failed to load rating of {}: {}",
+                    path.display(),
+                    error
+                ),
+            }
+        }
+        if is_changed {
+            self.apply_rating_changes(cx);
+        }
+    }
+
+    /// Sorts and filters the images again after their ratings changed, if
+    /// that goes by ratings.
+    fn apply_rating_changes(&mut self, cx: &mut Cx) {
+        if self.state.sort.key == SortKey::Rating
+            || self.state.filter.needs_ratings()
+        {
+            let current_image_path = self.state.current_image_path().cloned();
+            self.state.sort_images();
+            if self.state.current_image_path() != current_image_path.as_ref() {
+                self.set_current_image(cx, self.state.current_image_idx);
+            }
+        }
+        self.ui.redraw(cx);
+    }
+
+    fn apply_dir_changes(
+        &mut self,
+        cx: &mut Cx,
+        image_paths: Vec<PathBuf>,
+        ratings: Option<Ratings>,
+        renames: &[(PathBuf, PathBuf)],
+    ) {
+        let current_image_path = self.state.current_image_path().cloned();
+        let mut are_tags_renamed = false;
+        for (from, to) in renames {
+            are_tags_renamed |= self.tags.rename(from, to);
+        }
+        if are_tags_renamed && let Err(error) = self.tags.save() {
+            error!("Failed to save tags: {}", error);
+        }
+        self.state.apply_dir_changes(image_paths, ratings, renames);
+        self.state.image_infos.request(cx, &self.state.image_paths);
+
+        let image_paths: HashSet<&Path> = self
+            .state
+            .image_paths
+            .iter()
+            .map(PathBuf::as_path)
+            .collect();
+        self.textures.retain(|path| image_paths.contains(path));
+        if self.state.current_image_path() != current_image_path.as_ref() {
+            self.set_current_image(cx, self.state.current_image_idx);
+        }
+        self.ui.redraw(cx);
+    }
+
+    /// Drops everything read from images that were overwritten behind our
+    /// back, so they are read again.
+    fn forget_modified_images(
+        &mut self,
+        cx: &mut Cx,
+        modified_paths: &HashSet<PathBuf>,
+    ) {
+        for path in modified_paths {
+            self.state.thumbnails.remove(path);
+            self.textures.remove(path);
+            self.state.image_infos.remove(path);
+        }
+        if let Some(path) = self.state.current_image_path().cloned()
+            && modified_paths.contains(&path)
+        {
+            self.metadata = None;
+            self.slideshow_pixels = None;
+            self.load_slideshow_image(cx, &path);
+        }
+    }
+
+    fn open_folder(&mut self, cx: &mut Cx, dir: &Path) {
+        // Kept canonical, like the image directory of the config.
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        self.recent_folders.push(&dir);
+        if let Err(error) = self.recent_folders.save() {
+            error!("Failed to save recent folders: {}", error);
+        }
+        self.update_recent_folders(cx);
+
+        self.config.image_dir = dir.clone();
+        self.config.initial_image = None;
+        self.load_image_paths(cx, &dir);
+
+        self.ui
+            .portal_list(id!(image_grid.rows))
+            .set_first_id_and_scroll(0, 0.0);
+        self.ui
+            .page_flip(id!(page_flip))
+            .set_active_page(cx, live_id!(image_browser));
+    }
+
+    fn update_recent_folders(&mut self, cx: &mut Cx) {
+        let labels = self
+            .recent_folders
+            .folders()
+            .iter()
+            .map(|folder| folder.display().to_string())
+            .collect();
+
+        let drop_down = self.ui.drop_down(id!(recent_folders));
+        drop_down.set_labels(cx, labels);
+        drop_down.set_selected_item(cx, 0);
+    }
+
+    fn set_thumbnail_size(&mut self, cx: &mut Cx, size: f64) {
+        self.state.set_grid_item_size(size);
+        self.settings.thumbnail_size = self.state.grid_item_size;
+        if let Err(error) = self.settings.save() {
+            error!("Failed to save settings: {}", error);
+        }
+        self.ui.redraw(cx);
+    }
+
+    fn set_grid_layout(&mut self, cx: &mut Cx, grid_layout: GridLayout) {
+        self.state.set_grid_layout(grid_layout);
+        self.settings.grid_layout = grid_layout;
+        if let Err(error) = self.settings.save() {
+            error!("Failed to save settings: {}", error);
+        }
+        self.ui.redraw(cx);
+    }
+
+    fn set_zoom_mode(&mut self, zoom_mode: ZoomMode) {
+        self.settings.zoom_mode = zoom_mode;
+        if let Err(error) = self.settings.save() {
+            error!("Failed to save settings: {}", error);
+        }
+        self.zooms.clear();
+    }
+
+    fn set_sort(&mut self, cx: &mut Cx, sort: SortOptions) {
+        self.state.set_sort(sort);
+        self.settings.sort = sort;
+        if let Err(error) = self.settings.save() {
+            error!("Failed to save settings: {}", error);
+        }
+        self.ui
+            .portal_list(id!(image_grid.rows))
+            .set_first_id_and_scroll(0, 0.0);
+        self.ui.redraw(cx);
+    }
+
+    fn set_filter(&mut self, cx: &mut Cx, query: &str) {
+        let current_image_path = self.state.current_image_path().cloned();
+        self.state.set_filter(Filter::parse(query));
+        if self.state.current_image_path() != current_image_path.as_ref() {
+            self.set_current_image(cx, self.state.current_image_idx);
+        }
+        self.ui
+            .portal_list(id!(image_grid.rows))
+            .set_first_id_and_scroll(0, 0.0);
+        self.ui.redraw(cx);
+    }
+
+    fn init_drop_down(
+        &self,
+        cx: &mut Cx,
+        path: &[LiveId],
+        labels: impl IntoIterator<Item = &'static str>,
+        selected_item: usize,
+    ) {
+        let drop_down = self.ui.drop_down(path);
+        let labels = labels.into_iter().map(str::to_string).collect();
+        drop_down.set_labels(cx, labels);
+        drop_down.set_selected_item(cx, selected_item);
+    }
+
+    /// A plain click selects just the clicked image, while Ctrl+click
+    /// toggles it in the selection and Shift+click selects the range up to
+    /// it. Images are opened by double-clicking them or pressing Enter.
+    fn click_image(
+        &mut self,
+        cx: &mut Cx,
+        image_idx: usize,
+        modifiers: KeyModifiers,
+    ) {
+        let Some(path) = self.state.visible_image_paths.get(image_idx).cloned()
+        else {
+            return;
+        };
+        self.state.focused_image_idx = Some(image_idx);
+        let selection = &mut self.state.selection;
+        if modifiers.shift {
+            let extend = modifiers.control || modifiers.logo;
+            selection.select_range(
+                &self.state.visible_image_paths,
+                image_idx,
+                extend,
+            );
+        } else if modifiers.control || modifiers.logo {
+            selection.toggle(&path);
+        } else {
+            selection.select_only(&path);
+        }
+        self.ui.redraw(cx);
+    }
+
+    fn open_slideshow(&mut self, cx: &mut Cx) {
+        self.ui
+            .page_flip(id!(page_flip))
+            .set_active_page(cx, live_id!(slideshow));
+        self.ui.view(id!(overlay)).set_key_focus(cx);
+    }
+
+    /// Scrolls the grid to the image last shown in the slideshow and
+    /// highlights it, so it's easy to find again after leaving the
+    /// slideshow.
+    fn reveal_current_image_in_grid(&mut self, cx: &mut Cx) {
+        let image_idx = self.state.current_image_idx;
+        self.state.highlighted_image_idx = Some(image_idx);
+        self.state.focused_image_idx = Some(image_idx);
+        self.state.scroll_to_image_idx = Some(image_idx);
+        cx.set_key_focus(self.ui.widget(id!(image_grid)).area());
+        self.ui.redraw(cx);
+    }
+
+    fn open_initial_image(&mut self, cx: &mut Cx, path: &Path) {
+        let Ok(path) = path.canonicalize() else {
+            return;
+        };
+        let image_idx =
+            self.state
+                .visible_image_paths
+                .iter()
+                .position(|image_path| {
+                    image_path
+                        .canonicalize()
+                        .is_ok_and(|image_path| image_path == path)
+                });
+        if let Some(image_idx) = image_idx {
+            self.set_current_image(cx, image_idx);
+            self.ui
+                .page_flip(id!(page_flip))
+                .set_active_page(cx, live_id!(slideshow));
+        }
+    }
+
+    fn pick_folder(&mut self, cx: &mut Cx, purpose: FolderDialogPurpose) {
+        if let FolderDialogPurpose::MoveTo(paths)
+        | FolderDialogPurpose::CopyTo(paths) = &purpose
+            && paths.is_empty()
+        {
+            return;
+        }
+        self.folder_dialog_purpose = purpose;
+        let start_dir = Some(self.config.image_dir.clone());
+        folder_dialog::pick_folder(cx, start_dir);
+    }
+
+    fn run_file_op(&mut self, cx: &mut Cx, op: FileOp, paths: Vec<PathBuf>) {
+        if paths.is_empty() {
+            self.show_status(cx, "No images selected");
+            return;
+        }
+        self.save_recipe(cx);
+        file_ops::run(cx, op, paths);
+    }
+
+    fn undo_file_op(&mut self, cx: &mut Cx) {
+        self.save_recipe(cx);
+        match self.last_file_op.take() {
+            Some(changes) => file_ops::undo(cx, changes),
+            None => self.show_status(cx, "Nothing to undo"),
+        }
+    }
+
+    fn handle_file_op_action(&mut self, cx: &mut Cx, action: &FileOpAction) {
+        if !action.changes.is_empty() {
+            self.last_file_op = Some(action.changes.clone());
+        }
+        match &action.error {
+            Some(error) => {
+                let status =
+                    format!("{}, then failed: {}", action.description, error);
+                self.show_status(cx, &status);
+            }
+            None => self.show_status(cx, &action.description),
+        }
+        self.apply_file_effects(cx, &action.effects);
+    }
+
+    /// Updates the image list in place to reflect file operations, without
+    /// waiting for the directory watcher to pick them up.
+    fn apply_file_effects(&mut self, cx: &mut Cx, effects: &[Effect]) {
+        let mut image_paths = self.state.image_paths.clone();
+        let mut renames = Vec::new();
+        for effect in effects {
+            match effect {
+                Effect::Added(path) => {
+                    if self.is_in_image_dir(path) {
+                        image_paths.push(path.clone());
+                    }
+                }
+                Effect::Removed(path) => image_paths.retain(|p| p != path),
+                Effect::Renamed { from, to } => {
+                    image_paths.retain(|p| p != from);
+                    if self.is_in_image_dir(to) {
+                        image_paths.push(to.clone());
+                        renames.push((from.clone(), to.clone()));
+                    }
+                }
+            }
+        }
+        self.apply_dir_changes(cx, image_paths, None, &renames);
+    }
+
+    fn is_in_image_dir(&self, path: &Path) -> bool {
+        let image_dir = &self.config.image_dir;
+        if self.config.recursive {
+            path.starts_with(image_dir)
+        } else {
+            path.parent() == Some(image_dir.as_path())
+        }
+    }
+
+    fn show_status(&mut self, cx: &mut Cx, status: &str) {
+        self.ui.label(id!(file_ops_bar.status)).set_text(cx, status);
+        self.ui.label(id!(slideshow.status)).set_text(cx, status);
+        self.ui.redraw(cx);
+    }
+
+    fn go_to_previous_image(&mut self, cx: &mut Cx) {
+        if self.state.current_image_idx > 0 {
+            self.set_current_image(cx, self.state.current_image_idx - 1);
+        }
+    }
+
+    fn go_to_next_image(&mut self, cx: &mut Cx) {
+        if self.state.current_image_idx + 1 < self.state.num_images() {
+            self.set_current_image(cx, self.state.current_image_idx + 1);
+        }
+    }
+}
 
 impl LiveRegister for App {
     fn live_register(cx: &mut Cx) {
@@ -290,165 +2290,2556 @@ impl LiveRegister for App {
     }
 }
 
-impl LiveHook for App {
-    fn after_new_from_doc(&mut self, cx: &mut Cx) {
-        self.load_image_paths(cx, "../images".as_ref());
+impl LiveHook for App {
+    fn after_new_from_doc(&mut self, cx: &mut Cx) {
+        let (config, error) = Config::load();
+        if let Some(error) = error {
+            error!("Failed to load config: {}", error);
+        }
+        self.config = config;
+        self.textures = TextureCache::new(self.config.texture_cache_max_bytes);
+        self.state.thumbnails = Thumbnails::new(
+            self.state.decode_pool.clone(),
+            thumbnail_size_for(GRID_ITEM_SIZE),
+            self.config.thumbnail_cache_max_bytes,
+        );
+
+        self.settings = Settings::load().unwrap_or_else(|error| {
+            error!("Failed to load settings: {}", error);
+            Settings::default()
+        });
+        self.state.set_grid_item_size(self.settings.thumbnail_size);
+        self.ui
+            .slider(id!(thumbnail_size))
+            .set_value(cx, self.state.grid_item_size);
+        self.state.set_grid_layout(self.settings.grid_layout);
+        let grid_layout_idx = GridLayout::ALL
+            .iter()
+            .position(|grid_layout| *grid_layout == self.settings.grid_layout)
+            .unwrap_or(0);
+        self.init_drop_down(
+            cx,
+            id!(grid_layout),
+            GridLayout::ALL.map(|grid_layout| grid_layout.label()),
+            grid_layout_idx,
+        );
+        let zoom_mode_idx = ZoomMode::ALL
+            .iter()
+            .position(|zoom_mode| *zoom_mode == self.settings.zoom_mode)
+            .unwrap_or(0);
+        self.init_drop_down(
+            cx,
+            id!(zoom_mode),
+            ZoomMode::ALL.map(|zoom_mode| zoom_mode.label()),
+            zoom_mode_idx,
+        );
+        self.init_drop_down(
+            cx,
+            id!(aspect_ratio),
+            AspectRatio::ALL.map(|ratio| ratio.label()),
+            0,
+        );
+
+        let sort = self.settings.sort;
+        self.state.set_sort(sort);
+        let sort_key_idx = SortKey::ALL
+            .iter()
+            .position(|key| *key == sort.key)
+            .unwrap_or(0);
+        self.init_drop_down(
+            cx,
+            id!(sort_key),
+            SortKey::ALL.map(|key| key.label()),
+            sort_key_idx,
+        );
+        let sort_order_idx = SortOrder::ALL
+            .iter()
+            .position(|order| *order == sort.order)
+            .unwrap_or(0);
+        self.init_drop_down(
+            cx,
+            id!(sort_order),
+            SortOrder::ALL.map(|order| order.label()),
+            sort_order_idx,
+        );
+        let grouping_idx = Grouping::ALL
+            .iter()
+            .position(|grouping| *grouping == sort.grouping)
+            .unwrap_or(0);
+        self.init_drop_down(
+            cx,
+            id!(grouping),
+            Grouping::ALL.map(|grouping| grouping.label()),
+            grouping_idx,
+        );
+
+        self.recent_folders = RecentFolders::load().unwrap_or_else(|error| {
+            error!("Failed to load recent folders: {}", error);
+            RecentFolders::default()
+        });
+
+        self.tags = TagStore::load().unwrap_or_else(|error| {
+            error!("Failed to load tags: {}", error);
+            match tags::set_aside_unreadable() {
+                Ok(backup_path) => {
+                    log!("Moved the tags to {}", backup_path.display());
+                    TagStore::default()
+                }
+                Err(error) => {
+                    error!("Failed to move the tags aside: {}", error);
+                    TagStore::read_only()
+                }
+            }
+        });
+
+        let image_dir = self.config.image_dir.clone();
+        if image_dir.is_dir() {
+            self.recent_folders.push(&image_dir);
+        }
+        self.update_recent_folders(cx);
+
+        self.load_image_paths(cx, &image_dir);
+        if let Some(path) = self.config.initial_image.clone() {
+            self.open_initial_image(cx, &path);
+        }
+    }
+}
+
+impl AppMain for App {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event) {
+        if self.recipe_timer.is_event(event).is_some() {
+            self.save_recipe(cx);
+        }
+        self.match_event(cx, event);
+        let mut scope = Scope::with_data(&mut self.state);
+        self.ui.handle_event(cx, event, &mut scope);
+    }
+}
+
+impl MatchEvent for App {
+    fn handle_actions(&mut self, cx: &mut Cx, actions: &Actions) {
+        if self.ui.button(id!(button)).clicked(&actions) {
+            self.open_slideshow(cx);
+        }
+        if let Some(size) = self.ui.slider(id!(thumbnail_size)).slided(&actions)
+        {
+            self.set_thumbnail_size(cx, size);
+        }
+        if let Some(grid_layout_idx) =
+            self.ui.drop_down(id!(grid_layout)).selected(&actions)
+            && let Some(grid_layout) = GridLayout::ALL.get(grid_layout_idx)
+        {
+            self.set_grid_layout(cx, *grid_layout);
+        }
+        if let Some(zoom_mode_idx) =
+            self.ui.drop_down(id!(zoom_mode)).selected(&actions)
+            && let Some(zoom_mode) = ZoomMode::ALL.get(zoom_mode_idx)
+        {
+            self.set_zoom_mode(*zoom_mode);
+        }
+        if let Some(sort_key_idx) =
+            self.ui.drop_down(id!(sort_key)).selected(&actions)
+            && let Some(key) = SortKey::ALL.get(sort_key_idx)
+        {
+            let sort = SortOptions {
+                key: *key,
+                ..self.settings.sort
+            };
+            self.set_sort(cx, sort);
+        }
+        if let Some(sort_order_idx) =
+            self.ui.drop_down(id!(sort_order)).selected(&actions)
+            && let Some(order) = SortOrder::ALL.get(sort_order_idx)
+        {
+            let sort = SortOptions {
+                order: *order,
+                ..self.settings.sort
+            };
+            self.set_sort(cx, sort);
+        }
+        if let Some(grouping_idx) =
+            self.ui.drop_down(id!(grouping)).selected(&actions)
+            && let Some(grouping) = Grouping::ALL.get(grouping_idx)
+        {
+            let sort = SortOptions {
+                grouping: *grouping,
+                ..self.settings.sort
+            };
+            self.set_sort(cx, sort);
+        }
+        if let Some(query) = self.ui.text_input(id!(search)).changed(&actions) {
+            self.set_filter(cx, &query);
+        }
+        if self.ui.button(id!(rotate_left_button)).clicked(&actions) {
+            self.turn_edited_image(cx, Orientation::Rotate270);
+        }
+        if self.ui.button(id!(rotate_right_button)).clicked(&actions) {
+            self.turn_edited_image(cx, Orientation::Rotate90);
+        }
+        if self
+            .ui
+            .button(id!(flip_horizontal_button))
+            .clicked(&actions)
+        {
+            self.turn_edited_image(cx, Orientation::FlipHorizontal);
+        }
+        if self.ui.button(id!(flip_vertical_button)).clicked(&actions) {
+            self.turn_edited_image(cx, Orientation::FlipVertical);
+        }
+        if let Some(ratio_idx) =
+            self.ui.drop_down(id!(aspect_ratio)).selected(&actions)
+            && let Some(ratio) = AspectRatio::ALL.get(ratio_idx)
+        {
+            self.set_crop_ratio(cx, *ratio);
+        }
+        for adjustment in Adjustment::ALL {
+            if let Some(value) =
+                self.adjustment_slider(adjustment).slided(&actions)
+            {
+                let mut adjustments = self.adjustments;
+                adjustments.set(adjustment, value as f32);
+                self.set_adjustments(cx, adjustments);
+            }
+        }
+        if self
+            .ui
+            .button(id!(reset_adjustments_button))
+            .clicked(&actions)
+        {
+            self.set_adjustments(cx, Adjustments::default());
+        }
+        if self.ui.button(id!(export_button)).clicked(&actions) {
+            self.save_edit(cx, SaveTarget::NewFile);
+        }
+        if self.ui.button(id!(reset_edit_button)).clicked(&actions) {
+            self.reset_edit(cx);
+        }
+        if self.ui.button(id!(save_edit_button)).clicked(&actions) {
+            self.save_edit(cx, SaveTarget::Original);
+        }
+        if self
+            .ui
+            .button(id!(save_edit_as_new_button))
+            .clicked(&actions)
+        {
+            self.save_edit(cx, SaveTarget::NewFile);
+        }
+        if self.ui.button(id!(open_folder_button)).clicked(&actions) {
+            self.pick_folder(cx, FolderDialogPurpose::OpenFolder);
+        }
+        if self.ui.button(id!(trash_button)).clicked(&actions) {
+            let paths = self.state.selected_paths();
+            self.run_file_op(cx, FileOp::Trash, paths);
+        }
+        if self.ui.button(id!(move_button)).clicked(&actions) {
+            let paths = self.state.selected_paths();
+            self.pick_folder(cx, FolderDialogPurpose::MoveTo(paths));
+        }
+        if self.ui.button(id!(copy_button)).clicked(&actions) {
+            let paths = self.state.selected_paths();
+            self.pick_folder(cx, FolderDialogPurpose::CopyTo(paths));
+        }
+        if self.ui.button(id!(rename_button)).clicked(&actions) {
+            let template = self.ui.text_input(id!(rename_template)).text();
+            let paths = self.state.selected_paths();
+            self.run_file_op(cx, FileOp::Rename(template), paths);
+        }
+        if self.ui.button(id!(undo_button)).clicked(&actions) {
+            self.undo_file_op(cx);
+        }
+        if self.ui.button(id!(compare_button)).clicked(&actions) {
+            self.compare_selection(cx);
+        }
+        if self.ui.button(id!(collection_button)).clicked(&actions) {
+            let query = self.ui.text_input(id!(collection)).text();
+            self.open_collection(cx, &query);
+        }
+        if let Some(text) = self.ui.text_input(id!(tag_input)).changed(&actions)
+        {
+            self.update_tag_suggestions(cx, &text);
+        }
+        for (button_idx, button) in TAG_SUGGESTION_BUTTONS.iter().enumerate() {
+            if self.ui.button(button).clicked(&actions) {
+                self.accept_tag_suggestion(cx, button_idx);
+            }
+        }
+        if self.ui.button(id!(tag_button)).clicked(&actions) {
+            self.tag_selection(cx, true);
+        }
+        if self.ui.button(id!(untag_button)).clicked(&actions) {
+            self.tag_selection(cx, false);
+        }
+        if let Some(folder_idx) =
+            self.ui.drop_down(id!(recent_folders)).selected(&actions)
+            && let Some(dir) = self.recent_folders.folders().get(folder_idx)
+        {
+            let dir = dir.clone();
+            self.open_folder(cx, &dir);
+        }
+        for action in actions {
+            if let ImageGridItemAction::Clicked {
+                image_idx,
+                modifiers,
+            } = action.as_widget_action().cast()
+            {
+                self.click_image(cx, image_idx, modifiers);
+            }
+            if let ImageGridItemAction::DoubleClicked { image_idx } =
+                action.as_widget_action().cast()
+            {
+                self.set_current_image(cx, image_idx);
+                self.open_slideshow(cx);
+            }
+
+            match action.as_widget_action().cast() {
+                ZoomImageAction::PixelHovered(pixel) => {
+                    self.hovered_pixel = pixel;
+                    self.update_pixel_readout(cx);
+                }
+                ZoomImageAction::CropChanged(crop) => self.edit.crop = crop,
+                ZoomImageAction::None => {}
+            }
+
+            match action.as_widget_action().cast() {
+                ImageGridAction::ItemSizeChanged(size) => {
+                    self.ui.slider(id!(thumbnail_size)).set_value(cx, size);
+                    self.set_thumbnail_size(cx, size);
+                }
+                ImageGridAction::ImageOpened(image_idx) => {
+                    self.set_current_image(cx, image_idx);
+                    self.open_slideshow(cx);
+                }
+                ImageGridAction::TrashRequested => {
+                    let paths = self.state.selected_paths();
+                    self.run_file_op(cx, FileOp::Trash, paths);
+                }
+                ImageGridAction::UndoRequested => self.undo_file_op(cx),
+                ImageGridAction::CompareRequested => self.compare_selection(cx),
+                _ => {}
+            }
+
+            if let Some(action) = action.downcast_ref::<FileOpAction>() {
+                self.handle_file_op_action(cx, action);
+            }
+
+            if let Some(action) = action.downcast_ref::<DecodeAction>() {
+                self.handle_decode_action(cx, action);
+            }
+
+            if let Some(action) = action.downcast_ref::<EditAction>() {
+                self.handle_edit_action(cx, action);
+            }
+
+            if let Some(action) = action.downcast_ref::<MetadataAction>()
+                && self.state.current_image_path() == Some(&action.path)
+            {
+                self.metadata =
+                    Some((action.path.clone(), action.result.clone()));
+                self.update_metadata_panel(cx);
+            }
+
+            if let Some(action) = action.downcast_ref::<ImageInfoAction>() {
+                let current_image_path =
+                    self.state.current_image_path().cloned();
+                if self.state.handle_image_info_action(action) {
+                    if self.state.current_image_path()
+                        != current_image_path.as_ref()
+                    {
+                        self.set_current_image(
+                            cx,
+                            self.state.current_image_idx,
+                        );
+                    }
+                    self.ui.redraw(cx);
+                }
+            }
+
+            if let Some(action) = action.downcast_ref::<ThumbnailAction>()
+                && self.state.thumbnails.handle_action(cx, action)
+            {
+                self.ui.redraw(cx);
+            }
+
+            if let Some(DirWatcherAction::Changed {
+                dir,
+                image_paths,
+                ratings,
+                renames,
+                modified_paths,
+            }) = action.downcast_ref()
+                && *dir == self.config.image_dir
+                && self.collection.is_none()
+            {
+                self.forget_modified_images(cx, modified_paths);
+                self.apply_dir_changes(
+                    cx,
+                    image_paths.clone(),
+                    Some(ratings.clone()),
+                    renames,
+                );
+            }
+            if let Some(DirWatcherAction::SidecarsChanged {
+                dir,
+                sidecar_paths,
+            }) = action.downcast_ref()
+                && *dir == self.config.image_dir
+                && self.collection.is_none()
+            {
+                self.reload_ratings(cx, sidecar_paths);
+            }
+
+            match action.downcast_ref::<FolderDialogAction>() {
+                Some(FolderDialogAction::Picked(dir)) => {
+                    let dir = dir.clone();
+                    match std::mem::take(&mut self.folder_dialog_purpose) {
+                        FolderDialogPurpose::OpenFolder => {
+                            self.open_folder(cx, &dir)
+                        }
+                        FolderDialogPurpose::MoveTo(paths) => {
+                            self.run_file_op(cx, FileOp::Move(dir), paths)
+                        }
+                        FolderDialogPurpose::CopyTo(paths) => {
+                            self.run_file_op(cx, FileOp::Copy(dir), paths)
+                        }
+                    }
+                }
+                Some(FolderDialogAction::Failed(message)) => {
+                    error!("Failed to open folder dialog: {}", message);
+                }
+                None => {}
+            }
+        }
+
+        if self.ui.button(id!(left_button)).clicked(&actions) {
+            self.go_to_previous_image(cx);
+        }
+        if self.ui.button(id!(right_button)).clicked(&actions) {
+            self.go_to_next_image(cx);
+        }
+
+        if let Some(event) = self.ui.view(id!(overlay)).key_down(&actions) {
+            match event.key_code {
+                KeyCode::Escape => {
+                    self
+                        .ui
+                        .page_flip(id!(page_flip))
+                        .set_active_page(cx, live_id!(image_browser));
+                    self.reveal_current_image_in_grid(cx);
+                },
+                KeyCode::ArrowLeft => self.go_to_previous_image(cx),
+                KeyCode::ArrowRight => self.go_to_next_image(cx),
+                KeyCode::KeyI => self.toggle_metadata_panel(cx),
+                KeyCode::KeyL => self.toggle_loupe(cx),
+                KeyCode::KeyC if self.loupe_is_visible => {
+                    self.copy_hovered_pixel(cx)
+                }
+                KeyCode::KeyC => self.compare_current_image(cx),
+                KeyCode::KeyA => self.toggle_adjustments_panel(cx),
+                KeyCode::KeyE => self.toggle_edit_bar(cx),
+                KeyCode::KeyR if event.modifiers.shift => {
+                    self.turn_edited_image(cx, Orientation::Rotate270)
+                }
+                KeyCode::KeyR => {
+                    self.turn_edited_image(cx, Orientation::Rotate90)
+                }
+                KeyCode::KeyH => {
+                    self.turn_edited_image(cx, Orientation::FlipHorizontal)
+                }
+                KeyCode::KeyV => {
+                    self.turn_edited_image(cx, Orientation::FlipVertical)
+                }
+                KeyCode::Equals | KeyCode::NumpadAdd => {
+                    self.zoom_slideshow_image(cx, Some(KEY_ZOOM_FACTOR))
+                }
+                KeyCode::Minus | KeyCode::NumpadSubtract => {
+                    self.zoom_slideshow_image(cx, Some(1.0 / KEY_ZOOM_FACTOR))
+                }
+                KeyCode::Key0 | KeyCode::Numpad0 => {
+                    self.zoom_slideshow_image(cx, None)
+                }
+                KeyCode::Key1 => self
+                    .rate_current_image(cx, |rating| rating.toggled_stars(1)),
+                KeyCode::Key2 => self
+                    .rate_current_image(cx, |rating| rating.toggled_stars(2)),
+                KeyCode::Key3 => self
+                    .rate_current_image(cx, |rating| rating.toggled_stars(3)),
+                KeyCode::Key4 => self
+                    .rate_current_image(cx, |rating| rating.toggled_stars(4)),
+                KeyCode::Key5 => self
+                    .rate_current_image(cx, |rating| rating.toggled_stars(5)),
+                KeyCode::Key6 => self.rate_current_image(cx, |rating| {
+                    rating.toggled_label(ColorLabel::Red)
+                }),
+                KeyCode::Key7 => self.rate_current_image(cx, |rating| {
+                    rating.toggled_label(ColorLabel::Yellow)
+                }),
+                KeyCode::Key8 => self.rate_current_image(cx, |rating| {
+                    rating.toggled_label(ColorLabel::Green)
+                }),
+                KeyCode::Key9 => self.rate_current_image(cx, |rating| {
+                    rating.toggled_label(ColorLabel::Blue)
+                }),
+                KeyCode::KeyP => self.rate_current_image(cx, |rating| {
+                    rating.with_flag(Some(Flag::Pick))
+                }),
+                KeyCode::KeyX => self.rate_current_image(cx, |rating| {
+                    rating.with_flag(Some(Flag::Reject))
+                }),
+                KeyCode::KeyU => {
+                    self.rate_current_image(cx, |rating| rating.with_flag(None))
+                }
+                KeyCode::Delete => {
+                    let paths = self.state.current_image_path().cloned();
+                    self.run_file_op(
+                        cx,
+                        FileOp::Trash,
+                        paths.into_iter().collect(),
+                    );
+                }
+                KeyCode::KeyZ
+                    if event.modifiers.control || event.modifiers.logo =>
+                {
+                    self.undo_file_op(cx)
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(event) =
+            self.ui.view(id!(compare_overlay)).key_down(&actions)
+        {
+            match event.key_code {
+                KeyCode::Escape => self.close_compare(cx, false),
+                KeyCode::ReturnKey | KeyCode::NumpadEnter => {
+                    self.close_compare(cx, true)
+                }
+                KeyCode::ArrowLeft => self.step_challenger(cx, -1),
+                KeyCode::ArrowRight => self.step_challenger(cx, 1),
+                KeyCode::KeyS => self.swap_keeper(cx),
+                KeyCode::KeyW => self.toggle_compare_mode(cx),
+                KeyCode::Equals | KeyCode::NumpadAdd => {
+                    self.zoom_compared_images(cx, Some(KEY_ZOOM_FACTOR))
+                }
+                KeyCode::Minus | KeyCode::NumpadSubtract => {
+                    self.zoom_compared_images(cx, Some(1.0 / KEY_ZOOM_FACTOR))
+                }
+                KeyCode::Key0 | KeyCode::Numpad0 => {
+                    self.zoom_compared_images(cx, None)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, DefaultNone)]
+pub enum ImageGridAction {
+    ItemSizeChanged(f64),
+    ImageOpened(usize),
+    TrashRequested,
+    UndoRequested,
+    CompareRequested,
+    None,
+}
+
+/// A selection rectangle being dragged out over the grid. The images that
+/// were selected when the drag started with a modifier held stay selected.
+struct RubberBand {
+    /// Where the drag started, relative to the top left corner of the
+    /// content of the grid, so it stays with the images as the grid scrolls.
+    start: DVec2,
+    /// Where the pointer is, on screen.
+    end: DVec2,
+    is_dragging: bool,
+    base_selection: HashSet<PathBuf>,
+}
+
+impl RubberBand {
+    /// The rectangle on screen, with the content of the grid at `offset`.
+    fn rect(&self, offset: DVec2) -> Rect {
+        let start = self.start + offset;
+        let pos = dvec2(start.x.min(self.end.x), start.y.min(self.end.y));
+        let size =
+            dvec2((start.x - self.end.x).abs(), (start.y - self.end.y).abs());
+        Rect { pos, size }
+    }
+}
+
+#[derive(Live, LiveHook, Widget)]
+pub struct ImageGrid {
+    #[deref]
+    view: View,
+    #[live]
+    draw_rubber_band: DrawQuad,
+    #[rust]
+    rubber_band: Option<RubberBand>,
+}
+
+impl ImageGrid {
+    /// Scrolls the least amount needed for the row holding `image_idx` to be
+    /// fully in view.
+    fn scroll_to_image(
+        &mut self,
+        cx: &mut Cx,
+        image_idx: usize,
+        state: &State,
+    ) {
+        let rows = self.view.portal_list(id!(rows));
+        let row_idx = state.row_for_image(image_idx);
+        let first_row_idx = rows.first_id();
+        let height = self.view.area().rect(cx).size.y;
+        if row_idx <= first_row_idx {
+            // The header of the group the row starts comes along.
+            let row_idx = match row_idx.checked_sub(1) {
+                Some(header_idx) if state.row_title(header_idx).is_some() => {
+                    header_idx
+                }
+                _ => row_idx,
+            };
+            rows.set_first_id_and_scroll(row_idx, 0.0);
+        } else {
+            let rows_height: f64 = (first_row_idx..=row_idx)
+                .map(|row_idx| state.row_height(row_idx))
+                .sum();
+            if rows_height <= height {
+                return;
+            }
+            let row_height = state.row_height(row_idx);
+            rows.set_first_id_and_scroll(row_idx, height - row_height);
+        }
+        self.view.redraw(cx);
+    }
+
+    /// Where the top left corner of the content of the grid is on screen,
+    /// which is above the grid once it is scrolled down.
+    fn content_offset(&self, cx: &Cx, state: &State) -> DVec2 {
+        let rows = self.view.portal_list(id!(rows));
+        let scroll = rows.first_scroll() - state.row_top(rows.first_id());
+        self.view.area().rect(cx).pos + dvec2(0.0, scroll)
+    }
+
+    fn update_rubber_band(
+        &mut self,
+        cx: &mut Cx,
+        end: DVec2,
+        state: &mut State,
+    ) {
+        let offset = self.content_offset(cx, state);
+        let Some(rubber_band) = &mut self.rubber_band else {
+            return;
+        };
+        rubber_band.end = end;
+        if !rubber_band.is_dragging {
+            if (rubber_band.end - (rubber_band.start + offset)).length()
+                < RUBBER_BAND_THRESHOLD
+            {
+                return;
+            }
+            rubber_band.is_dragging = true;
+        }
+
+        // The images are hit tested against the layout rather than the
+        // items drawn, so those scrolled out of view stay selected.
+        let rect = rubber_band.rect(offset);
+        let rect = Rect {
+            pos: rect.pos - offset,
+            size: rect.size,
+        };
+        let mut paths = rubber_band.base_selection.clone();
+        for image_idx in state.images_in_rect(rect) {
+            if let Some(path) = state.visible_image_paths.get(image_idx) {
+                paths.insert(path.clone());
+            }
+        }
+        state.selection.set(paths);
+        self.view.redraw(cx);
+    }
+
+    /// Returns the image the keyboard cursor should move to when `key_code`
+    /// is pressed, or `None` if the key doesn't move the cursor.
+    fn cursor_target(
+        &self,
+        cx: &Cx,
+        key_code: KeyCode,
+        state: &State,
+    ) -> Option<usize> {
+        let last_image_idx = state.num_images().checked_sub(1)?;
+        let rows = self.view.portal_list(id!(rows));
+        // Without a cursor yet, any navigation key puts it on the first
+        // image in view.
+        let Some(image_idx) = state.focused_image_idx else {
+            let image_idx = state.first_image_for_row(rows.first_id());
+            return Some(image_idx.min(last_image_idx));
+        };
+
+        let row_idx = state.row_for_image(image_idx);
+        let last_row_idx = state.num_rows().saturating_sub(1);
+        let rows_per_page = (self.view.area().rect(cx).size.y
+            / state.row_height(row_idx)) as usize;
+        let target_row_idx = match key_code {
+            KeyCode::ArrowLeft => return Some(image_idx.saturating_sub(1)),
+            KeyCode::ArrowRight => {
+                return Some((image_idx + 1).min(last_image_idx));
+            }
+            KeyCode::Home => return Some(0),
+            KeyCode::End => return Some(last_image_idx),
+            KeyCode::ArrowUp => row_idx.saturating_sub(1),
+            KeyCode::ArrowDown => (row_idx + 1).min(last_row_idx),
+            KeyCode::PageUp => row_idx.saturating_sub(rows_per_page.max(1)),
+            KeyCode::PageDown => {
+                (row_idx + rows_per_page.max(1)).min(last_row_idx)
+            }
+            _ => return None,
+        };
+
+        // Header rows hold no images, so the cursor skips over them.
+        let target_row_idx = if state.row_title(target_row_idx).is_none() {
+            target_row_idx
+        } else if target_row_idx < row_idx && target_row_idx > 0 {
+            target_row_idx - 1
+        } else {
+            target_row_idx + 1
+        };
+
+        // Moving between rows keeps the column, or lands on the last image
+        // if the target row is shorter.
+        let column_idx = image_idx - state.first_image_for_row(row_idx);
+        let num_images = state.num_images_for_row(target_row_idx);
+        Some(
+            state.first_image_for_row(target_row_idx)
+                + column_idx.min(num_images.saturating_sub(1)),
+        )
+    }
+}
+
+impl Widget for ImageGrid {
+    fn draw_walk(
+        &mut self,
+        cx: &mut Cx2d,
+        scope: &mut Scope,
+        walk: Walk,
+    ) -> DrawStep {
+        let state = scope.data.get_mut::<State>().unwrap();
+        let thumbnail_size = thumbnail_size_for(state.grid_item_size);
+        state.thumbnails.set_size(thumbnail_size);
+        state.thumbnails.begin_frame();
+
+        while let Some(item) = self.view.draw_walk(cx, scope, walk).step() {
+            let state = scope.data.get_mut::<State>().unwrap();
+
+            if let Some(mut list) = item.as_portal_list().borrow_mut() {
+                list.set_item_range(cx, 0, state.num_rows());
+
+                while let Some(row_idx) = list.next_visible_item(cx) {
+                    if row_idx >= state.num_rows() {
+                        continue;
+                    }
+
+                    if let Some(title) = state.row_title(row_idx) {
+                        let header = list.item(cx, row_idx, live_id!(Header));
+                        header.label(id!(title)).set_text(cx, title);
+                        header.draw_all(cx, &mut Scope::empty());
+                        continue;
+                    }
+
+                    let row = list.item(cx, row_idx, live_id!(Row));
+                    let mut scope = Scope::with_data_props(state, &row_idx);
+                    row.draw_all(cx, &mut scope);
+                }
+            }
+        }
+        let state = scope.data.get_mut::<State>().unwrap();
+        state.thumbnails.end_frame();
+
+        // The layout of the rows follows the width of the grid. When it
+        // changes, we scroll to the row that now holds the image that was
+        // first in view, so the grid stays anchored at the same image.
+        let width = self.view.area().rect(cx).size.x;
+        if width > 0.0 {
+            let rows = self.view.portal_list(id!(rows));
+            let first_image_idx = state.first_image_for_row(rows.first_id());
+            if state.update_layout(width) {
+                let row_idx = state.row_for_image(first_image_idx);
+                rows.set_first_id_and_scroll(row_idx, 0.0);
+                self.view.redraw(cx);
+            }
+        }
+
+        if let Some(image_idx) = state.scroll_to_image_idx.take() {
+            self.scroll_to_image(cx, image_idx, state);
+        }
+
+        if let Some(rubber_band) = &self.rubber_band
+            && rubber_band.is_dragging
+        {
+            let offset = self.content_offset(cx, state);
+            self.draw_rubber_band.draw_abs(cx, rubber_band.rect(offset));
+        }
+        DrawStep::done()
+    }
+
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        // Ctrl+scroll zooms the thumbnails instead of scrolling, so it must
+        // be handled before the portal lists get to see the event.
+        if let Event::Scroll(event) = event
+            && (event.modifiers.control || event.modifiers.logo)
+            && self.view.area().rect(cx).contains(event.abs)
+        {
+            let state = scope.data.get_mut::<State>().unwrap();
+            let item_size =
+                state.grid_item_size * 2f64.powf(-event.scroll.y / 400.0);
+            if state.set_grid_item_size(item_size) {
+                cx.widget_action(
+                    self.widget_uid(),
+                    &scope.path,
+                    ImageGridAction::ItemSizeChanged(state.grid_item_size),
+                );
+                self.view.redraw(cx);
+            }
+            return;
+        }
+
+        self.view.handle_event(cx, event, scope);
+
+        // Hit testing rather than matching raw finger events keeps clicks on
+        // an open drop down menu over the grid from reaching it. The capture
+        // overload lets the grid see presses on its items too, to start a
+        // rubber band from there.
+        let hit = event.hits_with_capture_overload(cx, self.view.area(), true);
+        if let Hit::FingerDown(event) = &hit {
+            cx.set_key_focus(self.view.area());
+            let state = scope.data.get::<State>().unwrap();
+            let modifiers = event.modifiers;
+            let base_selection =
+                if modifiers.shift || modifiers.control || modifiers.logo {
+                    state.selection.paths().clone()
+                } else {
+                    HashSet::new()
+                };
+            let offset = self.content_offset(cx, state);
+            self.rubber_band = Some(RubberBand {
+                start: event.abs - offset,
+                end: event.abs,
+                is_dragging: false,
+                base_selection,
+            });
+        }
+        match event {
+            Event::FingerMove(event) => {
+                let state = scope.data.get_mut::<State>().unwrap();
+                self.update_rubber_band(cx, event.abs, state);
+            }
+            Event::FingerUp(_) => {
+                if self
+                    .rubber_band
+                    .take()
+                    .is_some_and(|rubber_band| rubber_band.is_dragging)
+                {
+                    self.view.redraw(cx);
+                }
+            }
+            _ => {}
+        }
+        if let Hit::KeyDown(event) = hit {
+            let state = scope.data.get_mut::<State>().unwrap();
+            match event.key_code {
+                KeyCode::ReturnKey | KeyCode::NumpadEnter => {
+                    if let Some(image_idx) = state.focused_image_idx {
+                        cx.widget_action(
+                            self.widget_uid(),
+                            &scope.path,
+                            ImageGridAction::ImageOpened(image_idx),
+                        );
+                    }
+                }
+                KeyCode::KeyA
+                    if event.modifiers.control || event.modifiers.logo =>
+                {
+                    state.selection.select_all(&state.visible_image_paths);
+                    self.view.redraw(cx);
+                }
+                KeyCode::Escape => {
+                    state.selection.clear();
+                    self.view.redraw(cx);
+                }
+                KeyCode::Delete => {
+                    cx.widget_action(
+                        self.widget_uid(),
+                        &scope.path,
+                        ImageGridAction::TrashRequested,
+                    );
+                }
+                KeyCode::KeyZ
+                    if event.modifiers.control || event.modifiers.logo =>
+                {
+                    cx.widget_action(
+                        self.widget_uid(),
+                        &scope.path,
+                        ImageGridAction::UndoRequested,
+                    );
+                }
+                KeyCode::KeyC => {
+                    cx.widget_action(
+                        self.widget_uid(),
+                        &scope.path,
+                        ImageGridAction::CompareRequested,
+                    );
+                }
+                key_code => {
+                    if let Some(image_idx) =
+                        self.cursor_target(cx, key_code, state)
+                    {
+                        // Shift extends the selection from the anchor, while
+                        // plain moves make the cursor the new anchor.
+                        let path = &state.visible_image_paths[image_idx];
+                        if event.modifiers.shift {
+                            state.selection.select_range(
+                                &state.visible_image_paths,
+                                image_idx,
+                                false,
+                            );
+                        } else {
+                            state.selection.set_anchor(path);
+                        }
+                        state.focused_image_idx = Some(image_idx);
+                        state.scroll_to_image_idx = Some(image_idx);
+                        self.view.redraw(cx);
+                    }
+                }
+            }
+        }
+
+        // Scrolling while dragging moves the content under the rubber band,
+        // so it now covers other images.
+        if let Event::Scroll(_) = event
+            && let Some(rubber_band) = &self.rubber_band
+            && rubber_band.is_dragging
+        {
+            let end = rubber_band.end;
+            let state = scope.data.get_mut::<State>().unwrap();
+            self.update_rubber_band(cx, end, state);
+        }
+    }
+}
+
+#[derive(Live, LiveHook, Widget)]
+pub struct ImageGridRow {
+    #[deref]
+    view: View,
+    #[rust]
+    row_height: f64,
+}
+
+impl Widget for ImageGridRow {
+    fn draw_walk(
+        &mut self,
+        cx: &mut Cx2d,
+        scope: &mut Scope,
+        walk: Walk,
+    ) -> DrawStep {
+        let row_idx = *scope.props.get::<usize>().unwrap();
+        let row_height = scope.data.get::<State>().unwrap().row_height(row_idx);
+        if self.row_height != row_height {
+            self.row_height = row_height;
+            self.view
+                .portal_list(id!(items))
+                .apply_over(cx, live! { height: (row_height) });
+        }
+
+        while let Some(item) = self.view.draw_walk(cx, scope, walk).step() {
+            let state = scope.data.get_mut::<State>().unwrap();
+
+            if let Some(mut list) = item.as_portal_list().borrow_mut() {
+                list.set_item_range(cx, 0, state.num_images_for_row(row_idx));
+
+                while let Some(item_idx) = list.next_visible_item(cx) {
+                    if item_idx >= state.num_images_for_row(row_idx) {
+                        continue;
+                    }
+
+                    let item = list.item(cx, item_idx, live_id!(Item));
+
+                    let first_image_idx = state.first_image_for_row(row_idx);
+                    let image_idx = first_image_idx + item_idx;
+                    // The layout is only updated once the grid is drawn, so
+                    // right after the images change it can be out of date.
+                    let Some(image_path) =
+                        state.visible_image_paths.get(image_idx)
+                    else {
+                        continue;
+                    };
+                    let size = dvec2(state.item_width(image_idx), row_height);
+                    let texture = state.thumbnails.texture(cx, image_path);
+                    let marks = ItemMarks {
+                        highlighted: state.highlighted_image_idx
+                            == Some(image_idx),
+                        focused: state.focused_image_idx == Some(image_idx),
+                        selected: state.selection.contains(image_path),
+                    };
+                    let rating = state.ratings.get(image_path);
+                    if let Some(mut item) = item.borrow_mut::<ImageGridItem>() {
+                        item.set_image(cx, image_path, texture);
+                        item.set_marks(cx, marks);
+                        item.set_rating(cx, rating);
+                        item.image_idx = image_idx;
+                        item.size = size;
+                    }
+
+                    item.draw_all(cx, &mut Scope::empty());
+                }
+            }
+        }
+        DrawStep::done()
+    }
+
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.view.handle_event(cx, event, scope)
+    }
+}
+
+#[derive(Clone, Debug, DefaultNone)]
+pub enum ImageGridItemAction {
+    Clicked {
+        image_idx: usize,
+        modifiers: KeyModifiers,
+    },
+    DoubleClicked {
+        image_idx: usize,
+    },
+    None,
+}
+
+/// The states an item can be marked with, on top of being hovered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct ItemMarks {
+    /// The image that was last shown in the slideshow.
+    highlighted: bool,
+    /// The image under the keyboard cursor.
+    focused: bool,
+    selected: bool,
+}
+
+#[derive(Live, LiveHook, Widget)]
+pub struct ImageGridItem {
+    #[deref]
+    view: View,
+    #[live]
+    placeholder: LiveDependency,
+    #[animator]
+    animator: Animator,
+    #[live]
+    hover: f64,
+    #[rust]
+    image_path: Option<PathBuf>,
+    #[rust]
+    has_thumbnail: bool,
+    #[rust]
+    image_idx: usize,
+    #[rust]
+    marks: ItemMarks,
+    #[rust]
+    rating: Rating,
+    /// Set by the second press of a double click, so that its release isn't
+    /// taken for a click too.
+    #[rust]
+    is_double_click: bool,
+    #[rust(dvec2(GRID_ITEM_SIZE, GRID_ITEM_SIZE))]
+    size: DVec2,
+    #[rust]
+    frame_size: DVec2,
+}
+
+impl ImageGridItem {
+    fn set_image(
+        &mut self,
+        cx: &mut Cx,
+        image_path: &Path,
+        thumbnail: Option<Texture>,
+    ) {
+        // Items are recycled by the portal lists, so an item that is reused
+        // for a different image (for instance after switching folders) must
+        // drop the texture of its previous image right away. Otherwise it
+        // keeps showing it until the new thumbnail finishes loading.
+        if self.image_path.as_deref() == Some(image_path)
+            && self.has_thumbnail == thumbnail.is_some()
+        {
+            return;
+        }
+        self.image_path = Some(image_path.to_path_buf());
+        self.has_thumbnail = thumbnail.is_some();
+
+        let image = self.view.image(id!(image));
+        match thumbnail {
+            Some(thumbnail) => image.set_texture(cx, Some(thumbnail)),
+            None => {
+                let placeholder = self.placeholder.as_str();
+                image.load_image_dep_by_path(cx, placeholder).unwrap();
+            }
+        }
+    }
+
+    fn set_marks(&mut self, cx: &mut Cx, marks: ItemMarks) {
+        if marks == self.marks {
+            return;
+        }
+        self.marks = marks;
+        let value = |mark: bool| if mark { 1.0 } else { 0.0 };
+        self.view.apply_over(
+            cx,
+            live! {
+                draw_bg: {
+                    highlighted: (value(marks.highlighted)),
+                    focused: (value(marks.focused)),
+                    selected: (value(marks.selected)),
+                }
+            },
+        );
+    }
+
+    /// Shows the rating as badges: a pip per star along the bottom, the
+    /// color label top right, a green dot top left for picked images, and
+    /// rejected images dimmed.
+    fn set_rating(&mut self, cx: &mut Cx, rating: Rating) {
+        if rating == self.rating {
+            return;
+        }
+        self.rating = rating;
+        let label_color =
+            rating.label.map_or(vec4(0.0, 0.0, 0.0, 0.0), label_color);
+        let value = |flag| if rating.flag == Some(flag) { 1.0 } else { 0.0 };
+        self.view.view(id!(badges)).apply_over(
+            cx,
+            live! {
+                draw_bg: {
+                    stars: (rating.stars as f64),
+                    label_color: (label_color),
+                    picked: (value(Flag::Pick)),
+                    rejected: (value(Flag::Reject)),
+                }
+            },
+        );
+    }
+}
+
+impl Widget for ImageGridItem {
+    fn draw_walk(
+        &mut self,
+        cx: &mut Cx2d,
+        scope: &mut Scope,
+        walk: Walk,
+    ) -> DrawStep {
+        // The hover animation scales the image between a fixed fraction of
+        // the item size and the full item size, so it follows thumbnail
+        // zooming.
+        let scale = HOVER_OFF_SCALE + (1.0 - HOVER_OFF_SCALE) * self.hover;
+        let frame_size = dvec2(self.size.x * scale, self.size.y * scale);
+        if self.frame_size != frame_size {
+            self.frame_size = frame_size;
+            self.view.view(id!(frame)).apply_over(
+                cx,
+                live! {
+                    width: (frame_size.x),
+                    height: (frame_size.y),
+                },
+            );
+        }
+
+        let walk = Walk {
+            width: Size::Fixed(self.size.x),
+            height: Size::Fixed(self.size.y),
+            ..walk
+        };
+        self.view.draw_walk(cx, scope, walk)
+    }
+
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        if self.animator_handle_event(cx, event).must_redraw() {
+            self.view.redraw(cx);
+        }
+        match event.hits(cx, self.view.area()) {
+            Hit::FingerHoverIn(_) => self.animator_play(cx, id!(hover.on)),
+            Hit::FingerHoverOut(_) => self.animator_play(cx, id!(hover.off)),
+            Hit::FingerDown(event) => {
+                self.is_double_click = event.tap_count == 2;
+                if self.is_double_click {
+                    cx.widget_action(
+                        self.widget_uid(),
+                        &scope.path,
+                        ImageGridItemAction::DoubleClicked {
+                            image_idx: self.image_idx,
+                        },
+                    );
+                }
+            }
+            Hit::FingerUp(event)
+                if event.is_over
+                    && event.was_tap()
+                    && !self.is_double_click =>
+            {
+                cx.widget_action(
+                    self.widget_uid(),
+                    &scope.path,
+                    ImageGridItemAction::Clicked {
+                        image_idx: self.image_idx,
+                        modifiers: event.modifiers,
+                    },
+                );
+            }
+            _ => {}
+        }
+
+        self.view.handle_event(cx, event, scope)
+    }
+}
+
+#[derive(Clone, Debug, DefaultNone)]
+pub enum ZoomImageAction {
+    /// The pixel under the loupe changed, or the loupe left the image.
+    PixelHovered(Option<(usize, usize)>),
+    /// The crop rectangle was dragged, or removed by clicking beside it.
+    CropChanged(Option<Rect>),
+    None,
+}
+
+/// What dragging over the slideshow image does to the crop rectangle.
+#[derive(Clone, Copy, Debug)]
+enum CropDrag {
+    /// Drags a corner, with the opposite corner `anchor` held in place.
+    Resize { anchor: DVec2 },
+    /// Moves the rectangle, which was grabbed `offset` from its top left
+    /// corner.
+    Move { offset: DVec2 },
+}
+
+/// The slideshow image, which zooms around the cursor with the mouse wheel
+/// and between the fingers of a pinch, pans when dragged, and switches
+/// between fitting the view and actual size when double-clicked. Clicks on
+/// the arrow buttons drawn over it go to the buttons. It can also show a
+/// loupe, which magnifies the pixels under the cursor, and while the image
+/// is edited, dragging draws a crop rectangle instead of panning.
+#[derive(Live, LiveHook, Widget)]
+pub struct ZoomImage {
+    #[deref]
+    view: View,
+    #[live]
+    draw_loupe: DrawQuad,
+    #[live]
+    draw_crop: DrawQuad,
+    /// The size of the image, or `None` while the placeholder is shown,
+    /// which is only ever fitted to the view. Thumbnails shown until the
+    /// full image is decoded get the size of the full image.
+    #[rust]
+    image_size: Option<ImageSize>,
+    /// Whether the texture holds the pixels of the image itself, rather
+    /// than those of its thumbnail. The loupe magnifies single pixels, so
+    /// it waits for those of the image.
+    #[rust]
+    is_full_resolution: bool,
+    #[rust]
+    zoom: Zoom,
+    #[rust(1.0)]
+    dpi_factor: f64,
+    #[rust]
+    image_rect: Option<Rect>,
+    /// Where the finger dragging the image was last seen.
+    #[rust]
+    drag_pos: Option<DVec2>,
+    /// The fingers touching the image and where they were last seen. Once
+    /// there are two, they pinch instead of dragging.
+    #[rust]
+    touches: Vec<(DigitId, DVec2)>,
+    #[rust]
+    loupe_is_visible: bool,
+    #[rust]
+    hover_pos: Option<DVec2>,
+    #[rust]
+    hovered_pixel: Option<(usize, usize)>,
+    #[rust]
+    crop_is_enabled: bool,
+    /// The crop rectangle, in image pixels.
+    #[rust]
+    crop: Option<Rect>,
+    #[rust]
+    crop_ratio: AspectRatio,
+    #[rust]
+    crop_drag: Option<CropDrag>,
+}
+
+impl ZoomImage {
+    fn set_texture(
+        &mut self,
+        cx: &mut Cx,
+        texture: Texture,
+        image_size: Option<ImageSize>,
+    ) {
+        self.draw_loupe.draw_vars.set_texture(0, &texture);
+        self.view.image(id!(image)).set_texture(cx, Some(texture));
+        self.image_size = image_size;
+        self.is_full_resolution = true;
+        self.view.redraw(cx);
+    }
+
+    /// Shows the thumbnail of an image until the image itself is decoded.
+    fn set_thumbnail(
+        &mut self,
+        cx: &mut Cx,
+        texture: Texture,
+        image_size: Option<ImageSize>,
+    ) {
+        self.view.image(id!(image)).set_texture(cx, Some(texture));
+        self.image_size = image_size;
+        self.is_full_resolution = false;
+        self.view.redraw(cx);
+    }
+
+    fn show_placeholder(&mut self, cx: &mut Cx, placeholder: &str) {
+        let image = self.view.image(id!(image));
+        image.load_image_dep_by_path(cx, placeholder).unwrap();
+        self.image_size = None;
+        self.is_full_resolution = false;
+        self.view.redraw(cx);
+    }
+
+    fn set_loupe_visible(&mut self, cx: &mut Cx, is_visible: bool) {
+        self.loupe_is_visible = is_visible;
+        // So the pixel under the cursor is reported again.
+        self.hovered_pixel = None;
+        self.view.redraw(cx);
+    }
+
+    fn set_crop_enabled(&mut self, cx: &mut Cx, is_enabled: bool) {
+        self.crop_is_enabled = is_enabled;
+        self.crop_drag = None;
+        self.view.redraw(cx);
+    }
+
+    fn set_crop(&mut self, cx: &mut Cx, crop: Option<Rect>) {
+        if self.crop != crop {
+            self.crop = crop;
+            self.view.redraw(cx);
+        }
+    }
+
+    /// Holds the crop rectangle to `ratio` from now on, shrinking it to the
+    /// ratio right away. Returns the crop rectangle.
+    fn set_crop_ratio(
+        &mut self,
+        cx: &mut Cx,
+        ratio: AspectRatio,
+    ) -> Option<Rect> {
+        self.crop_ratio = ratio;
+        if let Some(crop) = self.crop
+            && let Some(viewport) = self.viewport(cx)
+        {
+            let image_size = viewport.image_size;
+            let corner =
+                dvec2(crop.pos.x + crop.size.x, crop.pos.y + crop.size.y);
+            let crop = edit::crop_between(
+                crop.pos,
+                corner,
+                ratio.ratio(image_size),
+                image_size,
+            );
+            self.set_crop(cx, Some(crop));
+        }
+        self.crop
+    }
+
+    /// What dragging from `point` of the image does to the crop rectangle:
+    /// dragging a corner resizes it, and dragging inside it moves it.
+    /// Returns `None` for any other point.
+    fn crop_drag_at(
+        &self,
+        point: DVec2,
+        viewport: Viewport,
+    ) -> Option<CropDrag> {
+        let crop = self.crop?;
+        let scale =
+            self.zoom.image_rect(viewport).size.x / viewport.image_size.x;
+        let handle_size = CROP_HANDLE_SIZE / scale;
+        let (left, top) = (crop.pos.x, crop.pos.y);
+        let (right, bottom) = (left + crop.size.x, top + crop.size.y);
+        let corners = [
+            (dvec2(left, top), dvec2(right, bottom)),
+            (dvec2(right, top), dvec2(left, bottom)),
+            (dvec2(left, bottom), dvec2(right, top)),
+            (dvec2(right, bottom), dvec2(left, top)),
+        ];
+        for (corner, opposite_corner) in corners {
+            if (point.x - corner.x).abs() <= handle_size
+                && (point.y - corner.y).abs() <= handle_size
+            {
+                return Some(CropDrag::Resize {
+                    anchor: opposite_corner,
+                });
+            }
+        }
+        let is_inside = point.x >= left
+            && point.x <= right
+            && point.y >= top
+            && point.y <= bottom;
+        is_inside.then(|| CropDrag::Move {
+            offset: point - crop.pos,
+        })
+    }
+
+    /// Starts dragging the crop rectangle from `point` of the image, or
+    /// starts a new one there.
+    fn start_crop_drag(
+        &mut self,
+        cx: &mut Cx,
+        scope: &Scope,
+        point: DVec2,
+        viewport: Viewport,
+    ) {
+        if let Some(crop_drag) = self.crop_drag_at(point, viewport) {
+            self.crop_drag = Some(crop_drag);
+            return;
+        }
+        let image_size = viewport.image_size;
+        let anchor = dvec2(
+            point.x.clamp(0.0, image_size.x),
+            point.y.clamp(0.0, image_size.y),
+        );
+        self.crop_drag = Some(CropDrag::Resize { anchor });
+        let crop = Rect {
+            pos: anchor,
+            size: dvec2(0.0, 0.0),
+        };
+        self.change_crop(cx, scope, Some(crop));
+    }
+
+    fn drag_crop(
+        &mut self,
+        cx: &mut Cx,
+        scope: &Scope,
+        crop_drag: CropDrag,
+        abs: DVec2,
+    ) {
+        let (Some(viewport), Some(point)) =
+            (self.viewport(cx), self.image_point(cx, abs))
+        else {
+            return;
+        };
+        let image_size = viewport.image_size;
+        let crop = match crop_drag {
+            CropDrag::Resize { anchor } => edit::crop_between(
+                anchor,
+                point,
+                self.crop_ratio.ratio(image_size),
+                image_size,
+            ),
+            CropDrag::Move { offset } => {
+                let Some(crop) = self.crop else {
+                    return;
+                };
+                edit::crop_moved_to(crop, point - offset, image_size)
+            }
+        };
+        self.change_crop(cx, scope, Some(crop));
+    }
+
+    /// Sets the crop rectangle the user dragged, and reports it.
+    fn change_crop(&mut self, cx: &mut Cx, scope: &Scope, crop: Option<Rect>) {
+        self.set_crop(cx, crop);
+        cx.widget_action(
+            self.widget_uid(),
+            &scope.path,
+            ZoomImageAction::CropChanged(crop),
+        );
+    }
+
+    fn set_adjustments(&mut self, cx: &mut Cx, adjustments: Adjustments) {
+        self.view.image(id!(image)).apply_over(
+            cx,
+            live! {
+                draw_bg: {
+                    exposure: (adjustments.exposure as f64),
+                    contrast: (adjustments.contrast as f64),
+                    saturation: (adjustments.saturation as f64),
+                    temperature: (adjustments.temperature as f64),
+                    tint: (adjustments.tint as f64),
+                    vibrance: (adjustments.vibrance as f64),
+                },
+            },
+        );
+        self.view.redraw(cx);
     }
-}
 
-impl AppMain for App {
-    fn handle_event(&mut self, cx: &mut Cx, event: &Event) {
-        self.match_event(cx, event);
-        let mut scope = Scope::with_data(&mut self.state);
-        self.ui.handle_event(cx, event, &mut scope);
+    fn set_zoom(&mut self, cx: &mut Cx, zoom: Zoom) {
+        if self.zoom != zoom {
+            self.zoom = zoom;
+            self.view.redraw(cx);
+        }
     }
-}
 
-impl MatchEvent for App {
-    fn handle_actions(&mut self, cx: &mut Cx, actions: &Actions) {
-        if self.ui.button(id!(button)).clicked(&actions) {
-            self.ui
-                .page_flip(id!(page_flip))
-                .set_active_page(cx, live_id!(slideshow));
+    /// Zooms by `factor` around the center of the view.
+    fn zoom_by(&mut self, cx: &mut Cx, factor: f64) {
+        if let Some(viewport) = self.viewport(cx) {
+            let zoom = self.zoom.zoom_at(factor, viewport.center(), viewport);
+            self.set_zoom(cx, zoom);
         }
-        if self.ui.button(id!(left_button)).clicked(&actions) {
-            self.go_to_previous_image(cx);
+    }
+
+    /// Moves one of the two fingers of a pinch to `abs`, zooming by how much
+    /// they moved apart and panning by how much their center moved, so the
+    /// image stays under them.
+    fn pinch(&mut self, cx: &mut Cx, digit_id: DigitId, abs: DVec2) {
+        let Some(viewport) = self.viewport(cx) else {
+            return;
+        };
+        let [(_, old_a), (_, old_b)] = [self.touches[0], self.touches[1]];
+        let Some(touch) = self.touches[..2]
+            .iter_mut()
+            .find(|(touch_digit_id, _)| *touch_digit_id == digit_id)
+        else {
+            return;
+        };
+        touch.1 = abs;
+        let [(_, a), (_, b)] = [self.touches[0], self.touches[1]];
+
+        let old_distance = (old_b - old_a).length();
+        if old_distance < 1.0 {
+            return;
         }
-        if self.ui.button(id!(right_button)).clicked(&actions) {
-            self.go_to_next_image(cx);
+        let origin = self.view.area().rect(cx).pos;
+        let old_center = (old_a + old_b) * 0.5;
+        let center = (a + b) * 0.5;
+        let zoom = self.zoom.pan(center - old_center, viewport).zoom_at(
+            (b - a).length() / old_distance,
+            center - origin,
+            viewport,
+        );
+        self.set_zoom(cx, zoom);
+    }
+
+    /// The view size is only known once the view is laid out, so this is
+    /// the size it had when it was last drawn.
+    fn viewport(&self, cx: &Cx) -> Option<Viewport> {
+        let image_size = self.image_size?;
+        let view_size = self.view.area().rect(cx).size;
+        if image_size.pixels() == 0 || view_size.x <= 0.0 || view_size.y <= 0.0
+        {
+            return None;
         }
+        Some(Viewport {
+            image_size: dvec2(
+                image_size.width as f64,
+                image_size.height as f64,
+            ),
+            view_size,
+            dpi_factor: self.dpi_factor,
+        })
+    }
 
-        if let Some(event) = self.ui.view(id!(overlay)).key_down(&actions) {
-            match event.key_code {
-                KeyCode::Escape => {
-                    self
-                        .ui
-                        .page_flip(id!(page_flip))
-                        .set_active_page(cx, live_id!(image_browser));
-                },
-                KeyCode::ArrowLeft => self.go_to_previous_image(cx),
-                KeyCode::ArrowRight => self.go_to_next_image(cx),
-                _ => {}
+    /// The point of the image under `abs`, in image pixels.
+    fn image_point(&self, cx: &Cx, abs: DVec2) -> Option<DVec2> {
+        let viewport = self.viewport(cx)?;
+        let origin = self.view.area().rect(cx).pos;
+        let rect = self.zoom.image_rect(viewport);
+        let scale = rect.size.x / viewport.image_size.x;
+        Some(dvec2(
+            (abs.x - origin.x - rect.pos.x) / scale,
+            (abs.y - origin.y - rect.pos.y) / scale,
+        ))
+    }
+
+    /// Works out which pixel is under the cursor after it moved or the image
+    /// did, and reports it while the loupe is shown.
+    fn update_hovered_pixel(&mut self, cx: &mut Cx, scope: &Scope) {
+        let point = self
+            .hover_pos
+            .and_then(|hover_pos| self.image_point(cx, hover_pos));
+        let image_size = self.image_size;
+        let hovered_pixel = point.zip(image_size).and_then(|(point, size)| {
+            let is_inside = point.x >= 0.0
+                && point.y >= 0.0
+                && point.x < size.width as f64
+                && point.y < size.height as f64;
+            is_inside.then_some((point.x as usize, point.y as usize))
+        });
+        if self.loupe_is_visible {
+            self.view.redraw(cx);
+            if self.hovered_pixel != hovered_pixel {
+                cx.widget_action(
+                    self.widget_uid(),
+                    &scope.path,
+                    ZoomImageAction::PixelHovered(hovered_pixel),
+                );
             }
         }
+        self.hovered_pixel = hovered_pixel;
     }
-}
 
-#[derive(Live, LiveHook, Widget)]
-pub struct ImageGrid {
-    #[deref]
-    view: View,
+    fn draw_loupe_at_cursor(&mut self, cx: &mut Cx2d) {
+        let (Some(hover_pos), Some(image_size), Some(_)) =
+            (self.hover_pos, self.image_size, self.hovered_pixel)
+        else {
+            return;
+        };
+        let Some(center) = self.image_point(cx, hover_pos) else {
+            return;
+        };
+        self.draw_loupe.apply_over(
+            cx,
+            live! {
+                center_x: (center.x),
+                center_y: (center.y),
+                image_width: (image_size.width as f64),
+                image_height: (image_size.height as f64),
+            },
+        );
+        let rect = Rect {
+            pos: dvec2(
+                hover_pos.x - LOUPE_SIZE / 2.0,
+                hover_pos.y - LOUPE_SIZE / 2.0,
+            ),
+            size: dvec2(LOUPE_SIZE, LOUPE_SIZE),
+        };
+        self.draw_loupe.draw_abs(cx, rect);
+    }
+
+    fn draw_crop_rect(&mut self, cx: &mut Cx2d) {
+        let (Some(crop), Some(image_rect), Some(image_size)) =
+            (self.crop, self.image_rect, self.image_size)
+        else {
+            return;
+        };
+        let scale = image_rect.size.x / image_size.width as f64;
+        self.draw_crop.apply_over(
+            cx,
+            live! {
+                crop_x: (image_rect.pos.x + crop.pos.x * scale),
+                crop_y: (image_rect.pos.y + crop.pos.y * scale),
+                crop_width: (crop.size.x * scale),
+                crop_height: (crop.size.y * scale),
+            },
+        );
+        let rect = self.view.area().rect(cx);
+        self.draw_crop.draw_abs(cx, rect);
+    }
+
+    fn image_rect(&mut self, cx: &Cx) -> Option<Rect> {
+        let viewport = self.viewport(cx)?;
+        // The view may have been resized since the image was last panned.
+        self.zoom = self.zoom.clamped(viewport);
+        Some(self.zoom.image_rect(viewport))
+    }
 }
 
-impl Widget for ImageGrid {
+impl Widget for ZoomImage {
     fn draw_walk(
         &mut self,
         cx: &mut Cx2d,
         scope: &mut Scope,
         walk: Walk,
     ) -> DrawStep {
-        while let Some(item) = self.view.draw_walk(cx, scope, walk).step() {
-            let state = scope.data.get_mut::<State>().unwrap();
-
-            if let Some(mut list) = item.as_portal_list().borrow_mut() {
-                list.set_item_range(cx, 0, state.num_rows());
+        self.dpi_factor = cx.current_dpi_factor();
+        let image_rect = self.image_rect(cx);
+        if self.image_rect != image_rect {
+            self.image_rect = image_rect;
+            let image = self.view.image(id!(image));
+            match image_rect {
+                Some(rect) => image.apply_over(
+                    cx,
+                    live! {
+                        width: (rect.size.x),
+                        height: (rect.size.y),
+                        margin: {
+                            left: (rect.pos.x),
+                            top: (rect.pos.y),
+                        },
+                        fit: Stretch,
+                    },
+                ),
+                None => image.apply_over(
+                    cx,
+                    live! {
+                        width: Fill,
+                        height: Fill,
+                        margin: {
+                            left: 0.0,
+                            top: 0.0,
+                        },
+                        fit: Biggest,
+                    },
+                ),
+            }
+        }
 
-                while let Some(row_idx) = list.next_visible_item(cx) {
-                    if row_idx >= state.num_rows() {
-                        continue;
-                    }
+        self.view.draw_walk(cx, scope, walk)?;
+        if self.crop_is_enabled {
+            self.draw_crop_rect(cx);
+        }
+        if self.loupe_is_visible && self.is_full_resolution {
+            self.draw_loupe_at_cursor(cx);
+        }
 
-                    let row = list.item(cx, row_idx, live_id!(Row));
-                    let mut scope = Scope::with_data_props(state, &row_idx);
-                    row.draw_all(cx, &mut scope);
-                }
-            }
+        // If the view was resized, the image was laid out for its old size,
+        // so it's laid out again.
+        if self.image_rect(cx) != self.image_rect {
+            self.view.redraw(cx);
         }
         DrawStep::done()
     }
 
     fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        let viewport = self.viewport(cx);
+        let origin = self.view.area().rect(cx).pos;
+        let hit = event.hits(cx, self.view.area());
+        if let Hit::FingerDown(event) = &hit
+            && event.device.is_touch()
+        {
+            self.touches.push((event.digit_id, event.abs));
+        }
+        match hit {
+            Hit::FingerHoverIn(event) | Hit::FingerHoverOver(event) => {
+                self.hover_pos = Some(event.abs);
+                self.update_hovered_pixel(cx, scope);
+            }
+            // A second finger turns the drag of the first into a pinch.
+            Hit::FingerDown(_) if self.touches.len() >= 2 => {
+                self.drag_pos = None;
+                self.crop_drag = None;
+            }
+            Hit::FingerHoverOut(_) => {
+                self.hover_pos = None;
+                self.update_hovered_pixel(cx, scope);
+            }
+            Hit::FingerScroll(event) => {
+                if let Some(viewport) = viewport {
+                    let factor = SCROLL_ZOOM_FACTOR.powf(-event.scroll.y);
+                    let point = event.abs - origin;
+                    let zoom = self.zoom.zoom_at(factor, point, viewport);
+                    self.set_zoom(cx, zoom);
+                    self.update_hovered_pixel(cx, scope);
+                }
+            }
+            Hit::FingerDown(event) if event.tap_count == 2 => {
+                if let Some(viewport) = viewport {
+                    let zoom = match self.zoom {
+                        Zoom::Fit => self.zoom.scale_at(
+                            viewport.actual_size_scale(),
+                            event.abs - origin,
+                            viewport,
+                        ),
+                        Zoom::Fixed { .. } => Zoom::Fit,
+                    };
+                    self.set_zoom(cx, zoom);
+                    self.update_hovered_pixel(cx, scope);
+                }
+            }
+            Hit::FingerDown(event) if self.crop_is_enabled => {
+                if let Some(viewport) = viewport
+                    && let Some(point) = self.image_point(cx, event.abs)
+                {
+                    self.start_crop_drag(cx, scope, point, viewport);
+                }
+            }
+            Hit::FingerDown(event) => self.drag_pos = Some(event.abs),
+            Hit::FingerMove(event) if self.touches.len() >= 2 => {
+                self.pinch(cx, event.digit_id, event.abs);
+                self.update_hovered_pixel(cx, scope);
+            }
+            Hit::FingerMove(event) => {
+                if let Some(viewport) = viewport
+                    && let Some(drag_pos) = self.drag_pos
+                {
+                    let zoom = self.zoom.pan(event.abs - drag_pos, viewport);
+                    self.set_zoom(cx, zoom);
+                    self.drag_pos = Some(event.abs);
+                }
+                if let Some(crop_drag) = self.crop_drag {
+                    self.drag_crop(cx, scope, crop_drag, event.abs);
+                }
+                self.hover_pos = Some(event.abs);
+                self.update_hovered_pixel(cx, scope);
+            }
+            Hit::FingerUp(event) => {
+                self.drag_pos = None;
+                self.touches
+                    .retain(|(digit_id, _)| *digit_id != event.digit_id);
+                // Clicking beside the crop rectangle removes it.
+                if self.crop_drag.take().is_some()
+                    && self.crop.is_some_and(|crop| {
+                        crop.size.x < 1.0 || crop.size.y < 1.0
+                    })
+                {
+                    self.change_crop(cx, scope, None);
+                }
+            }
+            _ => {}
+        }
+
         self.view.handle_event(cx, event, scope)
     }
 }
 
+/// How the compare view lays out the two images.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum CompareMode {
+    #[default]
+    SideBySide,
+    /// Both images fill the view, with the second one shown right of a
+    /// divider that can be dragged.
+    Wipe,
+}
+
+/// What dragging over the compare view does.
+#[derive(Clone, Copy, Debug)]
+enum CompareDrag {
+    /// Pans both images, from where the finger was last seen.
+    Pan(DVec2),
+    /// Moves the divider of the wipe.
+    Wipe,
+}
+
+/// Two images side by side, or on top of each other with a wipe between
+/// them. They zoom and pan together, the way a single image does in the
+/// slideshow, so the same part of both is in view.
 #[derive(Live, LiveHook, Widget)]
-pub struct ImageGridRow {
+pub struct CompareView {
     #[deref]
     view: View,
+    #[live]
+    draw_divider: DrawQuad,
+    #[rust]
+    image_sizes: [Option<ImageSize>; 2],
+    #[rust]
+    mode: CompareMode,
+    #[rust]
+    zoom: Zoom,
+    #[rust(1.0)]
+    dpi_factor: f64,
+    /// Where the wipe divides the view, as a fraction of its width.
+    #[rust(0.5)]
+    wipe_pos: f64,
+    #[rust]
+    drag: Option<CompareDrag>,
+    /// The size of the view when it was last drawn.
+    #[rust]
+    view_size: DVec2,
 }
 
-impl Widget for ImageGridRow {
+impl CompareView {
+    fn pane(&self, pane_idx: usize) -> ViewRef {
+        match pane_idx {
+            0 => self.view.view(id!(first)),
+            _ => self.view.view(id!(second)),
+        }
+    }
+
+    fn set_image(
+        &mut self,
+        cx: &mut Cx,
+        pane_idx: usize,
+        texture: &Texture,
+        image_size: Option<ImageSize>,
+    ) {
+        let image = self.pane(pane_idx).image(id!(image));
+        image.set_texture(cx, Some(texture.clone()));
+        self.image_sizes[pane_idx] = image_size;
+        self.view.redraw(cx);
+    }
+
+    fn show_placeholder(
+        &mut self,
+        cx: &mut Cx,
+        pane_idx: usize,
+        placeholder: &str,
+    ) {
+        let image = self.pane(pane_idx).image(id!(image));
+        image.load_image_dep_by_path(cx, placeholder).unwrap();
+        self.image_sizes[pane_idx] = None;
+        self.view.redraw(cx);
+    }
+
+    fn set_caption(&mut self, cx: &mut Cx, pane_idx: usize, caption: &str) {
+        self.pane(pane_idx)
+            .label(id!(caption))
+            .set_text(cx, caption);
+    }
+
+    fn toggle_mode(&mut self, cx: &mut Cx) {
+        self.mode = match self.mode {
+            CompareMode::SideBySide => CompareMode::Wipe,
+            CompareMode::Wipe => CompareMode::SideBySide,
+        };
+        self.view.redraw(cx);
+    }
+
+    fn set_zoom(&mut self, cx: &mut Cx, zoom: Zoom) {
+        if self.zoom != zoom {
+            self.zoom = zoom;
+            self.view.redraw(cx);
+        }
+    }
+
+    /// Zooms by `factor` around the center of the panes.
+    fn zoom_by(&mut self, cx: &mut Cx, factor: f64) {
+        if let Some(viewport) = self.viewport(0).or_else(|| self.viewport(1)) {
+            let zoom = self.zoom.zoom_at(factor, viewport.center(), viewport);
+            self.set_zoom(cx, zoom);
+        }
+    }
+
+    /// The part of the view a pane shows, relative to the view.
+    fn pane_rect(&self, pane_idx: usize) -> Rect {
+        let size = self.view_size;
+        let (left, right) = match (self.mode, pane_idx) {
+            (CompareMode::SideBySide, 0) => (0.0, size.x / 2.0),
+            (CompareMode::SideBySide, _) => (size.x / 2.0, size.x),
+            (CompareMode::Wipe, 0) => (0.0, size.x),
+            (CompareMode::Wipe, _) => (size.x * self.wipe_pos, size.x),
+        };
+        Rect {
+            pos: dvec2(left, 0.0),
+            size: dvec2(right - left, size.y),
+        }
+    }
+
+    /// The viewport the image of a pane is zoomed in. In a wipe, both images
+    /// are zoomed in the whole view, so they line up.
+    fn viewport(&self, pane_idx: usize) -> Option<Viewport> {
+        let image_size = self.image_sizes[pane_idx]?;
+        let view_size = match self.mode {
+            CompareMode::SideBySide => {
+                dvec2(self.view_size.x / 2.0, self.view_size.y)
+            }
+            CompareMode::Wipe => self.view_size,
+        };
+        if image_size.pixels() == 0 || view_size.x <= 0.0 || view_size.y <= 0.0
+        {
+            return None;
+        }
+        Some(Viewport {
+            image_size: dvec2(
+                image_size.width as f64,
+                image_size.height as f64,
+            ),
+            view_size,
+            dpi_factor: self.dpi_factor,
+        })
+    }
+
+    /// Where the viewport of a pane starts, relative to the view.
+    fn viewport_offset(&self, pane_idx: usize) -> f64 {
+        match self.mode {
+            CompareMode::SideBySide => self.pane_rect(pane_idx).pos.x,
+            CompareMode::Wipe => 0.0,
+        }
+    }
+
+    /// The viewport of the image under `point` of the view, along with the
+    /// point relative to that viewport.
+    fn viewport_at(&self, point: DVec2) -> Option<(Viewport, DVec2)> {
+        let pane_idx = if point.x < self.pane_rect(1).pos.x {
+            0
+        } else {
+            1
+        };
+        let viewport = self.viewport(pane_idx)?;
+        let offset = self.viewport_offset(pane_idx);
+        Some((viewport, dvec2(point.x - offset, point.y)))
+    }
+
+    /// Where the image of a pane goes, relative to the pane.
+    fn image_rect(&self, pane_idx: usize) -> Option<Rect> {
+        let viewport = self.viewport(pane_idx)?;
+        let rect = self.zoom.clamped(viewport).image_rect(viewport);
+        let offset =
+            self.viewport_offset(pane_idx) - self.pane_rect(pane_idx).pos.x;
+        Some(Rect {
+            pos: dvec2(rect.pos.x + offset, rect.pos.y),
+            size: rect.size,
+        })
+    }
+}
+
+impl Widget for CompareView {
     fn draw_walk(
         &mut self,
         cx: &mut Cx2d,
         scope: &mut Scope,
         walk: Walk,
     ) -> DrawStep {
-        while let Some(item) = self.view.draw_walk(cx, scope, walk).step() {
-            let state = scope.data.get_mut::<State>().unwrap();
-            let row_idx = *scope.props.get::<usize>().unwrap();
-
-            if let Some(mut list) = item.as_portal_list().borrow_mut() {
-                list.set_item_range(cx, 0, state.num_images_for_row(row_idx));
-
-                while let Some(item_idx) = list.next_visible_item(cx) {
-                    if item_idx >= state.num_images_for_row(row_idx) {
-                        continue;
-                    }
-
-                    let item = list.item(cx, item_idx, live_id!(Item));
+        self.dpi_factor = cx.current_dpi_factor();
+        for pane_idx in 0..2 {
+            let pane_rect = self.pane_rect(pane_idx);
+            let pane = self.pane(pane_idx);
+            pane.apply_over(
+                cx,
+                live! {
+                    width: (pane_rect.size.x),
+                    margin: {
+                        left: (pane_rect.pos.x),
+                    },
+                },
+            );
+            let image = pane.image(id!(image));
+            match self.image_rect(pane_idx) {
+                Some(rect) => image.apply_over(
+                    cx,
+                    live! {
+                        width: (rect.size.x),
+                        height: (rect.size.y),
+                        margin: {
+                            left: (rect.pos.x),
+                            top: (rect.pos.y),
+                        },
+                        fit: Stretch,
+                    },
+                ),
+                None => image.apply_over(
+                    cx,
+                    live! {
+                        width: Fill,
+                        height: Fill,
+                        margin: {
+                            left: 0.0,
+                            top: 0.0,
+                        },
+                        fit: Biggest,
+                    },
+                ),
+            }
+        }
 
-                    let image = item.image(id!(image));
-                    let first_image_idx = state.first_image_for_row(row_idx);
-                    let image_idx = first_image_idx + item_idx;
-                    let image_path = &state.image_paths[image_idx];
-                    image.load_image_file_by_path_async(cx, &image_path).unwrap();
+        self.view.draw_walk(cx, scope, walk)?;
+        let rect = self.view.area().rect(cx);
+        if self.mode == CompareMode::Wipe {
+            let divider_rect = Rect {
+                pos: dvec2(
+                    rect.pos.x + rect.size.x * self.wipe_pos - 1.0,
+                    rect.pos.y,
+                ),
+                size: dvec2(2.0, rect.size.y),
+            };
+            self.draw_divider.draw_abs(cx, divider_rect);
+        }
 
-                    item.draw_all(cx, &mut Scope::empty());
-                }
-            }
+        // The panes are laid out for the size the view had when it was last
+        // drawn, so they're laid out again when it was resized.
+        if self.view_size != rect.size {
+            self.view_size = rect.size;
+            self.view.redraw(cx);
         }
         DrawStep::done()
     }
 
     fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        let origin = self.view.area().rect(cx).pos;
+        match event.hits(cx, self.view.area()) {
+            Hit::FingerScroll(event) => {
+                if let Some((viewport, point)) =
+                    self.viewport_at(event.abs - origin)
+                {
+                    let factor = SCROLL_ZOOM_FACTOR.powf(-event.scroll.y);
+                    let zoom = self.zoom.zoom_at(factor, point, viewport);
+                    self.set_zoom(cx, zoom);
+                }
+            }
+            Hit::FingerDown(event) if event.tap_count == 2 => {
+                if let Some((viewport, point)) =
+                    self.viewport_at(event.abs - origin)
+                {
+                    let zoom = match self.zoom {
+                        Zoom::Fit => self.zoom.scale_at(
+                            viewport.actual_size_scale(),
+                            point,
+                            viewport,
+                        ),
+                        Zoom::Fixed { .. } => Zoom::Fit,
+                    };
+                    self.set_zoom(cx, zoom);
+                }
+            }
+            Hit::FingerDown(event) => {
+                let wipe_x = origin.x + self.view_size.x * self.wipe_pos;
+                let is_on_divider = self.mode == CompareMode::Wipe
+                    && (event.abs.x - wipe_x).abs() <= WIPE_HANDLE_SIZE;
+                self.drag = Some(if is_on_divider {
+                    CompareDrag::Wipe
+                } else {
+                    CompareDrag::Pan(event.abs)
+                });
+            }
+            Hit::FingerMove(event) => match self.drag {
+                Some(CompareDrag::Pan(drag_pos)) => {
+                    if let Some(viewport) =
+                        self.viewport(0).or_else(|| self.viewport(1))
+                    {
+                        let zoom =
+                            self.zoom.pan(event.abs - drag_pos, viewport);
+                        self.set_zoom(cx, zoom);
+                    }
+                    self.drag = Some(CompareDrag::Pan(event.abs));
+                }
+                Some(CompareDrag::Wipe) if self.view_size.x > 0.0 => {
+                    self.wipe_pos = ((event.abs.x - origin.x)
+                        / self.view_size.x)
+                        .clamp(0.0, 1.0);
+                    self.view.redraw(cx);
+                }
+                _ => {}
+            },
+            Hit::FingerUp(_) => self.drag = None,
+            _ => {}
+        }
+
         self.view.handle_event(cx, event, scope)
     }
 }
 
+const GRID_ITEM_SIZE: f64 = 256.0;
+const MIN_GRID_ITEM_SIZE: f64 = 64.0;
+const MAX_GRID_ITEM_SIZE: f64 = 512.0;
+const HOVER_OFF_SCALE: f64 = 230.0 / 256.0;
+const RUBBER_BAND_THRESHOLD: f64 = 4.0;
+const GROUP_HEADER_HEIGHT: f64 = 40.0;
+const KEY_ZOOM_FACTOR: f64 = 1.25;
+const LOUPE_SIZE: f64 = 192.0;
+/// How long the adjustments have to stay the same before they are written to
+/// the recipe, in seconds.
+const RECIPE_SAVE_DELAY: f64 = 0.5;
+/// How close to a corner of the crop rectangle a drag resizes it, in
+/// logical pixels.
+const CROP_HANDLE_SIZE: f64 = 12.0;
+/// How close to the divider of a wipe a drag moves it, in logical pixels.
+const WIPE_HANDLE_SIZE: f64 = 8.0;
+const TAG_SUGGESTION_BUTTONS: [&[LiveId]; 3] = [
+    id!(tag_suggestion_0),
+    id!(tag_suggestion_1),
+    id!(tag_suggestion_2),
+];
+/// How much a scroll of one pixel zooms the slideshow image by.
+const SCROLL_ZOOM_FACTOR: f64 = 1.002;
+
+fn label_color(label: ColorLabel) -> Vec4 {
+    match label {
+        ColorLabel::Red => vec4(0.9, 0.25, 0.25, 1.0),
+        ColorLabel::Yellow => vec4(0.95, 0.8, 0.2, 1.0),
+        ColorLabel::Green => vec4(0.3, 0.75, 0.3, 1.0),
+        ColorLabel::Blue => vec4(0.25, 0.5, 0.95, 1.0),
+        ColorLabel::Purple => vec4(0.65, 0.35, 0.85, 1.0),
+    }
+}
+
+/// Formats a color as `#RRGGBBAA`.
+fn hex_color([r, g, b, a]: [u8; 4]) -> String {
+    format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
+}
+
+/// Thumbnails are decoded at a few fixed sizes rather than at the exact item
+/// size, so zooming doesn't invalidate the cache at every step.
+fn thumbnail_size_for(item_size: f64) -> usize {
+    [128, 256, 512]
+        .into_iter()
+        .find(|&size| item_size <= size as f64)
+        .unwrap_or(512)
+}
+
 struct State {
+    /// All images in the folder, in sort order.
     image_paths: Vec<PathBuf>,
-    max_images_per_row: usize,
+    /// The images that match the filter, in sort order. These are the ones
+    /// shown in the grid and the slideshow, and image indices refer to them.
+    visible_image_paths: Vec<PathBuf>,
+    filter: Filter,
+    decode_pool: DecodePool,
+    thumbnails: Thumbnails,
+    image_infos: ImageInfos,
+    ratings: Ratings,
+    image_dir: PathBuf,
+    sort: SortOptions,
+    grid_layout: GridLayout,
+    grid_width: f64,
+    grid_item_size: f64,
+    rows: Vec<GridRow>,
+    layout_is_dirty: bool,
     current_image_idx: usize,
+    highlighted_image_idx: Option<usize>,
+    focused_image_idx: Option<usize>,
+    scroll_to_image_idx: Option<usize>,
+    selection: Selection,
 }
 
 impl State {
     fn num_images(&self) -> usize {
-        self.image_paths.len()
+        self.visible_image_paths.len()
+    }
+
+    fn current_image_path(&self) -> Option<&PathBuf> {
+        self.visible_image_paths.get(self.current_image_idx)
+    }
+
+    /// Applies a change to the images in the folder. `ratings` come with a
+    /// fresh scan, and are otherwise kept.
+    fn apply_dir_changes(
+        &mut self,
+        image_paths: Vec<PathBuf>,
+        ratings: Option<Ratings>,
+        renames: &[(PathBuf, PathBuf)],
+    ) {
+        for (from, to) in renames {
+            for paths in [&mut self.image_paths, &mut self.visible_image_paths]
+            {
+                if let Some(path) = paths.iter_mut().find(|path| *path == from)
+                {
+                    *path = to.clone();
+                }
+            }
+            self.image_infos.rename(from, to);
+            self.ratings.rename(from, to);
+            self.selection.rename(from, to);
+        }
+        if let Some(ratings) = ratings {
+            self.ratings = ratings;
+        }
+
+        let old_image_paths: HashSet<&PathBuf> =
+            self.image_paths.iter().collect();
+        let added_image_paths: Vec<PathBuf> = image_paths
+            .iter()
+            .filter(|path| !old_image_paths.contains(path))
+            .cloned()
+            .collect();
+        drop(old_image_paths);
+
+        // If the current image was removed, we move on to the closest image
+        // after it that survived, or else the closest one before it.
+        let new_image_paths: HashSet<PathBuf> =
+            image_paths.into_iter().collect();
+        let current_image_idx = self.current_image_idx.min(self.num_images());
+        let anchor_path = self.visible_image_paths[current_image_idx..]
+            .iter()
+            .chain(self.visible_image_paths[..current_image_idx].iter().rev())
+            .find(|path| new_image_paths.contains(*path))
+            .cloned();
+
+        for path in &self.image_paths {
+            if !new_image_paths.contains(path) {
+                self.thumbnails.remove(path);
+                self.image_infos.remove(path);
+            }
+        }
+        self.image_paths
+            .retain(|path| new_image_paths.contains(path));
+        self.selection.retain(|path| new_image_paths.contains(path));
+        self.image_paths.extend(added_image_paths);
+        self.sort.sort(
+            &mut self.image_paths,
+            &self.image_infos,
+            &self.ratings,
+            &self.image_dir,
+        );
+        self.update_visible_images();
+
+        self.current_image_idx = anchor_path
+            .and_then(|anchor_path| self.image_idx(&anchor_path))
+            .unwrap_or(0);
+        self.highlighted_image_idx = None;
+        self.focused_image_idx = None;
+        self.invalidate_layout();
+    }
+
+    fn image_idx(&self, path: &Path) -> Option<usize> {
+        self.visible_image_paths.iter().position(|p| p == path)
+    }
+
+    fn set_sort(&mut self, sort: SortOptions) {
+        if sort != self.sort {
+            self.sort = sort;
+            self.sort_images();
+        }
+    }
+
+    fn set_filter(&mut self, filter: Filter) {
+        if filter != self.filter {
+            self.filter = filter;
+            self.update_visible_images();
+        }
+    }
+
+    fn sort_images(&mut self) {
+        self.sort.sort(
+            &mut self.image_paths,
+            &self.image_infos,
+            &self.ratings,
+            &self.image_dir,
+        );
+        self.update_visible_images();
+    }
+
+    /// Updates the visible images after the images were sorted or the filter
+    /// changed. The current, focused and highlighted images are kept if they
+    /// are still visible, so the slideshow carries on from the same image.
+    fn update_visible_images(&mut self) {
+        let path_for = |image_idx: Option<usize>| {
+            image_idx
+                .and_then(|image_idx| self.visible_image_paths.get(image_idx))
+        };
+        let current_path = path_for(Some(self.current_image_idx)).cloned();
+        let focused_path = path_for(self.focused_image_idx).cloned();
+        let highlighted_path = path_for(self.highlighted_image_idx).cloned();
+
+        self.visible_image_paths = self
+            .image_paths
+            .iter()
+            .filter(|path| {
+                self.filter.matches(
+                    path,
+                    self.image_infos.get(path),
+                    self.ratings.get(path),
+                )
+            })
+            .cloned()
+            .collect();
+
+        self.current_image_idx = current_path
+            .and_then(|path| self.image_idx(&path))
+            .unwrap_or(0);
+        self.focused_image_idx =
+            focused_path.and_then(|path| self.image_idx(&path));
+        self.highlighted_image_idx =
+            highlighted_path.and_then(|path| self.image_idx(&path));
+        self.invalidate_layout();
+    }
+
+    /// Handles a batch of image infos. Orderings that depend on the infos
+    /// are only applied once all of them arrived, so the grid doesn't
+    /// reshuffle with every batch, but filters are applied as they arrive.
+    fn handle_image_info_action(&mut self, action: &ImageInfoAction) -> bool {
+        if !self.image_infos.handle_action(action) {
+            return false;
+        }
+        if self.sort.needs_infos() && self.image_infos.is_complete() {
+            self.sort_images();
+        } else if self.filter.needs_infos() {
+            self.update_visible_images();
+        }
+        self.invalidate_layout();
+        true
+    }
+
+    /// The images file operations in the grid act on: the selection, in
+    /// grid order, or else the image under the keyboard cursor.
+    fn selected_paths(&self) -> Vec<PathBuf> {
+        let paths: Vec<PathBuf> = self
+            .visible_image_paths
+            .iter()
+            .filter(|path| self.selection.contains(path))
+            .cloned()
+            .collect();
+        if !paths.is_empty() {
+            return paths;
+        }
+        self.focused_image_idx
+            .and_then(|image_idx| self.visible_image_paths.get(image_idx))
+            .cloned()
+            .into_iter()
+            .collect()
+    }
+
+    fn set_grid_item_size(&mut self, item_size: f64) -> bool {
+        let item_size = item_size
+            .clamp(MIN_GRID_ITEM_SIZE, MAX_GRID_ITEM_SIZE)
+            .round();
+        if item_size == self.grid_item_size {
+            return false;
+        }
+        self.grid_item_size = item_size;
+        self.invalidate_layout();
+        true
+    }
+
+    fn set_grid_layout(&mut self, grid_layout: GridLayout) {
+        if grid_layout != self.grid_layout {
+            self.grid_layout = grid_layout;
+            self.invalidate_layout();
+        }
+    }
+
+    /// Marks the layout as out of date, so the next call to `update_layout`
+    /// recomputes it even if the grid width didn't change.
+    fn invalidate_layout(&mut self) {
+        self.layout_is_dirty = true;
+    }
+
+    /// Recomputes the layout of the rows for a grid of the given width, if
+    /// needed. Returns whether the rows may have changed.
+    fn update_layout(&mut self, width: f64) -> bool {
+        if width == self.grid_width && !self.layout_is_dirty {
+            return false;
+        }
+        self.grid_width = width;
+        self.layout_is_dirty = false;
+
+        let mut rows = Vec::new();
+        for (title, images) in self.groups() {
+            if let Some(title) = title {
+                rows.push(GridRow::header(
+                    images.start,
+                    GROUP_HEADER_HEIGHT,
+                    title,
+                ));
+            }
+            match self.grid_layout {
+                GridLayout::Square => {
+                    let images_per_row =
+                        ((width / self.grid_item_size) as usize).max(1);
+                    rows.extend(grid_layout::square_rows(
+                        images.start,
+                        images.len(),
+                        images_per_row,
+                        self.grid_item_size,
+                    ));
+                }
+                GridLayout::Justified => {
+                    let aspect_ratios = self.visible_image_paths
+                        [images.clone()]
+                    .iter()
+                    .map(|path| self.aspect_ratio(path));
+                    rows.extend(grid_layout::justify_rows(
+                        images.start,
+                        aspect_ratios,
+                        width,
+                        self.grid_item_size,
+                    ));
+                }
+            }
+        }
+
+        let changed = rows != self.rows;
+        self.rows = rows;
+        changed
+    }
+
+    /// Splits the images into runs that belong to the same group, each with
+    /// its title. Without grouping, all images form a single untitled run.
+    fn groups(&self) -> Vec<(Option<String>, Range<usize>)> {
+        let grouping = self.sort.grouping;
+        if grouping == Grouping::None {
+            return vec![(None, 0..self.num_images())];
+        }
+
+        let mut groups = Vec::new();
+        let mut start = 0;
+        let mut key = None;
+        for (image_idx, path) in self.visible_image_paths.iter().enumerate() {
+            let info = self.image_infos.get(path);
+            let image_key = grouping.key(path, info, &self.image_dir);
+            if image_idx > 0 && image_key != key {
+                let title = grouping.title(key.as_deref(), &self.image_dir);
+                groups.push((Some(title), start..image_idx));
+                start = image_idx;
+            }
+            key = image_key;
+        }
+        if start < self.num_images() {
+            let title = grouping.title(key.as_deref(), &self.image_dir);
+            groups.push((Some(title), start..self.num_images()));
+        }
+        groups
+    }
+
+    /// Images whose size is not known yet are laid out as squares until it
+    /// is.
+    fn aspect_ratio(&self, path: &Path) -> f64 {
+        self.image_infos
+            .get(path)
+            .and_then(|info| info.size)
+            .map_or(1.0, |size| size.aspect_ratio())
     }
 
     fn num_rows(&self) -> usize {
-        self.num_images().div_ceil(self.max_images_per_row)
+        self.rows.len()
+    }
+
+    fn row_for_image(&self, image_idx: usize) -> usize {
+        self.rows
+            .partition_point(|row| row.first_image_idx <= image_idx)
+            .saturating_sub(1)
     }
 
     fn first_image_for_row(&self, row_idx: usize) -> usize {
-        row_idx * self.max_images_per_row
+        self.rows
+            .get(row_idx)
+            .map_or(self.num_images(), |row| row.first_image_idx)
     }
 
     fn num_images_for_row(&self, row_idx: usize) -> usize {
-        let first_image_idx = self.first_image_for_row(row_idx);
-        let num_remaining_images = self.num_images() - first_image_idx;
-        num_remaining_images.min(self.max_images_per_row)
+        self.rows.get(row_idx).map_or(0, |row| row.num_images)
+    }
+
+    fn row_title(&self, row_idx: usize) -> Option<&str> {
+        self.rows.get(row_idx)?.title.as_deref()
+    }
+
+    fn row_height(&self, row_idx: usize) -> f64 {
+        self.rows
+            .get(row_idx)
+            .map_or(self.grid_item_size, |row| row.height)
+    }
+
+    /// How far down the content of the grid the row starts.
+    fn row_top(&self, row_idx: usize) -> f64 {
+        let row_idx = row_idx.min(self.num_rows());
+        self.rows[..row_idx].iter().map(|row| row.height).sum()
+    }
+
+    /// The images whose cells overlap `rect`, which is relative to the top
+    /// left corner of the content of the grid.
+    fn images_in_rect(&self, rect: Rect) -> Vec<usize> {
+        let min = (rect.pos.x, rect.pos.y);
+        let max = (rect.pos.x + rect.size.x, rect.pos.y + rect.size.y);
+        grid_layout::images_in_area(
+            &self.rows,
+            |image_idx| self.item_width(image_idx),
+            min,
+            max,
+        )
+    }
+
+    fn item_width(&self, image_idx: usize) -> f64 {
+        match self.grid_layout {
+            GridLayout::Square => self.grid_item_size,
+            GridLayout::Justified => {
+                let row_idx = self.row_for_image(image_idx);
+                let path = &self.visible_image_paths[image_idx];
+                self.row_height(row_idx) * self.aspect_ratio(path)
+            }
+        }
     }
 }
 
 impl Default for State {
     fn default() -> Self {
+        let decode_pool = DecodePool::default();
         Self {
             image_paths: Vec::new(),
-            max_images_per_row: 4,
+            visible_image_paths: Vec::new(),
+            filter: Filter::default(),
+            thumbnails: Thumbnails::new(
+                decode_pool.clone(),
+                thumbnail_size_for(GRID_ITEM_SIZE),
+                Config::default().thumbnail_cache_max_bytes,
+            ),
+            decode_pool,
+            image_infos: ImageInfos::default(),
+            ratings: Ratings::default(),
+            image_dir: PathBuf::new(),
+            sort: SortOptions::default(),
+            grid_layout: GridLayout::default(),
+            grid_width: 0.0,
+            grid_item_size: GRID_ITEM_SIZE,
+            rows: Vec::new(),
+            layout_is_dirty: true,
             current_image_idx: 0,
+            highlighted_image_idx: None,
+            focused_image_idx: None,
+            scroll_to_image_idx: None,
+            selection: Selection::default(),
         }
     }
 }
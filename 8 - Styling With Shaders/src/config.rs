@@ -0,0 +1,318 @@
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::scan::ScanOptions;
+
+pub const APP_NAME: &str = "image_viewer";
+pub const IMAGE_DIR_ENV_VAR: &str = "IMAGE_VIEWER_DIR";
+const DEFAULT_IMAGE_DIR: &str = "../images";
+const DEFAULT_THUMBNAIL_CACHE_MB: u64 = 512;
+const DEFAULT_TEXTURE_CACHE_MB: u64 = 512;
+const DEFAULT_PREFETCH_WINDOW: usize = 2;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub image_dir: PathBuf,
+    pub initial_image: Option<PathBuf>,
+    pub recursive: bool,
+    pub thumbnail_cache_max_bytes: u64,
+    pub texture_cache_max_bytes: u64,
+    pub prefetch_window: usize,
+}
+
+impl Config {
+    /// Resolves the configuration from, in order of precedence, the first
+    /// command-line argument, the config file, the `IMAGE_VIEWER_DIR`
+    /// environment variable and finally the built-in default.
+    ///
+    /// A config file that can't be read is left out rather than taking the
+    /// argument and the environment variable down with it, and the error is
+    /// returned alongside the configuration.
+    pub fn load() -> (Self, Option<ConfigError>) {
+        let (table, error) = match config_file_path() {
+            Some(path) if path.is_file() => match read_table(&path) {
+                Ok(table) => (table, None),
+                Err(error) => (Table::new(), Some(error)),
+            },
+            _ => (Table::new(), None),
+        };
+        let arg = env::args_os().nth(1).map(PathBuf::from);
+        let env_dir = env::var_os(IMAGE_DIR_ENV_VAR).map(PathBuf::from);
+        let mut config = Self::resolve(arg, &table, env_dir);
+        // The paths of images are found by joining their names onto the
+        // image directory, so making it canonical once makes them canonical
+        // too, and comparable with the paths file operations report.
+        if let Ok(image_dir) = config.image_dir.canonicalize() {
+            config.image_dir = image_dir;
+        }
+        if let Some(initial_image) = &mut config.initial_image
+            && let Ok(path) = initial_image.canonicalize()
+        {
+            *initial_image = path;
+        }
+        (config, error)
+    }
+
+    pub fn resolve(
+        arg: Option<PathBuf>,
+        table: &Table,
+        env_dir: Option<PathBuf>,
+    ) -> Self {
+        let mut config = Self::default();
+
+        if let Some(recursive) = table.get("recursive").and_then(Value::as_bool)
+        {
+            config.recursive = recursive;
+        }
+        if let Some(megabytes) = table
+            .get("thumbnail_cache_mb")
+            .and_then(Value::as_integer)
+            .and_then(|megabytes| u64::try_from(megabytes).ok())
+        {
+            config.thumbnail_cache_max_bytes =
+                megabytes.saturating_mul(1024 * 1024);
+        }
+        if let Some(megabytes) = table
+            .get("texture_cache_mb")
+            .and_then(Value::as_integer)
+            .and_then(|megabytes| u64::try_from(megabytes).ok())
+        {
+            config.texture_cache_max_bytes =
+                megabytes.saturating_mul(1024 * 1024);
+        }
+        if let Some(prefetch_window) = table
+            .get("prefetch_window")
+            .and_then(Value::as_integer)
+            .and_then(|prefetch_window| usize::try_from(prefetch_window).ok())
+        {
+            config.prefetch_window = prefetch_window;
+        }
+
+        let path = arg
+            .or_else(|| {
+                table
+                    .get("image_dir")
+                    .and_then(Value::as_str)
+                    .map(expand_home)
+            })
+            .or(env_dir);
+
+        if let Some(path) = path {
+            if path.is_file() {
+                config.image_dir = match path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => {
+                        parent.to_path_buf()
+                    }
+                    _ => PathBuf::from("."),
+                };
+                config.initial_image = Some(path);
+            } else {
+                config.image_dir = path;
+            }
+        }
+
+        config
+    }
+
+    pub fn scan_options(&self) -> ScanOptions {
+        ScanOptions {
+            recursive: self.recursive,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            image_dir: PathBuf::from(DEFAULT_IMAGE_DIR),
+            initial_image: None,
+            recursive: false,
+            thumbnail_cache_max_bytes: DEFAULT_THUMBNAIL_CACHE_MB * 1024 * 1024,
+            texture_cache_max_bytes: DEFAULT_TEXTURE_CACHE_MB * 1024 * 1024,
+            prefetch_window: DEFAULT_PREFETCH_WINDOW,
+        }
+    }
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+pub fn cache_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// The user's data directory, shared by all applications, as opposed to
+/// `config_dir` and `cache_dir`, which are specific to this one.
+pub fn data_home_dir() -> Option<PathBuf> {
+    xdg_base_dir("XDG_DATA_HOME", ".local/share")
+}
+
+fn xdg_dir(env_var: &str, home_fallback: &str) -> Option<PathBuf> {
+    Some(xdg_base_dir(env_var, home_fallback)?.join(APP_NAME))
+}
+
+fn xdg_base_dir(env_var: &str, home_fallback: &str) -> Option<PathBuf> {
+    env::var_os(env_var)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| home_dir().map(|home| home.join(home_fallback)))
+}
+
+pub fn config_file_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => match home_dir() {
+            Some(home) => home.join(rest),
+            None => PathBuf::from(path),
+        },
+        None => PathBuf::from(path),
+    }
+}
+
+pub use toml::{Table, Value};
+
+/// Reads a number that may have been written with or without a fraction.
+pub fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float(value) => Some(*value),
+        Value::Integer(value) => Some(*value as f64),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        error: toml::de::Error,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => {
+                write!(f, "{}: {}", path.display(), error)
+            }
+            Self::Parse { path, error } => {
+                write!(f, "{}: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+pub fn read_table(path: &Path) -> Result<Table, ConfigError> {
+    let source = fs::read_to_string(path).map_err(|error| ConfigError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    source.parse().map_err(|error| ConfigError::Parse {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+pub fn write_table(path: &Path, table: &Table) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, table.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_malformed_files() {
+        let path = env::temp_dir()
+            .join(format!("config-test-{}.toml", std::process::id()));
+        fs::write(&path, "recursive = true\nimage_dir =\n").unwrap();
+        let error = read_table(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(error, ConfigError::Parse { .. }));
+        assert!(error.to_string().contains("line 2"));
+    }
+
+    #[test]
+    fn round_trips_tables() {
+        let path = env::temp_dir()
+            .join(format!("config-round-trip-{}.toml", std::process::id()));
+        let mut table = Table::new();
+        table.insert(
+            "name".into(),
+            Value::String("tab\there \"quoted\" back\\slash".into()),
+        );
+        table.insert("size".into(), Value::Float(1.5));
+        write_table(&path, &table).unwrap();
+        let read = read_table(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read, table);
+    }
+
+    #[test]
+    fn resolves_image_dir_in_order_of_precedence() {
+        let table: Table = "image_dir = \"/from/file\"".parse().unwrap();
+        let env_dir = Some(PathBuf::from("/from/env"));
+        let image_dir = |arg: Option<&str>, table: &Table, env_dir| {
+            Config::resolve(arg.map(PathBuf::from), table, env_dir).image_dir
+        };
+        assert_eq!(
+            image_dir(Some("/from/arg"), &table, env_dir.clone()),
+            Path::new("/from/arg")
+        );
+        assert_eq!(
+            image_dir(None, &table, env_dir.clone()),
+            Path::new("/from/file")
+        );
+        assert_eq!(
+            image_dir(None, &Table::new(), env_dir),
+            Path::new("/from/env")
+        );
+        assert_eq!(
+            image_dir(None, &Table::new(), None),
+            Path::new(DEFAULT_IMAGE_DIR)
+        );
+    }
+
+    #[test]
+    fn opens_a_file_argument_in_its_folder() {
+        let path = env::temp_dir()
+            .join(format!("config-test-{}.png", std::process::id()));
+        fs::write(&path, b"").unwrap();
+        let config = Config::resolve(Some(path.clone()), &Table::new(), None);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(config.image_dir, env::temp_dir());
+        assert_eq!(config.initial_image, Some(path));
+    }
+
+    #[test]
+    fn saturates_huge_cache_sizes() {
+        let table: Table =
+            format!("thumbnail_cache_mb = {}\ntexture_cache_mb = -1", i64::MAX)
+                .parse()
+                .unwrap();
+        let config = Config::resolve(None, &table, None);
+        assert_eq!(config.thumbnail_cache_max_bytes, u64::MAX);
+        assert_eq!(
+            config.texture_cache_max_bytes,
+            DEFAULT_TEXTURE_CACHE_MB * 1024 * 1024
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Formats a time as `YYYY-MM-DD`, in UTC.
+pub fn format_date(time: SystemTime) -> String {
+    let (year, month, day, ..) = civil_from_time(time);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Formats a time as `YYYY-MM-DDThh:mm:ss`, in local time, or in UTC where
+/// the local time zone can't be found out.
+pub fn format_local_date_time(time: SystemTime) -> String {
+    let seconds = unix_seconds(time) + utc_offset(time).unwrap_or(0);
+    let (year, month, day, hour, minute, second) = civil_from_seconds(seconds);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// Converts an EXIF date, `YYYY:MM:DD HH:MM:SS`, to `YYYY-MM-DD`.
+pub fn exif_date(date_time: &str) -> Option<String> {
+    let date = date_time.get(..10)?;
+    Some(date.replace(':', "-"))
+}
+
+fn unix_seconds(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+/// How far ahead of UTC local time is at `time`, in seconds.
+#[cfg(unix)]
+fn utc_offset(time: SystemTime) -> Option<i64> {
+    let seconds = libc::time_t::try_from(unix_seconds(time)).ok()?;
+    // SAFETY: `tm` is plain data, for which all zeroes is a valid value.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    // SAFETY: both pointers are valid for the duration of the call, which
+    // doesn't hold on to them.
+    let result = unsafe { libc::localtime_r(&seconds, &mut tm) };
+    (!result.is_null()).then_some(tm.tm_gmtoff as i64)
+}
+
+#[cfg(not(unix))]
+fn utc_offset(_time: SystemTime) -> Option<i64> {
+    None
+}
+
+fn civil_from_time(time: SystemTime) -> (i64, u32, u32, u32, u32, u32) {
+    civil_from_seconds(unix_seconds(time))
+}
+
+fn civil_from_seconds(seconds: i64) -> (i64, u32, u32, u32, u32, u32) {
+    let days = seconds.div_euclid(86400);
+    let seconds_of_day = seconds.rem_euclid(86400) as u32;
+
+    // Howard Hinnant's days-to-civil algorithm.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+        - day_of_era / 146096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+    )
+}
@@ -0,0 +1,145 @@
+use crate::{image_format::ImageFormat, metadata::Header};
+use makepad_widgets::*;
+use std::{
+    fmt, fs,
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+#[derive(Clone)]
+pub struct DecodedImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl fmt::Debug for DecodedImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DecodedImage")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Posted by `decode_and_post` once a full-size image has been decoded.
+/// Decoded images can be huge, so the image is handed over through a
+/// `Mutex` for the receiver to take instead of being cloned.
+#[derive(Debug)]
+pub enum DecodeAction {
+    Decoded {
+        path: PathBuf,
+        image: Mutex<Option<DecodedImage>>,
+    },
+    Failed {
+        path: PathBuf,
+        error: String,
+    },
+}
+
+impl DecodeAction {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Decoded { path, .. } | Self::Failed { path, .. } => path,
+        }
+    }
+}
+
+pub fn decode_and_post(path: PathBuf) {
+    let action = match decode_file(&path) {
+        Ok(image) => DecodeAction::Decoded {
+            path,
+            image: Mutex::new(Some(image)),
+        },
+        Err(error) => DecodeAction::Failed {
+            path,
+            error: error.to_string(),
+        },
+    };
+    Cx::post_action(action);
+}
+
+impl DecodedImage {
+    /// The color of the pixel at `x`, `y`, as red, green, blue and alpha.
+    pub fn rgba(&self, x: usize, y: usize) -> Option<[u8; 4]> {
+        if x >= self.width {
+            return None;
+        }
+        let [b, g, r, a] = self.pixels.get(y * self.width + x)?.to_le_bytes();
+        Some([r, g, b, a])
+    }
+
+    pub fn into_texture(self, cx: &mut Cx) -> Texture {
+        texture_from_pixels(cx, self.width, self.height, self.pixels)
+    }
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Io(io::Error),
+    UnsupportedFormat,
+    Decode(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::UnsupportedFormat => write!(f, "unsupported image format"),
+            Self::Decode(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+pub fn decode_file(path: &Path) -> Result<DecodedImage, DecodeError> {
+    let data = fs::read(path)?;
+    decode(&data)
+}
+
+/// Decodes an image, turned upright according to its EXIF orientation.
+pub fn decode(data: &[u8]) -> Result<DecodedImage, DecodeError> {
+    let buffer = match ImageFormat::from_signature(data) {
+        Some(ImageFormat::Png) => ImageBuffer::from_png(data),
+        Some(ImageFormat::Jpeg) => ImageBuffer::from_jpg(data),
+        None => return Err(DecodeError::UnsupportedFormat),
+    }
+    .map_err(|error| DecodeError::Decode(format!("{:?}", error)))?;
+
+    let ImageBuffer {
+        width,
+        height,
+        data: pixels,
+        ..
+    } = buffer;
+    let orientation = Header::read(&mut Cursor::new(data))?.orientation();
+    let (width, height, pixels) = orientation.apply(width, height, pixels);
+    Ok(DecodedImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+pub fn texture_from_pixels(
+    cx: &mut Cx,
+    width: usize,
+    height: usize,
+    pixels: Vec<u32>,
+) -> Texture {
+    Texture::new_with_format(
+        cx,
+        TextureFormat::VecBGRAu8_32 {
+            width,
+            height,
+            data: Some(pixels),
+            updated: TextureUpdated::Full,
+        },
+    )
+}
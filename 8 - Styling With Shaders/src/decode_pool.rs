@@ -0,0 +1,254 @@
+use makepad_widgets::*;
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    path::PathBuf,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+};
+
+const MAX_WORKERS: usize = 4;
+
+/// How urgently a job should run. Jobs with a higher priority always run
+/// before jobs with a lower one; jobs with the same priority run in the order
+/// they were submitted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Prefetch,
+    Visible,
+    Current,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum JobKind {
+    Image,
+    Thumbnail,
+    Metadata,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct JobKey {
+    pub path: PathBuf,
+    pub kind: JobKind,
+}
+
+struct Job {
+    key: JobKey,
+    priority: Priority,
+    seq: u64,
+    task: Box<dyn FnOnce() + Send>,
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Job {}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Job {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: BinaryHeap<Job>,
+    next_seq: u64,
+    started: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    condvar: Condvar,
+}
+
+/// A pool of background threads that runs decode jobs by priority. Jobs
+/// are identified by a `JobKey`, so a queued job can be reprioritised by
+/// submitting it again, or cancelled before it starts running. Jobs report
+/// their results by posting actions to the UI thread.
+#[derive(Clone, Default)]
+pub struct DecodePool {
+    shared: Arc<Shared>,
+}
+
+impl DecodePool {
+    /// Queues `task` under `key`, replacing any job with the same key that
+    /// has not started yet.
+    pub fn submit(
+        &self,
+        cx: &mut Cx,
+        key: JobKey,
+        priority: Priority,
+        task: impl FnOnce() + Send + 'static,
+    ) {
+        {
+            let mut queue = self.shared.queue.lock().unwrap();
+            if !queue.started {
+                queue.started = true;
+                self.start_workers(cx);
+            }
+        }
+        self.queue_job(key, priority, Box::new(task));
+    }
+
+    fn queue_job(
+        &self,
+        key: JobKey,
+        priority: Priority,
+        task: Box<dyn FnOnce() + Send>,
+    ) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.jobs.retain(|job| job.key != key);
+        let seq = queue.next_seq;
+        queue.next_seq += 1;
+        queue.jobs.push(Job {
+            key,
+            priority,
+            seq,
+            task,
+        });
+        self.shared.condvar.notify_one();
+    }
+
+    /// Raises the priority of a queued job to at least `priority`. Returns
+    /// `false` if no job with that key is queued, e.g. because it is already
+    /// running.
+    pub fn raise_priority(&self, key: &JobKey, priority: Priority) -> bool {
+        let mut queue = self.shared.queue.lock().unwrap();
+        let mut jobs = std::mem::take(&mut queue.jobs).into_vec();
+        let job = jobs.iter_mut().find(|job| job.key == *key);
+        let found = job.is_some();
+        if let Some(job) = job {
+            job.priority = job.priority.max(priority);
+        }
+        queue.jobs = jobs.into();
+        found
+    }
+
+    /// Drops every queued job for which `f` returns `true` and returns their
+    /// keys. Jobs that are already running are not interrupted.
+    pub fn cancel(&self, mut f: impl FnMut(&JobKey) -> bool) -> Vec<JobKey> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        let mut cancelled = Vec::new();
+        queue.jobs.retain(|job| {
+            if f(&job.key) {
+                cancelled.push(job.key.clone());
+                false
+            } else {
+                true
+            }
+        });
+        cancelled
+    }
+
+    fn start_workers(&self, cx: &mut Cx) {
+        let num_workers = thread::available_parallelism()
+            .map_or(1, |n| n.get().saturating_sub(1))
+            .clamp(1, MAX_WORKERS);
+        for _ in 0..num_workers {
+            let shared = self.shared.clone();
+            cx.spawn_thread(move || run_worker(&shared));
+        }
+    }
+}
+
+fn run_worker(shared: &Shared) {
+    loop {
+        let job = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                match queue.jobs.pop() {
+                    Some(job) => break job,
+                    None => queue = shared.condvar.wait(queue).unwrap(),
+                }
+            }
+        };
+        (job.task)();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str, kind: JobKind) -> JobKey {
+        JobKey {
+            path: PathBuf::from(name),
+            kind,
+        }
+    }
+
+    fn queue(pool: &DecodePool, name: &str, priority: Priority) {
+        pool.queue_job(key(name, JobKind::Image), priority, Box::new(|| {}));
+    }
+
+    /// Takes the jobs off the queue in the order the workers would run them.
+    fn drain(pool: &DecodePool) -> Vec<String> {
+        let mut queue = pool.shared.queue.lock().unwrap();
+        std::iter::from_fn(|| queue.jobs.pop())
+            .map(|job| job.key.path.display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn runs_jobs_by_priority_then_in_order() {
+        let pool = DecodePool::default();
+        queue(&pool, "a", Priority::Prefetch);
+        queue(&pool, "b", Priority::Visible);
+        queue(&pool, "c", Priority::Visible);
+        queue(&pool, "d", Priority::Current);
+        assert_eq!(drain(&pool), ["d", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn replaces_jobs_submitted_again() {
+        let pool = DecodePool::default();
+        queue(&pool, "a", Priority::Visible);
+        queue(&pool, "b", Priority::Visible);
+        queue(&pool, "a", Priority::Visible);
+        assert_eq!(drain(&pool), ["b", "a"]);
+    }
+
+    #[test]
+    fn only_ever_raises_priorities() {
+        let pool = DecodePool::default();
+        queue(&pool, "a", Priority::Prefetch);
+        queue(&pool, "b", Priority::Visible);
+        queue(&pool, "c", Priority::Current);
+        let a = key("a", JobKind::Image);
+        let c = key("c", JobKind::Image);
+        assert!(pool.raise_priority(&a, Priority::Current));
+        assert!(pool.raise_priority(&c, Priority::Prefetch));
+        assert!(
+            !pool.raise_priority(&key("d", JobKind::Image), Priority::Current)
+        );
+        assert_eq!(drain(&pool), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn cancels_queued_jobs() {
+        let pool = DecodePool::default();
+        queue(&pool, "a", Priority::Visible);
+        pool.queue_job(
+            key("b", JobKind::Thumbnail),
+            Priority::Visible,
+            Box::new(|| {}),
+        );
+        queue(&pool, "c", Priority::Visible);
+        let cancelled = pool.cancel(|key| key.kind == JobKind::Thumbnail);
+        assert_eq!(cancelled, [key("b", JobKind::Thumbnail)]);
+        assert_eq!(drain(&pool), ["a", "c"]);
+    }
+}
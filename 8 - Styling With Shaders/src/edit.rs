@@ -0,0 +1,785 @@
+use crate::{
+    adjustments::Adjustments,
+    decode::{self, DecodeError, DecodedImage},
+    exif,
+    image_format::ImageFormat,
+    metadata::Header,
+    orientation::Orientation,
+    xmp,
+};
+use makepad_widgets::*;
+use std::{
+    fmt, fs,
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+};
+
+const JPEG_QUALITY: u8 = 92;
+/// The identifier an EXIF segment of a JPEG starts with.
+const EXIF_ID: &[u8] = b"Exif\0\0";
+
+/// Changes to an image, previewed in the slideshow until they are saved.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Edit {
+    /// How to turn the image once it is upright.
+    pub orientation: Orientation,
+    /// What to keep of the image, in pixels of the image once it is turned.
+    pub crop: Option<Rect>,
+}
+
+impl Edit {
+    pub fn is_empty(&self) -> bool {
+        self.orientation == Orientation::Normal && self.crop.is_none()
+    }
+
+    pub fn apply(&self, image: DecodedImage) -> DecodedImage {
+        let (width, height, pixels) =
+            self.orientation
+                .apply(image.width, image.height, image.pixels);
+        let image = DecodedImage {
+            width,
+            height,
+            pixels,
+        };
+        match self.crop {
+            Some(crop) => crop_image(&image, crop),
+            None => image,
+        }
+    }
+}
+
+/// The aspect ratios the crop rectangle can be held to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AspectRatio {
+    #[default]
+    Free,
+    /// The aspect ratio of the image itself.
+    Original,
+    Square,
+    FourThree,
+    ThreeTwo,
+    SixteenNine,
+}
+
+impl AspectRatio {
+    pub const ALL: [Self; 6] = [
+        Self::Free,
+        Self::Original,
+        Self::Square,
+        Self::FourThree,
+        Self::ThreeTwo,
+        Self::SixteenNine,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Free => "Free",
+            Self::Original => "Original",
+            Self::Square => "1:1",
+            Self::FourThree => "4:3",
+            Self::ThreeTwo => "3:2",
+            Self::SixteenNine => "16:9",
+        }
+    }
+
+    /// The ratio of width to height for an image of the given size, or
+    /// `None` for any ratio. Ratios are turned to match the image, so a
+    /// portrait image is cropped to a portrait 4:3.
+    pub fn ratio(&self, image_size: DVec2) -> Option<f64> {
+        let ratio = match self {
+            Self::Free => return None,
+            Self::Original => return Some(image_size.x / image_size.y),
+            Self::Square => 1.0,
+            Self::FourThree => 4.0 / 3.0,
+            Self::ThreeTwo => 3.0 / 2.0,
+            Self::SixteenNine => 16.0 / 9.0,
+        };
+        Some(if image_size.y > image_size.x {
+            1.0 / ratio
+        } else {
+            ratio
+        })
+    }
+}
+
+/// The crop rectangle dragged out from `anchor` to `point`, in image pixels,
+/// held to `ratio` and kept within the image.
+pub fn crop_between(
+    anchor: DVec2,
+    point: DVec2,
+    ratio: Option<f64>,
+    image_size: DVec2,
+) -> Rect {
+    let point = dvec2(
+        point.x.clamp(0.0, image_size.x),
+        point.y.clamp(0.0, image_size.y),
+    );
+    let mut width = (point.x - anchor.x).abs();
+    let mut height = (point.y - anchor.y).abs();
+    // The rectangle only ever shrinks towards the anchor to fit the ratio,
+    // so it stays within the image.
+    if let Some(ratio) = ratio {
+        if width > height * ratio {
+            width = height * ratio;
+        } else {
+            height = width / ratio;
+        }
+    }
+    Rect {
+        pos: dvec2(
+            if point.x < anchor.x {
+                anchor.x - width
+            } else {
+                anchor.x
+            },
+            if point.y < anchor.y {
+                anchor.y - height
+            } else {
+                anchor.y
+            },
+        ),
+        size: dvec2(width, height),
+    }
+}
+
+/// Moves `crop` to `pos`, keeping it within the image.
+pub fn crop_moved_to(crop: Rect, pos: DVec2, image_size: DVec2) -> Rect {
+    Rect {
+        pos: dvec2(
+            pos.x.clamp(0.0, (image_size.x - crop.size.x).max(0.0)),
+            pos.y.clamp(0.0, (image_size.y - crop.size.y).max(0.0)),
+        ),
+        size: crop.size,
+    }
+}
+
+fn crop_image(image: &DecodedImage, crop: Rect) -> DecodedImage {
+    let clamp =
+        |value: f64, max: usize| (value.round().max(0.0) as usize).min(max);
+    let left = clamp(crop.pos.x, image.width);
+    let top = clamp(crop.pos.y, image.height);
+    let right = clamp(crop.pos.x + crop.size.x, image.width).max(left);
+    let bottom = clamp(crop.pos.y + crop.size.y, image.height).max(top);
+    let mut pixels = Vec::with_capacity((right - left) * (bottom - top));
+    for y in top..bottom {
+        let row = y * image.width;
+        pixels.extend_from_slice(&image.pixels[row + left..row + right]);
+    }
+    DecodedImage {
+        width: right - left,
+        height: bottom - top,
+        pixels,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveTarget {
+    Original,
+    /// A new file next to the original, which is left as it was.
+    NewFile,
+}
+
+/// Where an edit was saved, and how.
+#[derive(Debug)]
+pub struct SavedEdit {
+    pub path: PathBuf,
+    /// Whether only the orientation tag of a JPEG was changed, rather than
+    /// its pixels, which is lossless, but not heeded by every viewer.
+    pub is_tag_only: bool,
+}
+
+/// Posted once an edit has been saved.
+#[derive(Debug)]
+pub struct EditAction {
+    pub path: PathBuf,
+    pub result: Result<SavedEdit, String>,
+}
+
+/// Saves `edit` of the image at `path`, followed by `adjustments`, on a
+/// background thread and posts an `EditAction` once it is done.
+pub fn save(
+    cx: &mut Cx,
+    path: PathBuf,
+    edit: Edit,
+    adjustments: Adjustments,
+    target: SaveTarget,
+) {
+    cx.spawn_thread(move || {
+        let result = save_edit(&path, edit, adjustments, target)
+            .map_err(|error| error.to_string());
+        Cx::post_action(EditAction { path, result });
+    });
+}
+
+#[derive(Debug)]
+pub enum EditError {
+    Io(io::Error),
+    Decode(DecodeError),
+    Encode(String),
+    EmptyCrop,
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Decode(error) => write!(f, "{}", error),
+            Self::Encode(message) => write!(f, "{}", message),
+            Self::EmptyCrop => write!(f, "nothing left after cropping"),
+        }
+    }
+}
+
+impl From<io::Error> for EditError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<DecodeError> for EditError {
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+fn save_edit(
+    path: &Path,
+    edit: Edit,
+    adjustments: Adjustments,
+    target: SaveTarget,
+) -> Result<SavedEdit, EditError> {
+    let data = fs::read(path)?;
+    let format = ImageFormat::from_signature(&data)
+        .ok_or(DecodeError::UnsupportedFormat)?;
+
+    // A JPEG that is only turned keeps its pixels, and only gets a new
+    // orientation tag, so it loses nothing to being encoded again.
+    let turned_jpeg = if format == ImageFormat::Jpeg
+        && edit.crop.is_none()
+        && adjustments.is_identity()
+    {
+        let orientation = Header::read(&mut Cursor::new(&data))?
+            .orientation()
+            .then(edit.orientation);
+        jpeg_with_orientation(&data, orientation)
+    } else {
+        None
+    };
+    let is_tag_only = turned_jpeg.is_some();
+    let bytes = match turned_jpeg {
+        Some(bytes) => bytes,
+        None => {
+            let mut image = edit.apply(decode::decode(&data)?);
+            if image.pixels.is_empty() {
+                return Err(EditError::EmptyCrop);
+            }
+            adjustments.apply(&mut image);
+            encode(format, &image, &data)?
+        }
+    };
+
+    let to = match target {
+        SaveTarget::Original => path.to_path_buf(),
+        SaveTarget::NewFile => new_file_path(path),
+    };
+    write_replacing(&to, &bytes)?;
+    // The image is adjusted now, so its recipe would adjust it again.
+    if target == SaveTarget::Original {
+        Adjustments::default().save(path)?;
+    }
+    Ok(SavedEdit {
+        path: to,
+        is_tag_only,
+    })
+}
+
+/// Sets the orientation tag of a JPEG, adding it to its EXIF segment, or
+/// adding an EXIF segment with just that tag if there is none. The pixels
+/// are left as they are, so viewers that ignore the tag show the image as it
+/// was. The orientation of its XMP is removed, so that viewers going by that
+/// one don't turn it differently. Returns `None` if the EXIF segment is
+/// malformed, or would grow too large.
+fn jpeg_with_orientation(
+    data: &[u8],
+    orientation: Orientation,
+) -> Option<Vec<u8>> {
+    let value = orientation.to_exif();
+    let segments = jpeg_segments(data);
+    // The segments to replace, by offset and length.
+    let mut splices = Vec::new();
+    let mut has_exif = false;
+    for segment in &segments {
+        if segment.marker != 0xe1 {
+            continue;
+        }
+        // APP1 holds either EXIF or XMP. Only the first EXIF segment counts.
+        let payload = match segment.payload.strip_prefix(EXIF_ID) {
+            Some(tiff) if !has_exif => {
+                has_exif = true;
+                [EXIF_ID, &exif::with_orientation(tiff, value)?].concat()
+            }
+            Some(_) => continue,
+            None => without_xmp_orientation(segment.payload),
+        };
+        if payload != segment.payload {
+            let segment_bytes = jpeg_segment(0xe1, &payload)?;
+            splices.push((segment.offset, segment.len(), segment_bytes));
+        }
+    }
+    // A new EXIF segment goes right after the start of image marker, or
+    // after the JFIF segment, which has to come first.
+    if !has_exif {
+        let offset = segments
+            .iter()
+            .find(|segment| segment.marker == 0xe0)
+            .map_or(2, |segment| segment.offset + segment.len());
+        let payload = [EXIF_ID, &exif::orientation_tiff(value)].concat();
+        splices.push((offset, 0, jpeg_segment(0xe1, &payload)?));
+    }
+
+    // Splicing from the end keeps the offsets of earlier segments valid. A
+    // new segment is inserted at the offset of the segment it goes before,
+    // so that one is replaced first.
+    splices.sort_by_key(|&(offset, len, _)| (offset, len));
+    let mut data = data.to_vec();
+    for (offset, len, segment) in splices.into_iter().rev() {
+        data.splice(offset..offset + len, segment);
+    }
+    Some(data)
+}
+
+/// A segment of a JPEG, found before its image data.
+struct JpegSegment<'a> {
+    marker: u8,
+    /// Where the marker starts.
+    offset: usize,
+    payload: &'a [u8],
+}
+
+impl JpegSegment<'_> {
+    /// The length of the whole segment, marker included.
+    fn len(&self) -> usize {
+        4 + self.payload.len()
+    }
+}
+
+/// The segments of a JPEG up to the start of its image data.
+fn jpeg_segments(data: &[u8]) -> Vec<JpegSegment<'_>> {
+    let mut segments = Vec::new();
+    let mut offset = 2;
+    while let Some(&[0xff, marker]) = data.get(offset..offset + 2) {
+        match marker {
+            // Fill bytes may precede a marker.
+            0xff => {
+                offset += 1;
+                continue;
+            }
+            // Markers without a payload.
+            0x01 | 0xd0..=0xd8 => {
+                offset += 2;
+                continue;
+            }
+            0xd9 | 0xda => break,
+            _ => {}
+        }
+        let Some(&[len_hi, len_lo]) = data.get(offset + 2..offset + 4) else {
+            break;
+        };
+        let len = u16::from_be_bytes([len_hi, len_lo]) as usize;
+        let Some(payload) = data.get(offset + 4..offset + 2 + len) else {
+            break;
+        };
+        segments.push(JpegSegment {
+            marker,
+            offset,
+            payload,
+        });
+        offset += 2 + len;
+    }
+    segments
+}
+
+/// A JPEG segment with the given marker and payload, or `None` if the
+/// payload is too large for one.
+fn jpeg_segment(marker: u8, payload: &[u8]) -> Option<Vec<u8>> {
+    let len = u16::try_from(2 + payload.len()).ok()?;
+    let mut segment = vec![0xff, marker];
+    segment.extend_from_slice(&len.to_be_bytes());
+    segment.extend_from_slice(payload);
+    Some(segment)
+}
+
+/// The chunks of a PNG, other than its image data and end.
+fn png_chunks(data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut chunks = Vec::new();
+    let mut offset = 8;
+    // Each chunk is its length and type, followed by its data and a CRC.
+    while let Some(&[a, b, c, d, ref chunk_type @ ..]) =
+        data.get(offset..offset + 8)
+    {
+        let len = u32::from_be_bytes([a, b, c, d]) as usize;
+        let chunk_type: [u8; 4] = chunk_type.try_into().unwrap();
+        let Some(chunk_data) = data.get(offset + 8..offset + 8 + len) else {
+            break;
+        };
+        if !matches!(&chunk_type, b"IDAT" | b"IEND") {
+            chunks.push((chunk_type, chunk_data));
+        }
+        offset += 12 + len;
+    }
+    chunks
+}
+
+/// The metadata of a JPEG to carry over once it is encoded again, as the
+/// payloads of its segments by marker: EXIF, XMP, IPTC and the color
+/// profile. As the pixels are encoded upright, the orientation is reset.
+fn jpeg_metadata(original: &[u8], image: &DecodedImage) -> Vec<(u8, Vec<u8>)> {
+    jpeg_segments(original)
+        .into_iter()
+        .filter_map(|segment| {
+            let payload = segment.payload;
+            let payload = match segment.marker {
+                // APP1 holds either EXIF or XMP.
+                0xe1 => match payload.strip_prefix(EXIF_ID) {
+                    Some(tiff) => {
+                        [EXIF_ID, &exif_to_keep(tiff, image)?].concat()
+                    }
+                    None => without_xmp_orientation(payload),
+                },
+                // APP2 holds the color profile, among other things.
+                0xe2 if payload.starts_with(b"ICC_PROFILE\0") => {
+                    payload.to_vec()
+                }
+                // APP13 holds IPTC.
+                0xed => payload.to_vec(),
+                _ => return None,
+            };
+            Some((segment.marker, payload))
+        })
+        .collect()
+}
+
+/// The metadata of a PNG to carry over once it is encoded again, as its
+/// chunks by type, like `jpeg_metadata`.
+fn png_metadata(
+    original: &[u8],
+    image: &DecodedImage,
+) -> Vec<([u8; 4], Vec<u8>)> {
+    png_chunks(original)
+        .into_iter()
+        .filter_map(|(chunk_type, data)| {
+            let data = match &chunk_type {
+                b"eXIf" => exif_to_keep(data, image)?,
+                b"iTXt" => without_xmp_orientation(data),
+                b"tEXt" | b"zTXt" | b"iCCP" | b"sRGB" | b"gAMA" | b"cHRM" => {
+                    data.to_vec()
+                }
+                _ => return None,
+            };
+            Some((chunk_type, data))
+        })
+        .collect()
+}
+
+/// An EXIF block for the edited image, which is left out if it can't be
+/// read, as its orientation would then be unknown.
+fn exif_to_keep(tiff: &[u8], image: &DecodedImage) -> Option<Vec<u8>> {
+    exif::for_encoded_image(tiff, image.width as u32, image.height as u32)
+}
+
+/// Removes the orientation from XMP, which some tools go by when there is
+/// no EXIF orientation. Anything else is left as it is.
+fn without_xmp_orientation(bytes: &[u8]) -> Vec<u8> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if text.contains("tiff:Orientation") => {
+            xmp::remove_property(text, "tiff:Orientation").into_bytes()
+        }
+        _ => bytes.to_vec(),
+    }
+}
+
+/// Encodes an image in `format`, with the metadata of the `original` file
+/// it was decoded from.
+fn encode(
+    format: ImageFormat,
+    image: &DecodedImage,
+    original: &[u8],
+) -> Result<Vec<u8>, EditError> {
+    let mut rgba = Vec::with_capacity(image.pixels.len() * 4);
+    for pixel in &image.pixels {
+        let [b, g, r, a] = pixel.to_le_bytes();
+        rgba.extend_from_slice(&[r, g, b, a]);
+    }
+
+    let mut bytes = Vec::new();
+    match format {
+        ImageFormat::Png => {
+            let mut encoder = png::Encoder::new(
+                &mut bytes,
+                image.width as u32,
+                image.height as u32,
+            );
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder
+                .write_header()
+                .map_err(|error| EditError::Encode(error.to_string()))?;
+            for (chunk_type, data) in png_metadata(original, image) {
+                writer
+                    .write_chunk(png::chunk::ChunkType(chunk_type), &data)
+                    .map_err(|error| EditError::Encode(error.to_string()))?;
+            }
+            writer
+                .write_image_data(&rgba)
+                .map_err(|error| EditError::Encode(error.to_string()))?;
+            writer
+                .finish()
+                .map_err(|error| EditError::Encode(error.to_string()))?;
+        }
+        ImageFormat::Jpeg => {
+            let (Ok(width), Ok(height)) =
+                (u16::try_from(image.width), u16::try_from(image.height))
+            else {
+                return Err(EditError::Encode(
+                    "too large to save as a JPEG".into(),
+                ));
+            };
+            let mut encoder =
+                jpeg_encoder::Encoder::new(&mut bytes, JPEG_QUALITY);
+            for (marker, payload) in jpeg_metadata(original, image) {
+                encoder
+                    .add_app_segment(marker - 0xe0, &payload)
+                    .map_err(|error| EditError::Encode(error.to_string()))?;
+            }
+            encoder
+                .encode(&rgba, width, height, jpeg_encoder::ColorType::Rgba)
+                .map_err(|error| EditError::Encode(error.to_string()))?;
+        }
+    }
+    Ok(bytes)
+}
+
+/// A path next to `path` that is not taken yet, like `IMG_1234_edited.jpg`.
+fn new_file_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|number| {
+            let suffix = match number {
+                1 => "_edited".to_string(),
+                number => format!("_edited_{}", number),
+            };
+            path.with_file_name(format!("{}{}{}", stem, suffix, extension))
+        })
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+/// Writes `bytes` to `path` through a temporary file next to it, so a failed
+/// write never leaves a half written image behind.
+fn write_replacing(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{}.tmp", file_name));
+    fs::write(&temp_path, bytes)?;
+    fs::rename(&temp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xmp::Xmp;
+
+    /// The smallest JPEG the header reader accepts: a start of image marker,
+    /// the given segments and a frame header for an image 3 by 2 pixels.
+    fn jpeg(segments: &[&[u8]]) -> Vec<u8> {
+        let mut data = vec![0xff, 0xd8];
+        for segment in segments {
+            data.extend_from_slice(segment);
+        }
+        data.extend_from_slice(&[
+            0xff, 0xc0, 0x00, 0x0b, 0x08, 0x00, 0x02, 0x00, 0x03, 0x01, 0x01,
+            0x11, 0x00,
+        ]);
+        data.extend_from_slice(&[0xff, 0xd9]);
+        data
+    }
+
+    fn app1(tiff: &[u8]) -> Vec<u8> {
+        let mut segment = vec![0xff, 0xe1];
+        segment.extend_from_slice(&(2 + 6 + tiff.len() as u16).to_be_bytes());
+        segment.extend_from_slice(b"Exif\0\0");
+        segment.extend_from_slice(tiff);
+        segment
+    }
+
+    fn orientation(data: &[u8]) -> Orientation {
+        Header::read(&mut Cursor::new(data)).unwrap().orientation()
+    }
+
+    #[test]
+    fn sets_jpeg_orientation_in_place() {
+        let data = jpeg(&[&app1(&exif::orientation_tiff(1))]);
+        let turned =
+            jpeg_with_orientation(&data, Orientation::Rotate90).unwrap();
+        assert_eq!(turned.len(), data.len());
+        assert_eq!(orientation(&turned), Orientation::Rotate90);
+    }
+
+    #[test]
+    fn adds_jpeg_orientation_after_jfif_segment() {
+        let jfif =
+            b"\xff\xe0\x00\x10JFIF\0\x01\x01\x00\x00\x01\x00\x01\x00\x00";
+        let data = jpeg(&[jfif]);
+        let turned =
+            jpeg_with_orientation(&data, Orientation::Rotate270).unwrap();
+        assert!(turned[2..].starts_with(jfif));
+        assert_eq!(orientation(&turned), Orientation::Rotate270);
+        let size = Header::read(&mut Cursor::new(&turned)).unwrap().size;
+        assert_eq!(size.map(|size| (size.width, size.height)), Some((2, 3)));
+    }
+
+    #[test]
+    fn removes_xmp_orientation_when_setting_jpeg_orientation() {
+        let packet = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:tiff="http://ns.adobe.com/tiff/1.0/"
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    tiff:Orientation="6"
+    xmp:Rating="3"/>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+        let payload =
+            [&b"http://ns.adobe.com/xap/1.0/\0"[..], packet.as_bytes()]
+                .concat();
+        let xmp_segment = jpeg_segment(0xe1, &payload).unwrap();
+        let data = jpeg(&[&xmp_segment]);
+        let turned =
+            jpeg_with_orientation(&data, Orientation::Rotate180).unwrap();
+        assert_eq!(orientation(&turned), Orientation::Rotate180);
+        let xmp_segment = jpeg_segments(&turned)
+            .into_iter()
+            .find(|segment| !segment.payload.starts_with(EXIF_ID))
+            .unwrap();
+        let packet = std::str::from_utf8(xmp_segment.payload).unwrap();
+        assert!(!packet.contains("tiff:Orientation"));
+        assert_eq!(
+            Header::read(&mut Cursor::new(&turned))
+                .unwrap()
+                .xmp
+                .unwrap(),
+            Xmp {
+                rating: Some(3),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn adds_orientation_to_exif_without_one() {
+        let data = include_bytes!("../fixtures/metadata/full.jpg");
+        let turned =
+            jpeg_with_orientation(data, Orientation::Rotate90).unwrap();
+        let header = Header::read(&mut Cursor::new(&turned)).unwrap();
+        assert_eq!(header.orientation(), Orientation::Rotate90);
+        let original = Header::read(&mut Cursor::new(data)).unwrap();
+        assert_eq!(header.xmp, original.xmp);
+        let (exif, original_exif) =
+            (header.exif.unwrap(), original.exif.unwrap());
+        assert_eq!(exif.camera(), original_exif.camera());
+        assert_eq!(
+            exif.date_time_original(),
+            original_exif.date_time_original()
+        );
+        assert_eq!(exif.gps_position(), original_exif.gps_position());
+    }
+
+    #[test]
+    fn keeps_metadata_when_encoding_again() {
+        let image = DecodedImage {
+            width: 2,
+            height: 1,
+            pixels: vec![0xff00_0000; 2],
+        };
+        for (format, original) in [
+            (
+                ImageFormat::Jpeg,
+                &include_bytes!("../fixtures/metadata/full.jpg")[..],
+            ),
+            (
+                ImageFormat::Png,
+                &include_bytes!("../fixtures/metadata/exif_xmp.png")[..],
+            ),
+        ] {
+            let bytes = encode(format, &image, original).unwrap();
+            let header = Header::read(&mut Cursor::new(&bytes)).unwrap();
+            let original = Header::read(&mut Cursor::new(original)).unwrap();
+            assert!(header.exif.is_some() && header.xmp.is_some());
+            assert_eq!(header.xmp, original.xmp);
+            assert_eq!(header.iptc, original.iptc);
+            let (exif, original_exif) =
+                (header.exif.unwrap(), original.exif.unwrap());
+            assert_eq!(exif.camera(), original_exif.camera());
+            assert_eq!(
+                exif.date_time_original(),
+                original_exif.date_time_original()
+            );
+            assert_eq!(exif.gps_position(), original_exif.gps_position());
+        }
+    }
+
+    #[test]
+    fn resets_orientation_when_encoding_again() {
+        let image = DecodedImage {
+            width: 2,
+            height: 3,
+            pixels: vec![0xff00_0000; 6],
+        };
+        let original = jpeg(&[&app1(&exif::orientation_tiff(6))]);
+        assert_eq!(orientation(&original), Orientation::Rotate90);
+        let bytes = encode(ImageFormat::Jpeg, &image, &original).unwrap();
+        assert_eq!(orientation(&bytes), Orientation::Normal);
+    }
+
+    #[test]
+    fn holds_crop_to_aspect_ratio_within_image() {
+        let image_size = dvec2(400.0, 300.0);
+        let ratio = AspectRatio::FourThree.ratio(image_size);
+        let crop = crop_between(
+            dvec2(100.0, 100.0),
+            dvec2(0.0, 500.0),
+            ratio,
+            image_size,
+        );
+        assert_eq!(crop.pos, dvec2(0.0, 100.0));
+        assert_eq!(crop.size, dvec2(100.0, 75.0));
+        assert_eq!(
+            AspectRatio::FourThree.ratio(dvec2(300.0, 400.0)),
+            Some(3.0 / 4.0)
+        );
+    }
+
+    #[test]
+    fn turns_then_crops() {
+        let image = DecodedImage {
+            width: 3,
+            height: 2,
+            pixels: vec![1, 2, 3, 4, 5, 6],
+        };
+        let edit = Edit {
+            orientation: Orientation::Rotate90,
+            crop: Some(Rect {
+                pos: dvec2(0.0, 1.0),
+                size: dvec2(2.0, 2.0),
+            }),
+        };
+        let image = edit.apply(image);
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(image.pixels, vec![5, 2, 6, 3]);
+    }
+}
@@ -0,0 +1,487 @@
+use std::collections::HashMap;
+
+pub const TAG_MAKE: u16 = 0x010f;
+pub const TAG_MODEL: u16 = 0x0110;
+pub const TAG_ORIENTATION: u16 = 0x0112;
+pub const TAG_EXPOSURE_TIME: u16 = 0x829a;
+pub const TAG_F_NUMBER: u16 = 0x829d;
+pub const TAG_ISO: u16 = 0x8827;
+pub const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+pub const TAG_FOCAL_LENGTH: u16 = 0x920a;
+pub const TAG_LENS_MAKE: u16 = 0xa433;
+pub const TAG_LENS_MODEL: u16 = 0xa434;
+pub const TAG_PIXEL_X_DIMENSION: u16 = 0xa002;
+pub const TAG_PIXEL_Y_DIMENSION: u16 = 0xa003;
+pub const TAG_GPS_LATITUDE_REF: u16 = 0x0001;
+pub const TAG_GPS_LATITUDE: u16 = 0x0002;
+pub const TAG_GPS_LONGITUDE_REF: u16 = 0x0003;
+pub const TAG_GPS_LONGITUDE: u16 = 0x0004;
+
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const MAX_ENTRIES: usize = 1024;
+
+/// The image file directories an EXIF block is made of. Tag numbers are only
+/// unique within a directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Ifd {
+    /// The main directory, describing the image itself.
+    Image,
+    /// Camera settings and capture details.
+    Exif,
+    Gps,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Byte(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    /// Numerators and denominators.
+    Rational(Vec<(u32, u32)>),
+    SignedRational(Vec<(i32, i32)>),
+    /// A value in a format that is not decoded.
+    Other,
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Ascii(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            Self::Byte(values) => values.first().map(|&value| value.into()),
+            Self::Short(values) => values.first().map(|&value| value.into()),
+            Self::Long(values) => values.first().copied(),
+            _ => None,
+        }
+    }
+
+    /// The rationals of a value, as floats. Values with a zero denominator
+    /// are left out, since they are more often corrupt than infinite.
+    pub fn as_f64s(&self) -> Vec<f64> {
+        match self {
+            Self::Rational(values) => values
+                .iter()
+                .filter(|(_, denominator)| *denominator != 0)
+                .map(|&(numerator, denominator)| {
+                    numerator as f64 / denominator as f64
+                })
+                .collect(),
+            Self::SignedRational(values) => values
+                .iter()
+                .filter(|(_, denominator)| *denominator != 0)
+                .map(|&(numerator, denominator)| {
+                    numerator as f64 / denominator as f64
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_f64s().first().copied()
+    }
+
+    /// The first rational of a value, as a numerator and denominator.
+    pub fn as_rational(&self) -> Option<(u32, u32)> {
+        match self {
+            Self::Rational(values) => values.first().copied(),
+            _ => None,
+        }
+    }
+}
+
+/// The tags of an EXIF block. Only the tags of the image, EXIF and GPS
+/// directories are read; thumbnails and maker notes are skipped.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Exif {
+    entries: HashMap<(Ifd, u16), Value>,
+}
+
+impl Exif {
+    /// Parses the payload of a JPEG APP1 segment, which starts with the
+    /// `Exif\0\0` identifier.
+    pub fn from_app1(bytes: &[u8]) -> Option<Self> {
+        Self::from_tiff(bytes.strip_prefix(b"Exif\0\0")?)
+    }
+
+    /// Parses an EXIF block in TIFF format. Malformed entries are skipped
+    /// rather than failing the whole block, as long as the header is valid.
+    pub fn from_tiff(tiff: &[u8]) -> Option<Self> {
+        let reader = match tiff.get(..4)? {
+            b"II*\0" => Reader {
+                bytes: tiff,
+                little_endian: true,
+            },
+            b"MM\0*" => Reader {
+                bytes: tiff,
+                little_endian: false,
+            },
+            _ => return None,
+        };
+
+        let mut exif = Self::default();
+        let image_ifd = reader.u32(4)? as usize;
+        exif.read_ifd(&reader, Ifd::Image, image_ifd);
+        for (tag, ifd) in [(TAG_EXIF_IFD, Ifd::Exif), (TAG_GPS_IFD, Ifd::Gps)] {
+            if let Some(offset) =
+                exif.get(Ifd::Image, tag).and_then(Value::as_u32)
+            {
+                exif.read_ifd(&reader, ifd, offset as usize);
+            }
+        }
+        Some(exif)
+    }
+
+    pub fn get(&self, ifd: Ifd, tag: u16) -> Option<&Value> {
+        self.entries.get(&(ifd, tag))
+    }
+
+    /// When the photo was taken, as `YYYY:MM:DD HH:MM:SS`.
+    pub fn date_time_original(&self) -> Option<&str> {
+        self.get(Ifd::Exif, TAG_DATE_TIME_ORIGINAL)?
+            .as_str()
+            .filter(|date| date.len() >= 10 && !date.starts_with("0000"))
+    }
+
+    /// The camera make and model, with the make left out if the model
+    /// already includes it, as in `Canon` and `Canon EOS R5`.
+    pub fn camera(&self) -> Option<String> {
+        let make = self.get(Ifd::Image, TAG_MAKE).and_then(Value::as_str);
+        let model = self.get(Ifd::Image, TAG_MODEL).and_then(Value::as_str);
+        combine_make_and_model(make, model)
+    }
+
+    /// The lens make and model, combined like `camera`.
+    pub fn lens(&self) -> Option<String> {
+        let make = self.get(Ifd::Exif, TAG_LENS_MAKE).and_then(Value::as_str);
+        let model = self.get(Ifd::Exif, TAG_LENS_MODEL).and_then(Value::as_str);
+        combine_make_and_model(make, model)
+    }
+
+    /// The GPS position as latitude and longitude in degrees, negative to
+    /// the south and west.
+    pub fn gps_position(&self) -> Option<(f64, f64)> {
+        let coordinate = |tag, ref_tag, negative_ref| {
+            let [degrees, minutes, seconds] =
+                self.get(Ifd::Gps, tag)?.as_f64s().try_into().ok()?;
+            let value = degrees + minutes / 60.0 + seconds / 3600.0;
+            let is_negative = self
+                .get(Ifd::Gps, ref_tag)
+                .and_then(Value::as_str)
+                .is_some_and(|r| r.eq_ignore_ascii_case(negative_ref));
+            Some(if is_negative { -value } else { value })
+        };
+        Some((
+            coordinate(TAG_GPS_LATITUDE, TAG_GPS_LATITUDE_REF, "S")?,
+            coordinate(TAG_GPS_LONGITUDE, TAG_GPS_LONGITUDE_REF, "W")?,
+        ))
+    }
+
+    fn read_ifd(&mut self, reader: &Reader, ifd: Ifd, offset: usize) {
+        let Some(num_entries) = reader.u16(offset) else {
+            return;
+        };
+        for entry_idx in 0..(num_entries as usize).min(MAX_ENTRIES) {
+            let entry_offset = offset + 2 + entry_idx * 12;
+            if let Some((tag, value)) = reader.entry(entry_offset) {
+                self.entries.insert((ifd, tag), value);
+            }
+        }
+    }
+}
+
+/// Sets the orientation tag of an EXIF block in TIFF format, adding it if
+/// the block has none. Returns `None` if the block is malformed.
+pub fn with_orientation(tiff: &[u8], value: u16) -> Option<Vec<u8>> {
+    let mut editor = Editor::new(tiff)?;
+    let image_ifd = editor.image_ifd()?;
+    if !editor.set_value(image_ifd, TAG_ORIENTATION, value.into()) {
+        editor.add_short(image_ifd, TAG_ORIENTATION, value)?;
+    }
+    Some(editor.bytes)
+}
+
+/// Prepares an EXIF block in TIFF format for an image that was encoded
+/// again, upright and at the given size. The orientation is reset, the
+/// pixel dimensions are updated, and the thumbnail is dropped, as it shows
+/// the image as it was. Returns `None` if the block is malformed.
+pub fn for_encoded_image(
+    tiff: &[u8],
+    width: u32,
+    height: u32,
+) -> Option<Vec<u8>> {
+    let mut editor = Editor::new(tiff)?;
+    let image_ifd = editor.image_ifd()?;
+    editor.set_value(image_ifd, TAG_ORIENTATION, 1);
+    if let Some(exif_ifd) = editor.value(image_ifd, TAG_EXIF_IFD) {
+        let exif_ifd = exif_ifd as usize;
+        editor.set_value(exif_ifd, TAG_PIXEL_X_DIMENSION, width);
+        editor.set_value(exif_ifd, TAG_PIXEL_Y_DIMENSION, height);
+    }
+    // The thumbnail is described by the directory after the image one.
+    let next_ifd_offset = editor.next_ifd_offset(image_ifd)?;
+    editor.write_u32(next_ifd_offset, 0);
+    Some(editor.bytes)
+}
+
+/// An EXIF block in TIFF format holding nothing but the orientation tag.
+pub fn orientation_tiff(value: u16) -> Vec<u8> {
+    let mut tiff = b"MM\0*".to_vec();
+    // The offset of the image directory, which comes right after.
+    tiff.extend_from_slice(&8u32.to_be_bytes());
+    tiff.extend_from_slice(&1u16.to_be_bytes());
+    tiff.extend_from_slice(&TAG_ORIENTATION.to_be_bytes());
+    // A single short, stored in the entry itself.
+    tiff.extend_from_slice(&3u16.to_be_bytes());
+    tiff.extend_from_slice(&1u32.to_be_bytes());
+    tiff.extend_from_slice(&value.to_be_bytes());
+    tiff.extend_from_slice(&[0, 0]);
+    // No next directory.
+    tiff.extend_from_slice(&0u32.to_be_bytes());
+    tiff
+}
+
+fn combine_make_and_model(
+    make: Option<&str>,
+    model: Option<&str>,
+) -> Option<String> {
+    match (make, model) {
+        (Some(make), Some(model)) if !model.starts_with(make) => {
+            Some(format!("{} {}", make, model))
+        }
+        (_, Some(model)) => Some(model.to_string()),
+        (Some(make), None) => Some(make.to_string()),
+        (None, None) => None,
+    }
+}
+
+/// A copy of an EXIF block in TIFF format being edited. Values are only
+/// changed where they are stored in their entries, and directories only
+/// grow at the end of the block, so no offsets into the block have to be
+/// updated.
+struct Editor {
+    bytes: Vec<u8>,
+    little_endian: bool,
+}
+
+impl Editor {
+    fn new(tiff: &[u8]) -> Option<Self> {
+        let little_endian = match tiff.get(..4)? {
+            b"II*\0" => true,
+            b"MM\0*" => false,
+            _ => return None,
+        };
+        Some(Self {
+            bytes: tiff.to_vec(),
+            little_endian,
+        })
+    }
+
+    fn reader(&self) -> Reader<'_> {
+        Reader {
+            bytes: &self.bytes,
+            little_endian: self.little_endian,
+        }
+    }
+
+    fn image_ifd(&self) -> Option<usize> {
+        self.reader().u32(4).map(|offset| offset as usize)
+    }
+
+    fn num_entries(&self, ifd: usize) -> Option<usize> {
+        let num_entries = self.reader().u16(ifd)? as usize;
+        Some(num_entries.min(MAX_ENTRIES))
+    }
+
+    fn next_ifd_offset(&self, ifd: usize) -> Option<usize> {
+        let offset = ifd + 2 + self.num_entries(ifd)? * 12;
+        self.reader().u32(offset)?;
+        Some(offset)
+    }
+
+    fn find_entry(&self, ifd: usize, tag: u16) -> Option<usize> {
+        (0..self.num_entries(ifd)?)
+            .map(|entry_idx| ifd + 2 + entry_idx * 12)
+            .find(|&entry_offset| self.reader().u16(entry_offset) == Some(tag))
+    }
+
+    fn value(&self, ifd: usize, tag: u16) -> Option<u32> {
+        let entry_offset = self.find_entry(ifd, tag)?;
+        self.reader().entry(entry_offset)?.1.as_u32()
+    }
+
+    /// Sets a tag holding a single short or long. Returns `false`, leaving
+    /// the block as it was, if the directory has no such tag.
+    fn set_value(&mut self, ifd: usize, tag: u16, value: u32) -> bool {
+        let Some(entry_offset) = self.find_entry(ifd, tag) else {
+            return false;
+        };
+        let reader = self.reader();
+        if reader.u32(entry_offset + 8).is_none() {
+            return false;
+        }
+        match (reader.u16(entry_offset + 2), reader.u32(entry_offset + 4)) {
+            (Some(3), Some(1)) => match u16::try_from(value) {
+                Ok(value) => {
+                    self.write_u16(entry_offset + 8, value);
+                    true
+                }
+                Err(_) => false,
+            },
+            (Some(4), Some(1)) => {
+                self.write_u32(entry_offset + 8, value);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Adds a tag holding a single short to a directory. The directory is
+    /// moved to the end of the block to make room for it.
+    fn add_short(&mut self, ifd: usize, tag: u16, value: u16) -> Option<()> {
+        let next_ifd_offset = self.next_ifd_offset(ifd)?;
+        let next_ifd = self.reader().u32(next_ifd_offset)?;
+        let mut entries: Vec<Vec<u8>> = self.bytes[ifd + 2..next_ifd_offset]
+            .chunks(12)
+            .map(<[u8]>::to_vec)
+            .collect();
+        let mut entry = Vec::with_capacity(12);
+        entry.extend_from_slice(&self.u16_bytes(tag));
+        entry.extend_from_slice(&self.u16_bytes(3));
+        entry.extend_from_slice(&self.u32_bytes(1));
+        entry.extend_from_slice(&self.u16_bytes(value));
+        entry.extend_from_slice(&[0, 0]);
+        entries.push(entry);
+        // Entries are sorted by tag.
+        entries.sort_by_key(|entry| {
+            Reader {
+                bytes: entry,
+                little_endian: self.little_endian,
+            }
+            .u16(0)
+        });
+
+        // Directories start on a word boundary.
+        self.bytes.resize(self.bytes.len().next_multiple_of(2), 0);
+        let new_ifd = u32::try_from(self.bytes.len()).ok()?;
+        let num_entries = u16::try_from(entries.len()).ok()?;
+        self.bytes.extend_from_slice(&self.u16_bytes(num_entries));
+        for entry in entries {
+            self.bytes.extend_from_slice(&entry);
+        }
+        self.bytes.extend_from_slice(&self.u32_bytes(next_ifd));
+        self.write_u32(4, new_ifd);
+        Some(())
+    }
+
+    fn u16_bytes(&self, value: u16) -> [u8; 2] {
+        if self.little_endian {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        }
+    }
+
+    fn u32_bytes(&self, value: u32) -> [u8; 4] {
+        if self.little_endian {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        }
+    }
+
+    fn write_u16(&mut self, offset: usize, value: u16) {
+        let bytes = self.u16_bytes(value);
+        self.bytes[offset..offset + 2].copy_from_slice(&bytes);
+    }
+
+    fn write_u32(&mut self, offset: usize, value: u32) {
+        let bytes = self.u32_bytes(value);
+        self.bytes[offset..offset + 4].copy_from_slice(&bytes);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+}
+
+impl Reader<'_> {
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.bytes.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.bytes.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn entry(&self, offset: usize) -> Option<(u16, Value)> {
+        let tag = self.u16(offset)?;
+        let format = self.u16(offset + 2)?;
+        let count = self.u32(offset + 4)? as usize;
+        let component_size = match format {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 => 4,
+            5 | 10 | 12 => 8,
+            _ => return None,
+        };
+
+        // Values of up to four bytes are stored in the entry itself, and
+        // larger ones elsewhere, at the offset stored in the entry.
+        let len = count.checked_mul(component_size)?;
+        let value_offset = if len <= 4 {
+            offset + 8
+        } else {
+            self.u32(offset + 8)? as usize
+        };
+        let bytes = self
+            .bytes
+            .get(value_offset..value_offset.checked_add(len)?)?;
+        let offsets = (0..count).map(|idx| value_offset + idx * component_size);
+
+        let value = match format {
+            1 | 6 => Value::Byte(bytes.to_vec()),
+            2 => {
+                let end = bytes.iter().position(|&byte| byte == 0);
+                let bytes = &bytes[..end.unwrap_or(bytes.len())];
+                Value::Ascii(String::from_utf8_lossy(bytes).trim().to_string())
+            }
+            3 | 8 => {
+                Value::Short(offsets.filter_map(|o| self.u16(o)).collect())
+            }
+            4 | 9 => Value::Long(offsets.filter_map(|o| self.u32(o)).collect()),
+            5 => Value::Rational(
+                offsets
+                    .filter_map(|o| Some((self.u32(o)?, self.u32(o + 4)?)))
+                    .collect(),
+            ),
+            10 => Value::SignedRational(
+                offsets
+                    .filter_map(|o| {
+                        Some((self.u32(o)? as i32, self.u32(o + 4)? as i32))
+                    })
+                    .collect(),
+            ),
+            _ => Value::Other,
+        };
+        Some((tag, value))
+    }
+}
@@ -0,0 +1,602 @@
+use crate::{config, date, rating};
+use makepad_widgets::*;
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileOp {
+    Trash,
+    Move(PathBuf),
+    Copy(PathBuf),
+    /// Renames the images according to a template. See `render_template`.
+    Rename(String),
+}
+
+impl fmt::Display for FileOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Trash => write!(f, "Moved to trash"),
+            Self::Move(dir) => write!(f, "Moved to {}", dir.display()),
+            Self::Copy(dir) => write!(f, "Copied to {}", dir.display()),
+            Self::Rename(_) => write!(f, "Renamed"),
+        }
+    }
+}
+
+/// A single change made to the file system, with enough information to
+/// revert it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    Moved {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Copied {
+        to: PathBuf,
+    },
+    Trashed {
+        from: PathBuf,
+        to: PathBuf,
+        info_path: PathBuf,
+        /// Where the XMP sidecar of the image went in the trash, and its info
+        /// file, if it had one.
+        sidecar: Option<(PathBuf, PathBuf)>,
+    },
+}
+
+impl Change {
+    fn effect(&self) -> Effect {
+        match self {
+            Self::Moved { from, to } => Effect::Renamed {
+                from: from.clone(),
+                to: to.clone(),
+            },
+            Self::Copied { to } => Effect::Added(to.clone()),
+            Self::Trashed { from, .. } => Effect::Removed(from.clone()),
+        }
+    }
+}
+
+/// How a change affects the list of images.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Effect {
+    Added(PathBuf),
+    Removed(PathBuf),
+    Renamed { from: PathBuf, to: PathBuf },
+}
+
+/// Posted once a file operation or an undo has finished. Operations stop at
+/// the first error, so `effects` only covers whatever was done before it.
+/// The `changes` of an operation can be passed to `undo`, while those of an
+/// undo are always empty, as undoing is not itself undoable.
+#[derive(Debug)]
+pub struct FileOpAction {
+    pub description: String,
+    pub changes: Vec<Change>,
+    pub effects: Vec<Effect>,
+    pub error: Option<String>,
+}
+
+/// Runs `op` on `paths` on a background thread and posts a `FileOpAction`
+/// once it is done.
+pub fn run(cx: &mut Cx, op: FileOp, paths: Vec<PathBuf>) {
+    cx.spawn_thread(move || {
+        let mut changes = Vec::new();
+        let result = apply(&op, &paths, &mut changes);
+        let count = changes.len();
+        Cx::post_action(FileOpAction {
+            description: format!(
+                "{} {} image{}",
+                op,
+                count,
+                if count == 1 { "" } else { "s" }
+            ),
+            effects: changes.iter().map(Change::effect).collect(),
+            changes,
+            error: result.err().map(|error| error.to_string()),
+        });
+    });
+}
+
+/// Reverts `changes` on a background thread, in reverse order, and posts a
+/// `FileOpAction` once it is done.
+pub fn undo(cx: &mut Cx, changes: Vec<Change>) {
+    cx.spawn_thread(move || {
+        let mut effects = Vec::new();
+        let result = changes.iter().rev().try_for_each(|change| {
+            effects.push(revert(change)?);
+            Ok::<_, FileOpError>(())
+        });
+        Cx::post_action(FileOpAction {
+            description: "Undid the last operation".into(),
+            changes: Vec::new(),
+            effects,
+            error: result.err().map(|error| error.to_string()),
+        });
+    });
+}
+
+fn apply(
+    op: &FileOp,
+    paths: &[PathBuf],
+    changes: &mut Vec<Change>,
+) -> Result<(), FileOpError> {
+    match op {
+        FileOp::Trash => {
+            let trash_dir = config::data_home_dir()
+                .ok_or(FileOpError::NoTrash)?
+                .join("Trash");
+            for path in paths {
+                changes.push(trash(path, &trash_dir)?);
+            }
+        }
+        FileOp::Move(dir) => {
+            for path in paths {
+                let to = destination(path, dir)?;
+                move_file(path, &to)?;
+                move_sidecar(path, &to);
+                changes.push(Change::Moved {
+                    from: path.clone(),
+                    to,
+                });
+            }
+        }
+        FileOp::Copy(dir) => {
+            for path in paths {
+                let to = destination(path, dir)?;
+                fs::copy(path, &to)
+                    .map_err(|error| FileOpError::io(path, error))?;
+                let sidecar_path = rating::sidecar_path(path);
+                let to_sidecar_path = rating::sidecar_path(&to);
+                if sidecar_path.is_file() && !to_sidecar_path.exists() {
+                    let _ = fs::copy(sidecar_path, to_sidecar_path);
+                }
+                changes.push(Change::Copied { to });
+            }
+        }
+        FileOp::Rename(template) => {
+            // All new names are checked up front, so a batch never stops
+            // halfway because two images would end up with the same name.
+            let renames = rename_targets(template, paths)?;
+            for (from, to) in renames {
+                if from == to {
+                    continue;
+                }
+                move_file(&from, &to)?;
+                move_sidecar(&from, &to);
+                changes.push(Change::Moved { from, to });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn revert(change: &Change) -> Result<Effect, FileOpError> {
+    match change {
+        Change::Moved { from, to } => {
+            ensure_free(from)?;
+            move_file(to, from)?;
+            move_sidecar(to, from);
+            Ok(Effect::Renamed {
+                from: to.clone(),
+                to: from.clone(),
+            })
+        }
+        Change::Copied { to } => {
+            fs::remove_file(to).map_err(|error| FileOpError::io(to, error))?;
+            Ok(Effect::Removed(to.clone()))
+        }
+        Change::Trashed {
+            from,
+            to,
+            info_path,
+            sidecar,
+        } => {
+            ensure_free(from)?;
+            move_file(to, from)?;
+            // A stale info file only makes the trash list an entry whose file
+            // is gone, so failing to remove it is not worth reporting.
+            let _ = fs::remove_file(info_path);
+            // Like when moving, a sidecar that can't be restored is left
+            // behind rather than failing the undo.
+            if let Some((sidecar_to, sidecar_info_path)) = sidecar {
+                let sidecar_from = rating::sidecar_path(from);
+                if !sidecar_from.exists()
+                    && move_file(sidecar_to, &sidecar_from).is_ok()
+                {
+                    let _ = fs::remove_file(sidecar_info_path);
+                }
+            }
+            Ok(Effect::Added(from.clone()))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FileOpError {
+    Io { path: PathBuf, error: io::Error },
+    AlreadyExists(PathBuf),
+    Template(String),
+    NoTrash,
+}
+
+impl FileOpError {
+    fn io(path: &Path, error: io::Error) -> Self {
+        Self::Io {
+            path: path.to_path_buf(),
+            error,
+        }
+    }
+}
+
+impl fmt::Display for FileOpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => {
+                write!(f, "{}: {}", path.display(), error)
+            }
+            Self::AlreadyExists(path) => {
+                write!(f, "{} already exists", path.display())
+            }
+            Self::Template(message) => {
+                write!(f, "Invalid template: {}", message)
+            }
+            Self::NoTrash => write!(f, "Could not find the trash directory"),
+        }
+    }
+}
+
+fn ensure_free(path: &Path) -> Result<(), FileOpError> {
+    if path.exists() {
+        return Err(FileOpError::AlreadyExists(path.to_path_buf()));
+    }
+    Ok(())
+}
+
+/// Returns the path `path` would have in `dir`. Existing files are never
+/// overwritten.
+fn destination(path: &Path, dir: &Path) -> Result<PathBuf, FileOpError> {
+    let file_name = path.file_name().ok_or_else(|| {
+        FileOpError::io(path, io::ErrorKind::InvalidInput.into())
+    })?;
+    let to = dir.join(file_name);
+    ensure_free(&to)?;
+    Ok(to)
+}
+
+/// Renames `from` to `to`, falling back to copying and deleting when they
+/// are on different file systems.
+fn move_file(from: &Path, to: &Path) -> Result<(), FileOpError> {
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == io::ErrorKind::CrossesDevices => {
+            fs::copy(from, to).map_err(|error| FileOpError::io(from, error))?;
+            fs::remove_file(from).map_err(|error| FileOpError::io(from, error))
+        }
+        Err(error) => Err(FileOpError::io(from, error)),
+    }
+}
+
+/// Moves the XMP sidecar of an image that was moved along with it, if it has
+/// one, so its rating follows it. The image itself was already moved, so a
+/// sidecar that can't follow is left behind rather than failing the move.
+fn move_sidecar(from: &Path, to: &Path) {
+    let from = rating::sidecar_path(from);
+    let to = rating::sidecar_path(to);
+    if from.is_file() && !to.exists() {
+        let _ = move_file(&from, &to);
+    }
+}
+
+/// Moves `path` to the trash at `trash_dir`, as specified by the
+/// freedesktop.org trash specification, along with its XMP sidecar. Only the
+/// home trash is used, so trashing a file on another file system copies it.
+fn trash(path: &Path, trash_dir: &Path) -> Result<Change, FileOpError> {
+    let (to, info_path) = trash_file(path, trash_dir)?;
+    // The image itself is already in the trash, so a sidecar that can't
+    // follow it is left behind rather than failing the operation.
+    let sidecar_path = rating::sidecar_path(path);
+    let sidecar = if sidecar_path.is_file() {
+        trash_file(&sidecar_path, trash_dir).ok()
+    } else {
+        None
+    };
+    Ok(Change::Trashed {
+        from: path.to_path_buf(),
+        to,
+        info_path,
+        sidecar,
+    })
+}
+
+/// Moves `path` to the `files` directory of `trash_dir` and writes its info
+/// file. Returns where the file and its info file ended up.
+fn trash_file(
+    path: &Path,
+    trash_dir: &Path,
+) -> Result<(PathBuf, PathBuf), FileOpError> {
+    let files_dir = trash_dir.join("files");
+    let info_dir = trash_dir.join("info");
+    for dir in [&files_dir, &info_dir] {
+        fs::create_dir_all(dir).map_err(|error| FileOpError::io(dir, error))?;
+    }
+
+    let absolute_path = path
+        .canonicalize()
+        .map_err(|error| FileOpError::io(path, error))?;
+    let info = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(&absolute_path),
+        date::format_local_date_time(SystemTime::now()),
+    );
+
+    // The info file is created exclusively, which reserves the name in the
+    // trash even if another application is trashing files at the same time.
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    for attempt in 0.. {
+        let name = match attempt {
+            0 => file_name.clone(),
+            _ => format!("{}.{}", file_name, attempt),
+        };
+        let info_path = info_dir.join(format!("{}.trashinfo", name));
+        match fs::File::create_new(&info_path) {
+            Ok(_) => {}
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                continue;
+            }
+            Err(error) => return Err(FileOpError::io(&info_path, error)),
+        }
+        let to = files_dir.join(&name);
+        let result = fs::write(&info_path, &info)
+            .map_err(|error| FileOpError::io(&info_path, error))
+            .and_then(|()| move_file(path, &to));
+        if let Err(error) = result {
+            let _ = fs::remove_file(&info_path);
+            return Err(error);
+        }
+        return Ok((to, info_path));
+    }
+    unreachable!()
+}
+
+fn percent_encode(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &byte in path.as_os_str().as_encoded_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Works out the new name of every image for a rename, in the order of
+/// `paths`, and checks that none of them clash.
+fn rename_targets(
+    template: &str,
+    paths: &[PathBuf],
+) -> Result<Vec<(PathBuf, PathBuf)>, FileOpError> {
+    let index_width = paths.len().to_string().len();
+    let mut renames: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for (index, path) in paths.iter().enumerate() {
+        let modified = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|error| FileOpError::io(path, error))?;
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut file_name = render_template(
+            template,
+            &stem,
+            &date::format_date(modified),
+            index + 1,
+            index_width,
+        )
+        .map_err(FileOpError::Template)?;
+        if let Some(extension) = path.extension() {
+            file_name.push('.');
+            file_name.push_str(&extension.to_string_lossy());
+        }
+        let to = path.with_file_name(file_name);
+
+        let clashes_in_batch = renames.iter().any(|(_, other)| *other == to);
+        let clashes_on_disk = to.exists() && !paths.contains(&to);
+        if clashes_in_batch || clashes_on_disk {
+            return Err(FileOpError::AlreadyExists(to));
+        }
+        renames.push((path.clone(), to));
+    }
+
+    // Renaming onto a name that another image in the batch still has would
+    // overwrite it, so those images must be renamed first.
+    let sources: Vec<&PathBuf> = renames.iter().map(|(from, _)| from).collect();
+    if renames
+        .iter()
+        .any(|(from, to)| from != to && sources.contains(&to))
+    {
+        return Err(FileOpError::Template(
+            "the new names overlap with the current ones".into(),
+        ));
+    }
+    Ok(renames)
+}
+
+/// Expands `{name}`, the original file name without its extension, `{date}`,
+/// the date the file was last modified, and `{index}`, its 1-based position
+/// in the batch, zero-padded to `index_width` digits.
+fn render_template(
+    template: &str,
+    name: &str,
+    date: &str,
+    index: usize,
+    index_width: usize,
+) -> Result<String, String> {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| "unclosed `{`".to_string())?;
+        match &rest[start + 1..start + end] {
+            "name" => rendered.push_str(name),
+            "date" => rendered.push_str(date),
+            "index" => rendered.push_str(&format!(
+                "{:0width$}",
+                index,
+                width = index_width
+            )),
+            token => return Err(format!("unknown field `{{{}}}`", token)),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    rendered.push_str(rest);
+
+    if rendered.is_empty() || rendered.contains(['/', '\\']) {
+        return Err(format!("`{}` is not a valid file name", rendered));
+    }
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "file-ops-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn renders_templates() {
+        let render =
+            |template| render_template(template, "IMG_1", "2025-06-01", 7, 3);
+        assert_eq!(render("{date}_{index}").unwrap(), "2025-06-01_007");
+        assert_eq!(render("trip-{name}").unwrap(), "trip-IMG_1");
+        assert_eq!(render("plain").unwrap(), "plain");
+    }
+
+    #[test]
+    fn rejects_bad_templates() {
+        let render =
+            |template| render_template(template, "IMG_1", "2025-06-01", 1, 1);
+        assert!(render("{name").unwrap_err().contains("unclosed"));
+        assert!(render("{size}").unwrap_err().contains("{size}"));
+        assert!(render("").is_err());
+        assert!(render("a/{name}").is_err());
+        assert!(render("a\\{name}").is_err());
+    }
+
+    #[test]
+    fn percent_encodes_trash_paths() {
+        assert_eq!(
+            percent_encode(Path::new("/photos/my trip/ça_1.jpg")),
+            "/photos/my%20trip/%C3%A7a_1.jpg"
+        );
+    }
+
+    #[test]
+    fn names_a_batch_in_order() {
+        let dir = temp_dir("batch");
+        let paths: Vec<PathBuf> = ["b.jpg", "a.png"]
+            .iter()
+            .map(|name| dir.join(name))
+            .collect();
+        for path in &paths {
+            fs::write(path, b"").unwrap();
+        }
+        let renames = rename_targets("trip-{index}", &paths).unwrap();
+        assert_eq!(
+            renames,
+            [
+                (paths[0].clone(), dir.join("trip-1.jpg")),
+                (paths[1].clone(), dir.join("trip-2.png")),
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_names_that_clash() {
+        let dir = temp_dir("clash");
+        let paths: Vec<PathBuf> = ["a.jpg", "b.jpg"]
+            .iter()
+            .map(|name| dir.join(name))
+            .collect();
+        for path in &paths {
+            fs::write(path, b"").unwrap();
+        }
+
+        // Within the batch.
+        assert!(matches!(
+            rename_targets("same", &paths),
+            Err(FileOpError::AlreadyExists(path))
+                if path == dir.join("same.jpg")
+        ));
+
+        // With a file that isn't being renamed.
+        fs::write(dir.join("other-a.jpg"), b"").unwrap();
+        assert!(matches!(
+            rename_targets("other-{name}", &paths),
+            Err(FileOpError::AlreadyExists(path))
+                if path == dir.join("other-a.jpg")
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_names_that_overlap_with_current_ones() {
+        let dir = temp_dir("overlap");
+        let paths = [dir.join("2.jpg"), dir.join("1.jpg")];
+        for path in &paths {
+            fs::write(path, b"").unwrap();
+        }
+        // Swapping the names would overwrite `1.jpg` before it is renamed.
+        assert!(matches!(
+            rename_targets("{index}", &paths),
+            Err(FileOpError::Template(_))
+        ));
+        // Names that stay the same are fine.
+        let renames = rename_targets("{name}", &paths).unwrap();
+        assert!(renames.iter().all(|(from, to)| from == to));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn restores_the_sidecar_of_a_trashed_image() {
+        let dir = temp_dir("trash");
+        let trash_dir = dir.join("Trash");
+        let path = dir.join("a.jpg");
+        fs::write(&path, b"image").unwrap();
+        fs::write(rating::sidecar_path(&path), b"sidecar").unwrap();
+
+        let change = trash(&path, &trash_dir).unwrap();
+        assert!(!path.exists());
+        assert!(!rating::sidecar_path(&path).exists());
+        let Change::Trashed { sidecar, .. } = &change else {
+            panic!("expected a trashed image, got {:?}", change);
+        };
+        assert!(sidecar.is_some());
+        assert_eq!(fs::read_dir(trash_dir.join("info")).unwrap().count(), 2);
+
+        assert_eq!(revert(&change).unwrap(), Effect::Added(path.clone()));
+        assert_eq!(fs::read(&path).unwrap(), b"image");
+        assert_eq!(fs::read(rating::sidecar_path(&path)).unwrap(), b"sidecar");
+        assert_eq!(fs::read_dir(trash_dir.join("info")).unwrap().count(), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
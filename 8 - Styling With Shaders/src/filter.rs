@@ -0,0 +1,394 @@
+use crate::{
+    image_info::ImageInfo,
+    rating::{ColorLabel, Flag, Rating},
+};
+use std::path::Path;
+
+/// A search query over the images. The query is split on whitespace into
+/// terms that must all match. Terms of the form `key:value` or `key>value`
+/// match metadata, and any other term matches the file name, either as a
+/// substring or, if it contains `*` or `?`, as a glob. Matching is case
+/// insensitive throughout.
+///
+/// Supported metadata terms are `ext:png`, `camera:x100v`, `date:2025-06`,
+/// `label:red`, `flag:pick`, `flag:reject` or `flag:none`, and comparisons
+/// of `width`, `height` or `rating` with `<`, `<=`, `=`, `>=` or `>`, as in
+/// `width>4000` or `rating>=3`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    terms: Vec<Term>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Term {
+    Name(String),
+    Glob(String),
+    Ext(String),
+    Camera(String),
+    /// A prefix of a `YYYY-MM-DD` date.
+    Date(String),
+    Dimension {
+        dimension: Dimension,
+        comparison: Comparison,
+        value: usize,
+    },
+    Label(ColorLabel),
+    /// `None` matches images that are neither picked nor rejected.
+    Flag(Option<Flag>),
+    Stars {
+        comparison: Comparison,
+        value: u8,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Dimension {
+    Width,
+    Height,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Filter {
+    /// Parses a query. Terms that look like metadata terms but are not
+    /// valid ones are matched against the file name instead.
+    pub fn parse(query: &str) -> Self {
+        Self {
+            terms: query
+                .split_whitespace()
+                .map(|term| Term::parse(&term.to_lowercase()))
+                .collect(),
+        }
+    }
+
+    /// Returns whether matching depends on image infos, so the images need
+    /// to be filtered again as those arrive.
+    pub fn needs_infos(&self) -> bool {
+        self.terms.iter().any(|term| {
+            matches!(
+                term,
+                Term::Camera(_) | Term::Date(_) | Term::Dimension { .. }
+            )
+        })
+    }
+
+    /// Returns whether matching depends on ratings, so the images need to be
+    /// filtered again when one changes.
+    pub fn needs_ratings(&self) -> bool {
+        self.terms.iter().any(|term| {
+            matches!(term, Term::Label(_) | Term::Flag(_) | Term::Stars { .. })
+        })
+    }
+
+    /// Returns whether an image matches. Metadata terms never match images
+    /// whose info is not known.
+    pub fn matches(
+        &self,
+        path: &Path,
+        info: Option<&ImageInfo>,
+        rating: Rating,
+    ) -> bool {
+        self.terms
+            .iter()
+            .all(|term| term.matches(path, info, rating))
+    }
+}
+
+impl Term {
+    fn parse(term: &str) -> Self {
+        if let Some((key, value)) = term.split_once(':')
+            && !value.is_empty()
+        {
+            let value = value.to_string();
+            match key {
+                "ext" => {
+                    return Self::Ext(value.trim_start_matches('.').into());
+                }
+                "camera" => return Self::Camera(value),
+                "date" => return Self::Date(value),
+                "label" => {
+                    if let Some(label) = ColorLabel::from_name(&value) {
+                        return Self::Label(label);
+                    }
+                }
+                "flag" if value == "none" => return Self::Flag(None),
+                "flag" => {
+                    if let Some(flag) = Flag::from_name(&value) {
+                        return Self::Flag(Some(flag));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(idx) = term.find(['<', '=', '>'])
+            && let Some((comparison, value)) = Comparison::parse(&term[idx..])
+        {
+            let key = &term[..idx];
+            if let Some(dimension) = Dimension::from_name(key)
+                && let Ok(value) = value.parse()
+            {
+                return Self::Dimension {
+                    dimension,
+                    comparison,
+                    value,
+                };
+            }
+            if key == "rating"
+                && let Ok(value) = value.parse()
+            {
+                return Self::Stars { comparison, value };
+            }
+        }
+
+        if term.contains(['*', '?']) {
+            Self::Glob(term.to_string())
+        } else {
+            Self::Name(term.to_string())
+        }
+    }
+
+    fn matches(
+        &self,
+        path: &Path,
+        info: Option<&ImageInfo>,
+        rating: Rating,
+    ) -> bool {
+        let file_name = || {
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_lowercase()
+        };
+        match self {
+            Self::Name(name) => file_name().contains(name.as_str()),
+            Self::Glob(pattern) => glob_matches(pattern, &file_name()),
+            Self::Ext(ext) => path
+                .extension()
+                .is_some_and(|e| e.to_string_lossy().to_lowercase() == *ext),
+            Self::Camera(camera) => info
+                .and_then(|info| info.exif.as_ref()?.camera())
+                .is_some_and(|c| c.to_lowercase().contains(camera.as_str())),
+            Self::Date(prefix) => info
+                .and_then(ImageInfo::day)
+                .is_some_and(|day| day.starts_with(prefix.as_str())),
+            Self::Dimension {
+                dimension,
+                comparison,
+                value,
+            } => {
+                let Some(size) = info.and_then(|info| info.size) else {
+                    return false;
+                };
+                let actual = match dimension {
+                    Dimension::Width => size.width,
+                    Dimension::Height => size.height,
+                };
+                comparison.compare(actual, *value)
+            }
+            Self::Label(label) => rating.label == Some(*label),
+            Self::Flag(flag) => rating.flag == *flag,
+            Self::Stars { comparison, value } => {
+                comparison.compare(rating.stars, *value)
+            }
+        }
+    }
+}
+
+impl Dimension {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "width" => Some(Self::Width),
+            "height" => Some(Self::Height),
+            _ => None,
+        }
+    }
+}
+
+impl Comparison {
+    /// Splits the comparison operator off the start of `string`.
+    fn parse(string: &str) -> Option<(Self, &str)> {
+        [
+            ("<=", Self::LessOrEqual),
+            (">=", Self::GreaterOrEqual),
+            ("<", Self::Less),
+            ("=", Self::Equal),
+            (">", Self::Greater),
+        ]
+        .into_iter()
+        .find_map(|(operator, comparison)| {
+            Some((comparison, string.strip_prefix(operator)?))
+        })
+    }
+
+    fn compare<T: Ord>(&self, actual: T, value: T) -> bool {
+        match self {
+            Self::Less => actual < value,
+            Self::LessOrEqual => actual <= value,
+            Self::Equal => actual == value,
+            Self::GreaterOrEqual => actual >= value,
+            Self::Greater => actual > value,
+        }
+    }
+}
+
+/// Matches a whole string against a glob, where `*` matches any run of
+/// characters and `?` matches any single one.
+fn glob_matches(pattern: &str, string: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let string: Vec<char> = string.chars().collect();
+    let mut pattern_idx = 0;
+    let mut string_idx = 0;
+    // Where the last `*` was seen: the pattern just past it, and where it
+    // started matching in the string. When the rest of the pattern fails to
+    // match, the star takes one more character and matching resumes.
+    let mut backtrack = None;
+    while string_idx < string.len() {
+        match pattern.get(pattern_idx) {
+            Some('*') => {
+                pattern_idx += 1;
+                backtrack = Some((pattern_idx, string_idx));
+            }
+            Some(&char) if char == '?' || char == string[string_idx] => {
+                pattern_idx += 1;
+                string_idx += 1;
+            }
+            _ => match backtrack {
+                Some((star_pattern_idx, star_string_idx)) => {
+                    pattern_idx = star_pattern_idx;
+                    string_idx = star_string_idx + 1;
+                    backtrack = Some((star_pattern_idx, string_idx));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[pattern_idx..].iter().all(|&char| char == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_info::ImageSize;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// A 6000 by 4000 image last modified on 2025-06-15, at noon UTC.
+    fn info() -> ImageInfo {
+        ImageInfo {
+            modified: Some(UNIX_EPOCH + Duration::from_secs(1_749_988_800)),
+            len: 1024,
+            size: Some(ImageSize {
+                width: 6000,
+                height: 4000,
+            }),
+            exif: None,
+        }
+    }
+
+    fn matches(query: &str, name: &str) -> bool {
+        Filter::parse(query).matches(
+            Path::new(name),
+            Some(&info()),
+            Rating::default(),
+        )
+    }
+
+    #[test]
+    fn parses_metadata_terms() {
+        assert_eq!(
+            Filter::parse("Width>4000 ext:.PNG date:2025-06").terms,
+            [
+                Term::Dimension {
+                    dimension: Dimension::Width,
+                    comparison: Comparison::Greater,
+                    value: 4000,
+                },
+                Term::Ext("png".into()),
+                Term::Date("2025-06".into()),
+            ]
+        );
+        assert_eq!(
+            Filter::parse("rating<=2 label:red flag:none").terms,
+            [
+                Term::Stars {
+                    comparison: Comparison::LessOrEqual,
+                    value: 2,
+                },
+                Term::Label(ColorLabel::Red),
+                Term::Flag(None),
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_bad_terms_against_the_name() {
+        assert_eq!(
+            Filter::parse("width>big label:pink ext: rating>=-1 depth>3").terms,
+            [
+                Term::Name("width>big".into()),
+                Term::Name("label:pink".into()),
+                Term::Name("ext:".into()),
+                Term::Name("rating>=-1".into()),
+                Term::Name("depth>3".into()),
+            ]
+        );
+        assert_eq!(Filter::parse("   ").terms, []);
+    }
+
+    #[test]
+    fn matches_metadata() {
+        assert!(matches("width>4000", "a.jpg"));
+        assert!(!matches("height>4000", "a.jpg"));
+        assert!(matches("height>=4000 width=6000", "a.jpg"));
+        assert!(matches("ext:png", "a.PNG"));
+        assert!(!matches("ext:png", "png.jpg"));
+        assert!(matches("date:2025-06", "a.jpg"));
+        assert!(!matches("date:2025-07", "a.jpg"));
+        assert!(!matches("camera:x100v", "a.jpg"));
+
+        // Without an info, no metadata term matches.
+        let filter = Filter::parse("width<100000");
+        assert!(!filter.matches(Path::new("a.jpg"), None, Rating::default()));
+    }
+
+    #[test]
+    fn matches_ratings() {
+        let rating = Rating::default()
+            .toggled_stars(3)
+            .toggled_label(ColorLabel::Red)
+            .with_flag(Some(Flag::Pick));
+        let matches = |query| {
+            Filter::parse(query).matches(Path::new("a.jpg"), None, rating)
+        };
+        assert!(matches("rating>=3 label:red flag:pick"));
+        assert!(!matches("rating>3"));
+        assert!(!matches("flag:none"));
+        assert!(!matches("label:blue"));
+    }
+
+    #[test]
+    fn matches_names_and_globs() {
+        assert!(matches("beach", "IMG_Beach_01.jpg"));
+        assert!(!matches("beach sunset", "IMG_Beach_01.jpg"));
+        assert!(matches("img_*.jpg", "IMG_Beach_01.jpg"));
+        assert!(matches("*_0?.jpg", "IMG_Beach_01.jpg"));
+        assert!(!matches("*_0?.jpg", "IMG_Beach_010.jpg"));
+        assert!(!matches("beach*", "IMG_Beach_01.jpg"));
+    }
+
+    #[test]
+    fn matches_globs_with_backtracking() {
+        assert!(glob_matches("*a*b", "xaxxab"));
+        assert!(glob_matches("a**", "a"));
+        assert!(glob_matches("*", ""));
+        assert!(!glob_matches("a*b", "ab_"));
+        assert!(!glob_matches("?", ""));
+    }
+}
@@ -0,0 +1,90 @@
+use makepad_widgets::*;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+#[derive(Clone, Debug)]
+pub enum FolderDialogAction {
+    Picked(PathBuf),
+    Failed(String),
+}
+
+/// Shows the platform's native folder picker on a background thread and
+/// posts a `FolderDialogAction` back to the UI thread once it closes. Nothing
+/// is posted if the user cancels the dialog.
+pub fn pick_folder(cx: &mut Cx, start_dir: Option<PathBuf>) {
+    cx.spawn_thread(move || match run_dialog(start_dir.as_deref()) {
+        Ok(Some(path)) => Cx::post_action(FolderDialogAction::Picked(path)),
+        Ok(None) => {}
+        Err(error) => {
+            Cx::post_action(FolderDialogAction::Failed(error.to_string()))
+        }
+    });
+}
+
+fn run_dialog(start_dir: Option<&Path>) -> io::Result<Option<PathBuf>> {
+    let mut command = dialog_command(start_dir);
+    let output = command.output()?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let path = stdout.trim_end_matches(['\r', '\n']);
+    if path.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(PathBuf::from(path)))
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn dialog_command(start_dir: Option<&Path>) -> Command {
+    let mut script = String::from("POSIX path of (choose folder");
+    if let Some(start_dir) = start_dir {
+        script.push_str(&format!(
+            " default location POSIX file \"{}\"",
+            start_dir.display().to_string().replace('"', "\\\"")
+        ));
+    }
+    script.push(')');
+
+    let mut command = Command::new("osascript");
+    command.arg("-e").arg(script);
+    command
+}
+
+#[cfg(target_os = "windows")]
+fn dialog_command(start_dir: Option<&Path>) -> Command {
+    let mut script = String::from(
+        "Add-Type -AssemblyName System.Windows.Forms;\
+         $d = New-Object System.Windows.Forms.FolderBrowserDialog;",
+    );
+    if let Some(start_dir) = start_dir {
+        script.push_str(&format!(
+            "$d.SelectedPath = '{}';",
+            start_dir.display().to_string().replace('\'', "''")
+        ));
+    }
+    script.push_str(
+        "if ($d.ShowDialog() -eq 'OK') { $d.SelectedPath } else { exit 1 }",
+    );
+
+    let mut command = Command::new("powershell");
+    command.args(["-NoProfile", "-Command", &script]);
+    command
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn dialog_command(start_dir: Option<&Path>) -> Command {
+    let mut command = Command::new("zenity");
+    command.args(["--file-selection", "--directory"]);
+    if let Some(start_dir) = start_dir {
+        let mut filename = start_dir.as_os_str().to_owned();
+        filename.push("/");
+        command.arg("--filename").arg(filename);
+    }
+    command
+}
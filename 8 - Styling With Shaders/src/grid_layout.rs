@@ -0,0 +1,218 @@
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GridLayout {
+    /// Square cells, with as many images per row as fit the grid width.
+    #[default]
+    Square,
+    /// Rows packed with images at their natural aspect ratio, each row
+    /// scaled to fill the grid width exactly.
+    Justified,
+}
+
+impl GridLayout {
+    pub const ALL: [Self; 2] = [Self::Square, Self::Justified];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Square => "square",
+            Self::Justified => "justified",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Square => "Grid",
+            Self::Justified => "Justified",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|layout| layout.name() == name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GridRow {
+    pub first_image_idx: usize,
+    pub num_images: usize,
+    pub height: f64,
+    /// The title of the group that starts at this row. Header rows like
+    /// these hold no images.
+    pub title: Option<String>,
+}
+
+impl GridRow {
+    pub fn header(first_image_idx: usize, height: f64, title: String) -> Self {
+        Self {
+            first_image_idx,
+            num_images: 0,
+            height,
+            title: Some(title),
+        }
+    }
+
+    pub fn images(
+        first_image_idx: usize,
+        num_images: usize,
+        height: f64,
+    ) -> Self {
+        Self {
+            first_image_idx,
+            num_images,
+            height,
+            title: None,
+        }
+    }
+}
+
+/// Splits images into rows of `images_per_row` square cells of `size`.
+pub fn square_rows(
+    first_image_idx: usize,
+    num_images: usize,
+    images_per_row: usize,
+    size: f64,
+) -> Vec<GridRow> {
+    let end_image_idx = first_image_idx + num_images;
+    (first_image_idx..end_image_idx)
+        .step_by(images_per_row.max(1))
+        .map(|image_idx| {
+            let num_images = images_per_row.min(end_image_idx - image_idx);
+            GridRow::images(image_idx, num_images, size)
+        })
+        .collect()
+}
+
+/// Packs images into rows of roughly `target_height`. Images are added to a
+/// row until it would overflow `width` at that height, and the row is then
+/// scaled down to fit `width` exactly. The last row is left at
+/// `target_height` rather than stretched across the grid.
+pub fn justify_rows(
+    first_image_idx: usize,
+    aspect_ratios: impl IntoIterator<Item = f64>,
+    width: f64,
+    target_height: f64,
+) -> Vec<GridRow> {
+    let mut rows = Vec::new();
+    let mut first_image_idx = first_image_idx;
+    let mut num_images = 0;
+    let mut total_aspect_ratio = 0.0;
+    for aspect_ratio in aspect_ratios {
+        num_images += 1;
+        total_aspect_ratio += aspect_ratio;
+        if total_aspect_ratio * target_height >= width {
+            rows.push(GridRow::images(
+                first_image_idx,
+                num_images,
+                width / total_aspect_ratio,
+            ));
+            first_image_idx += num_images;
+            num_images = 0;
+            total_aspect_ratio = 0.0;
+        }
+    }
+    if num_images > 0 {
+        rows.push(GridRow::images(first_image_idx, num_images, target_height));
+    }
+    rows
+}
+
+/// Finds the images whose cells overlap the area from `min` to `max`, with
+/// `rows` laid out from the top left corner of the grid and the cell of each
+/// image `item_width` wide. Header rows take up height but hold no cells.
+pub fn images_in_area(
+    rows: &[GridRow],
+    item_width: impl Fn(usize) -> f64,
+    min: (f64, f64),
+    max: (f64, f64),
+) -> Vec<usize> {
+    let mut image_idxs = Vec::new();
+    let mut top = 0.0;
+    for row in rows {
+        if top >= max.1 {
+            break;
+        }
+        let bottom = top + row.height;
+        if bottom > min.1 {
+            let mut left = 0.0;
+            let end_image_idx = row.first_image_idx + row.num_images;
+            for image_idx in row.first_image_idx..end_image_idx {
+                let right = left + item_width(image_idx);
+                if left < max.0 && right > min.0 {
+                    image_idxs.push(image_idx);
+                }
+                left = right;
+            }
+        }
+        top = bottom;
+    }
+    image_idxs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_images_into_square_rows() {
+        assert_eq!(
+            square_rows(2, 5, 2, 100.0),
+            [
+                GridRow::images(2, 2, 100.0),
+                GridRow::images(4, 2, 100.0),
+                GridRow::images(6, 1, 100.0),
+            ]
+        );
+        // A grid narrower than a single cell still shows one per row.
+        assert_eq!(square_rows(0, 2, 0, 100.0).len(), 2);
+        assert!(square_rows(0, 0, 3, 100.0).is_empty());
+    }
+
+    #[test]
+    fn justifies_rows_to_the_width() {
+        let rows = justify_rows(0, [2.0, 2.0, 1.0, 1.0, 1.0], 500.0, 100.0);
+        assert_eq!(
+            rows,
+            [
+                // At 100 high, the first three images would be 500 wide.
+                GridRow::images(0, 3, 100.0),
+                // The last row isn't stretched.
+                GridRow::images(3, 2, 100.0),
+            ]
+        );
+
+        let rows = justify_rows(0, [4.0, 2.0], 400.0, 100.0);
+        assert_eq!(
+            rows,
+            [GridRow::images(0, 1, 100.0), GridRow::images(1, 1, 100.0)]
+        );
+        let rows = justify_rows(0, [3.0, 2.0], 400.0, 100.0);
+        assert_eq!(rows, [GridRow::images(0, 2, 80.0)]);
+    }
+
+    #[test]
+    fn finds_the_images_in_an_area() {
+        let rows = [
+            GridRow::header(0, 40.0, "2025".into()),
+            GridRow::images(0, 3, 100.0),
+            GridRow::images(3, 3, 100.0),
+        ];
+        let item_width = |_| 100.0;
+        // The header holds no images.
+        assert!(
+            images_in_area(&rows, item_width, (0.0, 0.0), (300.0, 40.0))
+                .is_empty()
+        );
+        assert_eq!(
+            images_in_area(&rows, item_width, (150.0, 100.0), (250.0, 200.0)),
+            [1, 2, 4, 5]
+        );
+        // Cells that only touch the area are left out.
+        assert_eq!(
+            images_in_area(&rows, item_width, (100.0, 140.0), (200.0, 240.0)),
+            [4]
+        );
+        assert!(
+            images_in_area(&rows, item_width, (0.0, 300.0), (300.0, 400.0))
+                .is_empty()
+        );
+    }
+}
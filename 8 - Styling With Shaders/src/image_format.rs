@@ -0,0 +1,34 @@
+use std::{fs::File, io, io::Read, path::Path};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+}
+
+impl ImageFormat {
+    const MAX_SIGNATURE_LEN: usize = 8;
+
+    pub fn from_signature(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if bytes.starts_with(b"\xff\xd8\xff") {
+            Some(Self::Jpeg)
+        } else {
+            None
+        }
+    }
+
+    pub fn from_path(path: &Path) -> io::Result<Option<Self>> {
+        let mut file = File::open(path)?;
+        let mut bytes = [0; Self::MAX_SIGNATURE_LEN];
+        let mut len = 0;
+        while len < bytes.len() {
+            match file.read(&mut bytes[len..])? {
+                0 => break,
+                n => len += n,
+            }
+        }
+        Ok(Self::from_signature(&bytes[..len]))
+    }
+}
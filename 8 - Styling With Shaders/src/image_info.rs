@@ -0,0 +1,181 @@
+use crate::{date, exif::Exif, metadata::Header};
+use makepad_widgets::*;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::SystemTime,
+};
+
+const BATCH_SIZE: usize = 512;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageSize {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl ImageSize {
+    pub fn aspect_ratio(&self) -> f64 {
+        if self.width == 0 || self.height == 0 {
+            return 1.0;
+        }
+        self.width as f64 / self.height as f64
+    }
+
+    pub fn pixels(&self) -> usize {
+        self.width * self.height
+    }
+}
+
+/// What can be learned about an image without decoding it: its file
+/// metadata, and whatever its header says.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImageInfo {
+    pub modified: Option<SystemTime>,
+    pub len: u64,
+    /// `None` if the file is not in a supported format or its header is
+    /// malformed.
+    pub size: Option<ImageSize>,
+    pub exif: Option<Exif>,
+}
+
+impl ImageInfo {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let header = Header::read(&mut BufReader::new(File::open(path)?))?;
+        Ok(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            size: header.size,
+            exif: header.exif,
+        })
+    }
+
+    /// When the photo was taken, as `YYYY:MM:DD HH:MM:SS`.
+    pub fn date_taken(&self) -> Option<&str> {
+        self.exif.as_ref()?.date_time_original()
+    }
+
+    /// The day the photo was taken, or else the day the file was last
+    /// modified, as `YYYY-MM-DD`.
+    pub fn day(&self) -> Option<String> {
+        self.date_taken()
+            .and_then(date::exif_date)
+            .or_else(|| self.modified.map(date::format_date))
+    }
+}
+
+#[derive(Debug)]
+pub struct ImageInfoAction {
+    generation: u64,
+    infos: Vec<(PathBuf, ImageInfo)>,
+    is_last_batch: bool,
+}
+
+/// Image infos read on a background thread, so layouts and orderings that
+/// depend on them can be computed long before the images are decoded. Infos
+/// arrive in batches through `ImageInfoAction`s.
+pub struct ImageInfos {
+    generation: Arc<AtomicU64>,
+    infos: HashMap<PathBuf, ImageInfo>,
+    num_pending_requests: usize,
+}
+
+impl ImageInfos {
+    pub fn get(&self, path: &Path) -> Option<&ImageInfo> {
+        self.infos.get(path)
+    }
+
+    /// Returns whether the infos of every requested image have arrived.
+    pub fn is_complete(&self) -> bool {
+        self.num_pending_requests == 0
+    }
+
+    /// Starts reading the infos of those `paths` whose info is not known yet.
+    pub fn request(&mut self, cx: &mut Cx, paths: &[PathBuf]) {
+        let paths: Vec<PathBuf> = paths
+            .iter()
+            .filter(|path| !self.infos.contains_key(*path))
+            .cloned()
+            .collect();
+        if paths.is_empty() {
+            return;
+        }
+
+        self.num_pending_requests += 1;
+        let generation = self.generation.clone();
+        let job_generation = generation.load(Ordering::Relaxed);
+        cx.spawn_thread(move || {
+            let num_batches = paths.len().div_ceil(BATCH_SIZE);
+            for (batch_idx, paths) in paths.chunks(BATCH_SIZE).enumerate() {
+                // Stop early once the infos were cleared, e.g. because
+                // another folder was opened.
+                if generation.load(Ordering::Relaxed) != job_generation {
+                    return;
+                }
+                let infos = paths
+                    .iter()
+                    .filter_map(|path| match ImageInfo::read(path) {
+                        Ok(info) => Some((path.clone(), info)),
+                        Err(error) => {
+                            error!(
+                                "Failed to read info of {}: {}",
+                                path.display(),
+                                error
+                            );
+                            None
+                        }
+                    })
+                    .collect();
+                Cx::post_action(ImageInfoAction {
+                    generation: job_generation,
+                    infos,
+                    is_last_batch: batch_idx + 1 == num_batches,
+                });
+            }
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        self.infos.clear();
+        self.num_pending_requests = 0;
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.infos.remove(path);
+    }
+
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        if let Some(info) = self.infos.remove(from) {
+            self.infos.insert(to.to_path_buf(), info);
+        }
+    }
+
+    pub fn handle_action(&mut self, action: &ImageInfoAction) -> bool {
+        if action.generation != self.generation.load(Ordering::Relaxed) {
+            return false;
+        }
+        if action.is_last_batch {
+            self.num_pending_requests -= 1;
+        }
+        self.infos.extend(action.infos.iter().cloned());
+        true
+    }
+}
+
+impl Default for ImageInfos {
+    fn default() -> Self {
+        Self {
+            generation: Arc::new(AtomicU64::new(0)),
+            infos: HashMap::new(),
+            num_pending_requests: 0,
+        }
+    }
+}
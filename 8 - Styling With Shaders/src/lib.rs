@@ -1 +1,29 @@
 pub mod app;
+mod adjustments;
+mod config;
+mod date;
+mod decode;
+mod decode_pool;
+mod edit;
+mod exif;
+mod file_ops;
+mod filter;
+mod folder_dialog;
+mod grid_layout;
+mod image_format;
+mod image_info;
+mod metadata;
+mod orientation;
+mod rating;
+mod recent_folders;
+mod scan;
+mod selection;
+mod settings;
+mod sort;
+mod tags;
+mod texture_cache;
+mod thumbnail_cache;
+mod thumbnails;
+mod watcher;
+mod xmp;
+mod zoom;
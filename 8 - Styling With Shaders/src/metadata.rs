@@ -0,0 +1,533 @@
+use crate::{
+    exif::{self, Exif, Ifd, Value},
+    image_format::ImageFormat,
+    image_info::ImageSize,
+    orientation::Orientation,
+    xmp::Xmp,
+};
+use makepad_widgets::*;
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+/// PNG chunks larger than this are skipped rather than read into memory.
+const MAX_CHUNK_LEN: u32 = 1 << 24;
+const XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+
+/// What the header of an image file says about it: the segments of a JPEG
+/// before its frame header, or the chunks of a PNG before its image data.
+/// Metadata that is missing or can't be parsed is left out, without failing
+/// the rest.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Header {
+    /// The size the image is displayed at, after its orientation is applied.
+    pub size: Option<ImageSize>,
+    pub exif: Option<Exif>,
+    pub xmp: Option<Xmp>,
+    pub iptc: Option<Iptc>,
+}
+
+impl Header {
+    pub fn read(reader: &mut (impl Read + Seek)) -> io::Result<Self> {
+        let mut header = Self::default();
+        let mut signature = [0; 8];
+        if read_fully(reader, &mut signature)? < signature.len() {
+            return Ok(header);
+        }
+        match ImageFormat::from_signature(&signature) {
+            Some(ImageFormat::Png) => header.read_png(reader)?,
+            Some(ImageFormat::Jpeg) => header.read_jpeg(reader)?,
+            None => {}
+        }
+        if header.orientation().swaps_dimensions()
+            && let Some(size) = &mut header.size
+        {
+            std::mem::swap(&mut size.width, &mut size.height);
+        }
+        Ok(header)
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.exif
+            .as_ref()
+            .and_then(|exif| exif.get(Ifd::Image, exif::TAG_ORIENTATION))
+            .and_then(Value::as_u32)
+            .and_then(Orientation::from_exif)
+            .unwrap_or_default()
+    }
+
+    fn read_png(&mut self, reader: &mut (impl Read + Seek)) -> io::Result<()> {
+        loop {
+            // Each chunk is its length and type, followed by its data and a
+            // CRC, which is not checked.
+            let mut chunk_header = [0; 8];
+            if read_fully(reader, &mut chunk_header)? < chunk_header.len() {
+                return Ok(());
+            }
+            let len = u32::from_be_bytes(chunk_header[..4].try_into().unwrap());
+            let chunk_type = &chunk_header[4..];
+            if matches!(chunk_type, b"IDAT" | b"IEND") {
+                return Ok(());
+            }
+            if !matches!(chunk_type, b"IHDR" | b"eXIf" | b"iTXt")
+                || len > MAX_CHUNK_LEN
+            {
+                reader.seek_relative(len as i64 + 4)?;
+                continue;
+            }
+
+            let mut data = vec![0; len as usize];
+            if read_fully(reader, &mut data)? < data.len() {
+                return Ok(());
+            }
+            reader.seek_relative(4)?;
+            match chunk_type {
+                b"IHDR" if data.len() >= 8 => {
+                    let width =
+                        u32::from_be_bytes(data[..4].try_into().unwrap());
+                    let height =
+                        u32::from_be_bytes(data[4..8].try_into().unwrap());
+                    self.size = Some(ImageSize {
+                        width: width as usize,
+                        height: height as usize,
+                    });
+                }
+                b"eXIf" => self.exif = Exif::from_tiff(&data),
+                b"iTXt" => {
+                    if let Some(text) = xmp_from_itxt(&data) {
+                        self.xmp = Some(Xmp::parse(&text));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn read_jpeg(&mut self, reader: &mut (impl Read + Seek)) -> io::Result<()> {
+        // The signature check consumed the SOI marker and the first byte of
+        // the next marker, so step back to the start of that marker.
+        reader.seek_relative(-6)?;
+        loop {
+            let mut marker = [0; 2];
+            if read_fully(reader, &mut marker)? < marker.len()
+                || marker[0] != 0xff
+            {
+                return Ok(());
+            }
+            match marker[1] {
+                // Fill bytes may precede a marker.
+                0xff => {
+                    reader.seek_relative(-1)?;
+                    continue;
+                }
+                // Markers without a payload.
+                0x01 | 0xd0..=0xd8 => continue,
+                // End of image or start of scan before any frame header.
+                0xd9 | 0xda => return Ok(()),
+                _ => {}
+            }
+
+            let mut len = [0; 2];
+            if read_fully(reader, &mut len)? < len.len() {
+                return Ok(());
+            }
+            let len = u16::from_be_bytes(len) as i64;
+            if len < 2 {
+                return Ok(());
+            }
+
+            // APP1 holds either EXIF or XMP, and APP13 holds IPTC.
+            if matches!(marker[1], 0xe1 | 0xed) {
+                let mut segment = vec![0; len as usize - 2];
+                if read_fully(reader, &mut segment)? < segment.len() {
+                    return Ok(());
+                }
+                // Only the first segment of each kind is read.
+                let is_exif = segment.starts_with(b"Exif\0");
+                if marker[1] == 0xed && self.iptc.is_none() {
+                    self.iptc = Iptc::from_app13(&segment);
+                } else if marker[1] == 0xe1 && is_exif && self.exif.is_none() {
+                    self.exif = Exif::from_app1(&segment);
+                } else if marker[1] == 0xe1 && !is_exif && self.xmp.is_none() {
+                    self.xmp = Xmp::from_app1(&segment);
+                }
+                continue;
+            }
+
+            // SOF0 to SOF15, except DHT, JPG and DAC, which share the range.
+            if matches!(marker[1], 0xc0..=0xcf)
+                && !matches!(marker[1], 0xc4 | 0xc8 | 0xcc)
+            {
+                let mut frame = [0; 5];
+                if read_fully(reader, &mut frame)? < frame.len() {
+                    return Ok(());
+                }
+                let height = u16::from_be_bytes([frame[1], frame[2]]);
+                let width = u16::from_be_bytes([frame[3], frame[4]]);
+                self.size = Some(ImageSize {
+                    width: width as usize,
+                    height: height as usize,
+                });
+                return Ok(());
+            }
+            reader.seek_relative(len - 2)?;
+        }
+    }
+}
+
+/// The text of an `iTXt` chunk, if it holds uncompressed XMP.
+fn xmp_from_itxt(data: &[u8]) -> Option<String> {
+    // The keyword, compression flag and method, language tag and translated
+    // keyword come before the text, with the strings null terminated.
+    let mut fields = data.splitn(2, |&byte| byte == 0);
+    if fields.next()? != XMP_KEYWORD {
+        return None;
+    }
+    let rest = fields.next()?;
+    let ([compression_flag, _], rest) = rest.split_first_chunk::<2>()?;
+    if *compression_flag != 0 {
+        return None;
+    }
+    let mut fields = rest.splitn(3, |&byte| byte == 0);
+    let text = fields.nth(2)?;
+    Some(String::from_utf8_lossy(text).into_owned())
+}
+
+/// The IPTC-IIM records of a JPEG, as stored by Photoshop.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Iptc {
+    pub keywords: Vec<String>,
+    pub caption: Option<String>,
+}
+
+impl Iptc {
+    /// Parses the payload of a JPEG APP13 segment, which holds Photoshop
+    /// image resources, one of which holds the IPTC records.
+    fn from_app13(bytes: &[u8]) -> Option<Self> {
+        let mut resources = bytes.strip_prefix(b"Photoshop 3.0\0")?;
+        while let Some(resource) = resources.strip_prefix(b"8BIM") {
+            let id = u16::from_be_bytes(resource.get(..2)?.try_into().ok()?);
+            // The name is a Pascal string, padded to an even length.
+            let name_len = *resource.get(2)? as usize;
+            let data_offset = 2 + (1 + name_len).next_multiple_of(2);
+            let len = u32::from_be_bytes(
+                resource
+                    .get(data_offset..data_offset + 4)?
+                    .try_into()
+                    .ok()?,
+            ) as usize;
+            let data_start = data_offset + 4;
+            let data = resource.get(data_start..data_start + len)?;
+            if id == 0x0404 {
+                return Some(Self::from_records(data));
+            }
+            resources = resource.get(data_start + len.next_multiple_of(2)..)?;
+        }
+        None
+    }
+
+    fn from_records(mut records: &[u8]) -> Self {
+        let mut iptc = Self::default();
+        // Each record is a tag marker, record and dataset numbers, and a
+        // big endian length. Extended lengths, with the top bit set, are
+        // only used for large binary data, and end the parse.
+        while let [0x1c, record, dataset, len_hi, len_lo, rest @ ..] = records {
+            let len = u16::from_be_bytes([*len_hi, *len_lo]) as usize;
+            if len & 0x8000 != 0 || rest.len() < len {
+                break;
+            }
+            let value =
+                String::from_utf8_lossy(&rest[..len]).trim().to_string();
+            match (record, dataset) {
+                (2, 25) if !value.is_empty() => iptc.keywords.push(value),
+                (2, 120) if !value.is_empty() => iptc.caption = Some(value),
+                _ => {}
+            }
+            records = &rest[len..];
+        }
+        iptc
+    }
+}
+
+/// Everything the metadata panel shows about an image.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    pub size: Option<ImageSize>,
+    pub file_size: u64,
+    pub camera: Option<String>,
+    pub lens: Option<String>,
+    /// As a fraction of a second, like `(1, 250)`.
+    pub exposure_time: Option<(u32, u32)>,
+    pub f_number: Option<f64>,
+    pub iso: Option<u32>,
+    /// In millimeters.
+    pub focal_length: Option<f64>,
+    /// Latitude and longitude in degrees.
+    pub gps_position: Option<(f64, f64)>,
+    /// As `YYYY:MM:DD HH:MM:SS`.
+    pub date_taken: Option<String>,
+    /// XMP keywords, followed by any IPTC keywords not among them.
+    pub keywords: Vec<String>,
+    pub caption: Option<String>,
+}
+
+/// Posted by `read_and_post` once the metadata of an image has been read.
+#[derive(Debug)]
+pub struct MetadataAction {
+    pub path: PathBuf,
+    pub result: Result<Metadata, String>,
+}
+
+impl Metadata {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let file_size = file.metadata()?.len();
+        let header = Header::read(&mut BufReader::new(file))?;
+        Ok(Self::new(header, file_size))
+    }
+
+    pub fn read_and_post(path: PathBuf) {
+        let result = Self::read(&path).map_err(|error| error.to_string());
+        Cx::post_action(MetadataAction { path, result });
+    }
+
+    pub fn new(header: Header, file_size: u64) -> Self {
+        let exif = header.exif.unwrap_or_default();
+        let exif_value = |tag| exif.get(Ifd::Exif, tag);
+        let xmp = header.xmp.unwrap_or_default();
+        let iptc = header.iptc.unwrap_or_default();
+
+        let mut keywords = xmp.keywords;
+        for keyword in iptc.keywords {
+            if !keywords.contains(&keyword) {
+                keywords.push(keyword);
+            }
+        }
+
+        Self {
+            size: header.size,
+            file_size,
+            camera: exif.camera(),
+            lens: exif.lens(),
+            exposure_time: exif_value(exif::TAG_EXPOSURE_TIME)
+                .and_then(Value::as_rational)
+                .filter(|&(numerator, denominator)| {
+                    numerator != 0 && denominator != 0
+                }),
+            f_number: exif_value(exif::TAG_F_NUMBER).and_then(Value::as_f64),
+            iso: exif_value(exif::TAG_ISO).and_then(Value::as_u32),
+            focal_length: exif_value(exif::TAG_FOCAL_LENGTH)
+                .and_then(Value::as_f64),
+            gps_position: exif.gps_position(),
+            date_taken: exif.date_time_original().map(str::to_string),
+            keywords,
+            caption: xmp.caption.or(iptc.caption),
+        }
+    }
+
+    /// The labels and values to show, leaving out those that are missing.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = Vec::new();
+        if let Some(size) = self.size {
+            let dimensions = format!("{} × {}", size.width, size.height);
+            fields.push(("Dimensions", dimensions));
+        }
+        fields.push(("File Size", format_file_size(self.file_size)));
+        if let Some(date_taken) = &self.date_taken {
+            fields.push(("Taken", date_taken.clone()));
+        }
+        if let Some(camera) = &self.camera {
+            fields.push(("Camera", camera.clone()));
+        }
+        if let Some(lens) = &self.lens {
+            fields.push(("Lens", lens.clone()));
+        }
+        let exposure: Vec<String> = [
+            self.exposure_time.map(format_exposure_time),
+            self.f_number
+                .map(|f_number| format!("f/{}", format_decimal(f_number))),
+            self.iso.map(|iso| format!("ISO {}", iso)),
+            self.focal_length.map(|focal_length| {
+                format!("{} mm", format_decimal(focal_length))
+            }),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if !exposure.is_empty() {
+            fields.push(("Exposure", exposure.join("  ")));
+        }
+        if let Some((latitude, longitude)) = self.gps_position {
+            let position = format!(
+                "{:.5}° {}, {:.5}° {}",
+                latitude.abs(),
+                if latitude < 0.0 { "S" } else { "N" },
+                longitude.abs(),
+                if longitude < 0.0 { "W" } else { "E" },
+            );
+            fields.push(("Location", position));
+        }
+        if !self.keywords.is_empty() {
+            fields.push(("Keywords", self.keywords.join(", ")));
+        }
+        if let Some(caption) = &self.caption {
+            fields.push(("Caption", caption.clone()));
+        }
+        fields
+    }
+}
+
+fn format_exposure_time((numerator, denominator): (u32, u32)) -> String {
+    let seconds = numerator as f64 / denominator as f64;
+    if seconds < 1.0 {
+        format!("1/{} s", (1.0 / seconds).round())
+    } else {
+        format!("{} s", format_decimal(seconds))
+    }
+}
+
+/// Formats a number with at most one decimal, leaving out a zero decimal.
+fn format_decimal(value: f64) -> String {
+    let formatted = format!("{:.1}", value);
+    match formatted.strip_suffix(".0") {
+        Some(integer) => integer.to_string(),
+        None => formatted,
+    }
+}
+
+fn format_file_size(len: u64) -> String {
+    const UNITS: [&str; 4] = ["bytes", "KB", "MB", "GB"];
+    let mut size = len as f64;
+    let mut unit_idx = 0;
+    while size >= 1000.0 && unit_idx + 1 < UNITS.len() {
+        size /= 1000.0;
+        unit_idx += 1;
+    }
+    if unit_idx == 0 {
+        format!("{} {}", len, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit_idx])
+    }
+}
+
+fn read_fully(reader: &mut impl Read, bytes: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < bytes.len() {
+        match reader.read(&mut bytes[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn metadata(bytes: &[u8]) -> Metadata {
+        let header = Header::read(&mut Cursor::new(bytes)).unwrap();
+        Metadata::new(header, bytes.len() as u64)
+    }
+
+    fn field(metadata: &Metadata, label: &str) -> Option<String> {
+        metadata
+            .fields()
+            .into_iter()
+            .find(|(field_label, _)| *field_label == label)
+            .map(|(_, value)| value)
+    }
+
+    #[test]
+    fn reads_exif_xmp_and_iptc_from_jpeg() {
+        let metadata =
+            metadata(include_bytes!("../fixtures/metadata/full.jpg"));
+        assert_eq!(
+            metadata.size,
+            Some(ImageSize {
+                width: 3,
+                height: 2,
+            })
+        );
+        assert_eq!(metadata.camera.as_deref(), Some("FUJIFILM X100V"));
+        assert_eq!(metadata.lens.as_deref(), Some("FUJIFILM FUJINON 23mm F2"));
+        assert_eq!(metadata.date_taken.as_deref(), Some("2025:06:14 19:42:07"));
+        assert_eq!(metadata.keywords, ["tokyo", "street", "night"]);
+        assert_eq!(metadata.caption.as_deref(), Some("Shibuya & rain"));
+        assert_eq!(
+            field(&metadata, "Exposure").as_deref(),
+            Some("1/250 s  f/2.8  ISO 200  23 mm")
+        );
+        assert_eq!(
+            field(&metadata, "Location").as_deref(),
+            Some("35.67917° N, 139.77167° W")
+        );
+    }
+
+    #[test]
+    fn reads_big_endian_exif() {
+        let metadata =
+            metadata(include_bytes!("../fixtures/metadata/big_endian.jpg"));
+        assert_eq!(metadata.camera.as_deref(), Some("Canon EOS R5"));
+        assert_eq!(metadata.exposure_time, Some((2, 1)));
+        assert_eq!(field(&metadata, "Exposure").as_deref(), Some("2 s"));
+        assert_eq!(metadata.date_taken.as_deref(), Some("2024:12:31 23:59:59"));
+    }
+
+    #[test]
+    fn reads_exif_and_xmp_from_png() {
+        let metadata =
+            metadata(include_bytes!("../fixtures/metadata/exif_xmp.png"));
+        assert_eq!(
+            metadata.size,
+            Some(ImageSize {
+                width: 3,
+                height: 2,
+            })
+        );
+        assert_eq!(metadata.camera.as_deref(), Some("Apple iPhone 15"));
+        assert_eq!(metadata.keywords, ["screenshot", "ui <draft>"]);
+    }
+
+    #[test]
+    fn skips_corrupt_metadata() {
+        let metadata =
+            metadata(include_bytes!("../fixtures/metadata/corrupt.jpg"));
+        assert_eq!(
+            metadata.size,
+            Some(ImageSize {
+                width: 3,
+                height: 2,
+            })
+        );
+        assert_eq!(metadata.camera, None);
+        assert_eq!(metadata.f_number, None);
+        assert_eq!(metadata.date_taken, None);
+        assert!(metadata.keywords.is_empty());
+        assert_eq!(metadata.caption, None);
+    }
+
+    #[test]
+    fn reads_what_it_can_from_truncated_files() {
+        let metadata =
+            metadata(include_bytes!("../fixtures/metadata/truncated.jpg"));
+        assert_eq!(metadata.size, None);
+        assert_eq!(metadata.camera, None);
+        assert_eq!(field(&metadata, "File Size").as_deref(), Some("60 bytes"));
+    }
+
+    #[test]
+    fn reads_images_without_metadata() {
+        let metadata =
+            metadata(include_bytes!("../fixtures/metadata/no_metadata.png"));
+        let labels: Vec<_> = metadata
+            .fields()
+            .into_iter()
+            .map(|(label, _)| label)
+            .collect();
+        assert_eq!(labels, ["Dimensions", "File Size"]);
+    }
+}
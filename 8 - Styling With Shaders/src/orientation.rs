@@ -0,0 +1,326 @@
+/// How the stored pixels of an image must be transformed to display it
+/// upright, as given by the EXIF Orientation tag. Cameras write images in
+/// sensor order and record how they were held in this tag, rather than
+/// rotating the pixels themselves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Orientation {
+    #[default]
+    Normal,
+    FlipHorizontal,
+    Rotate180,
+    FlipVertical,
+    /// Flipped across the diagonal from the top left corner.
+    Transpose,
+    /// Rotated 90 degrees clockwise.
+    Rotate90,
+    /// Flipped across the diagonal from the top right corner.
+    Transverse,
+    /// Rotated 90 degrees counterclockwise.
+    Rotate270,
+}
+
+impl Orientation {
+    pub const ALL: [Self; 8] = [
+        Self::Normal,
+        Self::FlipHorizontal,
+        Self::Rotate180,
+        Self::FlipVertical,
+        Self::Transpose,
+        Self::Rotate90,
+        Self::Transverse,
+        Self::Rotate270,
+    ];
+
+    /// Converts the value of the EXIF tag, which runs from 1 to 8 in the
+    /// order of `ALL`.
+    pub fn from_exif(value: u32) -> Option<Self> {
+        Self::ALL.get((value as usize).checked_sub(1)?).copied()
+    }
+
+    pub fn to_exif(self) -> u16 {
+        Self::ALL.iter().position(|o| *o == self).unwrap() as u16 + 1
+    }
+
+    /// Every orientation is a horizontal flip or not, followed by a number
+    /// of clockwise quarter turns.
+    fn to_parts(self) -> (bool, u8) {
+        match self {
+            Self::Normal => (false, 0),
+            Self::Rotate90 => (false, 1),
+            Self::Rotate180 => (false, 2),
+            Self::Rotate270 => (false, 3),
+            Self::FlipHorizontal => (true, 0),
+            Self::Transverse => (true, 1),
+            Self::FlipVertical => (true, 2),
+            Self::Transpose => (true, 3),
+        }
+    }
+
+    fn from_parts(is_flipped: bool, quarter_turns: u8) -> Self {
+        let orientations = if is_flipped {
+            [
+                Self::FlipHorizontal,
+                Self::Transverse,
+                Self::FlipVertical,
+                Self::Transpose,
+            ]
+        } else {
+            [
+                Self::Normal,
+                Self::Rotate90,
+                Self::Rotate180,
+                Self::Rotate270,
+            ]
+        };
+        orientations[quarter_turns as usize % 4]
+    }
+
+    /// Returns the orientation that transforms the pixels like this one
+    /// does, followed by `next`.
+    pub fn then(&self, next: Self) -> Self {
+        let (is_flipped, quarter_turns) = self.to_parts();
+        let (next_is_flipped, next_quarter_turns) = next.to_parts();
+        // Flipping an image turned one way is the same as turning it the
+        // other way and then flipping it.
+        let quarter_turns = if next_is_flipped {
+            4 - quarter_turns
+        } else {
+            quarter_turns
+        };
+        Self::from_parts(
+            is_flipped != next_is_flipped,
+            quarter_turns + next_quarter_turns,
+        )
+    }
+
+    /// Returns whether the image is displayed with its width and height
+    /// swapped.
+    pub fn swaps_dimensions(&self) -> bool {
+        matches!(
+            self,
+            Self::Transpose
+                | Self::Rotate90
+                | Self::Transverse
+                | Self::Rotate270
+        )
+    }
+
+    /// Transforms the pixels of an image stored `width` pixels wide into
+    /// the pixels to display. Returns the displayed width and height along
+    /// with the pixels.
+    pub fn apply(
+        &self,
+        width: usize,
+        height: usize,
+        pixels: Vec<u32>,
+    ) -> (usize, usize, Vec<u32>) {
+        if *self == Self::Normal {
+            return (width, height, pixels);
+        }
+        let (display_width, display_height) = if self.swaps_dimensions() {
+            (height, width)
+        } else {
+            (width, height)
+        };
+
+        let mut display_pixels = Vec::with_capacity(pixels.len());
+        for y in 0..display_height {
+            for x in 0..display_width {
+                let (source_x, source_y) =
+                    self.source_pixel(x, y, width, height);
+                display_pixels.push(pixels[source_y * width + source_x]);
+            }
+        }
+        (display_width, display_height, display_pixels)
+    }
+
+    /// Returns which of the stored pixels of an image stored `width` pixels
+    /// wide and `height` pixels high is displayed at `x`, `y`.
+    pub fn source_pixel(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> (usize, usize) {
+        match self {
+            Self::Normal => (x, y),
+            Self::FlipHorizontal => (width - 1 - x, y),
+            Self::Rotate180 => (width - 1 - x, height - 1 - y),
+            Self::FlipVertical => (x, height - 1 - y),
+            Self::Transpose => (y, x),
+            Self::Rotate90 => (y, height - 1 - x),
+            Self::Transverse => (width - 1 - y, height - 1 - x),
+            Self::Rotate270 => (width - 1 - y, x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode, metadata::Header};
+    use std::io::Cursor;
+
+    const UPRIGHT: [u32; 6] = [1, 2, 3, 4, 5, 6];
+
+    /// For every orientation, the pixels a camera stores for the upright
+    /// image 3 pixels wide and 2 high in `UPRIGHT`, with their width.
+    const STORED: [(usize, [u32; 6]); 8] = [
+        (3, [1, 2, 3, 4, 5, 6]),
+        (3, [3, 2, 1, 6, 5, 4]),
+        (3, [6, 5, 4, 3, 2, 1]),
+        (3, [4, 5, 6, 1, 2, 3]),
+        (2, [1, 4, 2, 5, 3, 6]),
+        (2, [3, 6, 2, 5, 1, 4]),
+        (2, [6, 3, 5, 2, 4, 1]),
+        (2, [4, 1, 5, 2, 6, 3]),
+    ];
+
+    /// PNGs of the same upright image, stored in each orientation, with the
+    /// orientation in an `eXIf` chunk.
+    const CORPUS: [&[u8]; 8] = [
+        include_bytes!("../fixtures/orientation/1.png"),
+        include_bytes!("../fixtures/orientation/2.png"),
+        include_bytes!("../fixtures/orientation/3.png"),
+        include_bytes!("../fixtures/orientation/4.png"),
+        include_bytes!("../fixtures/orientation/5.png"),
+        include_bytes!("../fixtures/orientation/6.png"),
+        include_bytes!("../fixtures/orientation/7.png"),
+        include_bytes!("../fixtures/orientation/8.png"),
+    ];
+
+    /// JPEGs of an upright image made of 3 by 2 blocks of 16 pixels, one
+    /// color each, stored in each orientation like `STORED`, with the
+    /// orientation in an EXIF segment. The blocks line up with those JPEG
+    /// compresses, so every orientation decodes to nearly the same pixels.
+    const JPEG_CORPUS: [&[u8]; 8] = [
+        include_bytes!("../fixtures/orientation/1.jpg"),
+        include_bytes!("../fixtures/orientation/2.jpg"),
+        include_bytes!("../fixtures/orientation/3.jpg"),
+        include_bytes!("../fixtures/orientation/4.jpg"),
+        include_bytes!("../fixtures/orientation/5.jpg"),
+        include_bytes!("../fixtures/orientation/6.jpg"),
+        include_bytes!("../fixtures/orientation/7.jpg"),
+        include_bytes!("../fixtures/orientation/8.jpg"),
+    ];
+
+    #[test]
+    fn converts_exif_values() {
+        assert_eq!(Orientation::from_exif(0), None);
+        assert_eq!(Orientation::from_exif(1), Some(Orientation::Normal));
+        assert_eq!(Orientation::from_exif(6), Some(Orientation::Rotate90));
+        assert_eq!(Orientation::from_exif(8), Some(Orientation::Rotate270));
+        assert_eq!(Orientation::from_exif(9), None);
+        for orientation in Orientation::ALL {
+            let value = orientation.to_exif() as u32;
+            assert_eq!(Orientation::from_exif(value), Some(orientation));
+        }
+    }
+
+    #[test]
+    fn turns_stored_pixels_upright() {
+        for (orientation, (width, pixels)) in
+            Orientation::ALL.into_iter().zip(STORED)
+        {
+            let height = pixels.len() / width;
+            assert_eq!(
+                orientation.apply(width, height, pixels.to_vec()),
+                (3, 2, UPRIGHT.to_vec()),
+                "{:?}",
+                orientation
+            );
+        }
+    }
+
+    #[test]
+    fn composes_like_applying_one_after_the_other() {
+        for first in Orientation::ALL {
+            for next in Orientation::ALL {
+                let (width, height, pixels) =
+                    first.apply(3, 2, UPRIGHT.to_vec());
+                assert_eq!(
+                    first.then(next).apply(3, 2, UPRIGHT.to_vec()),
+                    next.apply(width, height, pixels),
+                    "{:?} then {:?}",
+                    first,
+                    next
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn reads_orientation_and_display_size_from_corpus() {
+        for (orientation, bytes) in Orientation::ALL.into_iter().zip(CORPUS) {
+            let header = Header::read(&mut Cursor::new(bytes)).unwrap();
+            assert_eq!(header.orientation(), orientation);
+            let size = header.size.unwrap();
+            assert_eq!((size.width, size.height), (3, 2), "{:?}", orientation);
+        }
+    }
+
+    #[test]
+    fn reads_orientation_and_display_size_from_jpeg_corpus() {
+        for (orientation, bytes) in
+            Orientation::ALL.into_iter().zip(JPEG_CORPUS)
+        {
+            let header = Header::read(&mut Cursor::new(bytes)).unwrap();
+            assert_eq!(header.orientation(), orientation);
+            let size = header.size.unwrap();
+            assert_eq!(
+                (size.width, size.height),
+                (48, 32),
+                "{:?}",
+                orientation
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_jpeg_corpus_upright() {
+        let upright = decode::decode(JPEG_CORPUS[0]).unwrap();
+        for (orientation, bytes) in
+            Orientation::ALL.into_iter().zip(JPEG_CORPUS)
+        {
+            let image = decode::decode(bytes).unwrap();
+            assert_eq!(
+                (image.width, image.height),
+                (upright.width, upright.height),
+                "{:?}",
+                orientation
+            );
+            // Compression leaves the colors a little off, but never by as
+            // much as the blocks differ by.
+            for (pixel, upright_pixel) in
+                image.pixels.iter().zip(&upright.pixels)
+            {
+                let channels = pixel.to_le_bytes().into_iter();
+                let upright_channels = upright_pixel.to_le_bytes().into_iter();
+                assert!(
+                    channels
+                        .zip(upright_channels)
+                        .all(|(a, b)| a.abs_diff(b) <= 8),
+                    "{:?}: {:08x} != {:08x}",
+                    orientation,
+                    pixel,
+                    upright_pixel
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_corpus_upright() {
+        let upright = decode::decode(CORPUS[0]).unwrap();
+        for (orientation, bytes) in Orientation::ALL.into_iter().zip(CORPUS) {
+            let image = decode::decode(bytes).unwrap();
+            assert_eq!(
+                (image.width, image.height, &image.pixels),
+                (upright.width, upright.height, &upright.pixels),
+                "{:?}",
+                orientation
+            );
+        }
+    }
+}
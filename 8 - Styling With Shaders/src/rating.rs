@@ -0,0 +1,378 @@
+use crate::xmp::{self, Xmp};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The most stars an image can be given.
+pub const MAX_STARS: u8 = 5;
+
+/// The color labels other photo tools offer, by the names they write to
+/// `xmp:Label`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorLabel {
+    Red,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+}
+
+impl ColorLabel {
+    pub const ALL: [Self; 5] = [
+        Self::Red,
+        Self::Yellow,
+        Self::Green,
+        Self::Blue,
+        Self::Purple,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Red => "red",
+            Self::Yellow => "yellow",
+            Self::Green => "green",
+            Self::Blue => "blue",
+            Self::Purple => "purple",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Red => "Red",
+            Self::Yellow => "Yellow",
+            Self::Green => "Green",
+            Self::Blue => "Blue",
+            Self::Purple => "Purple",
+        }
+    }
+
+    /// Looks a label up by name, ignoring case, so both `red` and the `Red`
+    /// of an XMP packet are found.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|label| label.name().eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flag {
+    Pick,
+    Reject,
+}
+
+impl Flag {
+    pub const ALL: [Self; 2] = [Self::Pick, Self::Reject];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pick => "pick",
+            Self::Reject => "reject",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|flag| flag.name() == name)
+    }
+}
+
+/// How an image was rated while culling. Ratings are kept in an XMP sidecar
+/// next to the image, where other photo tools read them too: stars as
+/// `xmp:Rating`, the color label as `xmp:Label`, and the flag as
+/// `xmpDM:good`. Rejected images are also given the rating -1, which is how
+/// Lightroom and darktable mark them. That leaves no room for stars, so
+/// rejecting an image clears its stars, and starring it lifts the reject.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rating {
+    pub stars: u8,
+    pub label: Option<ColorLabel>,
+    pub flag: Option<Flag>,
+}
+
+impl Rating {
+    pub fn from_xmp(xmp: &Xmp) -> Self {
+        let flag = match (xmp.rating, xmp.good) {
+            (_, Some(true)) => Some(Flag::Pick),
+            (Some(-1), _) | (_, Some(false)) => Some(Flag::Reject),
+            _ => None,
+        };
+        let stars = match flag {
+            Some(Flag::Reject) => 0,
+            _ => xmp.rating.unwrap_or(0).clamp(0, MAX_STARS as i32) as u8,
+        };
+        Self {
+            stars,
+            label: xmp.label.as_deref().and_then(ColorLabel::from_name),
+            flag,
+        }
+    }
+
+    /// Reads the rating from the sidecar of the image at `path`, if it has
+    /// one.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(sidecar_path(path)) {
+            Ok(packet) => Ok(Self::from_xmp(&Xmp::parse(&packet))),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                Ok(Self::default())
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Writes the rating to the sidecar of the image at `path`, keeping
+    /// whatever else other tools wrote there.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let sidecar_path = sidecar_path(path);
+        let packet = match fs::read_to_string(&sidecar_path) {
+            Ok(packet) => packet,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                if *self == Self::default() {
+                    return Ok(());
+                }
+                xmp::EMPTY_PACKET.to_string()
+            }
+            Err(error) => return Err(error),
+        };
+        fs::write(&sidecar_path, self.write_to(&packet))
+    }
+
+    /// Sets the properties of the rating in an XMP packet.
+    fn write_to(&self, packet: &str) -> String {
+        let rating = match self.flag {
+            Some(Flag::Reject) => Some("-1".to_string()),
+            _ if self.stars > 0 => Some(self.stars.to_string()),
+            _ => None,
+        };
+        let good = self.flag.map(|flag| match flag {
+            Flag::Pick => "True",
+            Flag::Reject => "False",
+        });
+        let packet = xmp::set_property(
+            packet,
+            "xmp:Rating",
+            xmp::NS_XMP,
+            rating.as_deref(),
+        );
+        let packet = xmp::set_property(
+            &packet,
+            "xmp:Label",
+            xmp::NS_XMP,
+            self.label.map(|label| label.label()),
+        );
+        xmp::set_property(&packet, "xmpDM:good", xmp::NS_XMP_DM, good)
+    }
+
+    /// The rating as `xmp:Rating` has it, which orders rejected images
+    /// before unrated ones.
+    pub fn value(&self) -> i32 {
+        match self.flag {
+            Some(Flag::Reject) => -1,
+            _ => self.stars as i32,
+        }
+    }
+
+    /// Gives the image `stars` stars, or none if it already has that many.
+    /// Stars lift a reject.
+    pub fn toggled_stars(self, stars: u8) -> Self {
+        let stars = if self.stars == stars { 0 } else { stars };
+        let flag = match self.flag {
+            Some(Flag::Reject) if stars > 0 => None,
+            flag => flag,
+        };
+        Self {
+            stars,
+            flag,
+            ..self
+        }
+    }
+
+    /// Gives the image `label`, or removes it if it already has it.
+    pub fn toggled_label(self, label: ColorLabel) -> Self {
+        Self {
+            label: if self.label == Some(label) {
+                None
+            } else {
+                Some(label)
+            },
+            ..self
+        }
+    }
+
+    /// Sets the flag of the image. A reject clears its stars.
+    pub fn with_flag(self, flag: Option<Flag>) -> Self {
+        let stars = match flag {
+            Some(Flag::Reject) => 0,
+            _ => self.stars,
+        };
+        Self {
+            stars,
+            flag,
+            ..self
+        }
+    }
+
+    /// Describes the rating for the status line, as in `3 stars, Red,
+    /// picked`.
+    pub fn describe(&self) -> String {
+        let mut parts = vec![match self.stars {
+            0 => "No stars".to_string(),
+            1 => "1 star".to_string(),
+            stars => format!("{} stars", stars),
+        }];
+        if let Some(label) = self.label {
+            parts.push(label.label().to_string());
+        }
+        match self.flag {
+            Some(Flag::Pick) => parts.push("picked".into()),
+            Some(Flag::Reject) => parts.push("rejected".into()),
+            None => {}
+        }
+        parts.join(", ")
+    }
+}
+
+/// The ratings of the images in a folder. Images without a sidecar are
+/// unrated.
+#[derive(Clone, Debug, Default)]
+pub struct Ratings {
+    ratings: HashMap<PathBuf, Rating>,
+}
+
+impl Ratings {
+    pub fn get(&self, path: &Path) -> Rating {
+        self.ratings.get(path).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, path: &Path, rating: Rating) {
+        if rating == Rating::default() {
+            self.ratings.remove(path);
+        } else {
+            self.ratings.insert(path.to_path_buf(), rating);
+        }
+    }
+
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        if let Some(rating) = self.ratings.remove(from) {
+            self.ratings.insert(to.to_path_buf(), rating);
+        }
+    }
+}
+
+/// The sidecar of `IMG_1234.jpg` is `IMG_1234.jpg.xmp`, next to it, as
+/// darktable names them. Keeping the extension of the image means that
+/// `IMG_1234.jpg` and `IMG_1234.png` don't share a sidecar.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".xmp");
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_xmp() {
+        let ratings = [
+            Rating::default(),
+            Rating {
+                stars: 3,
+                label: Some(ColorLabel::Green),
+                flag: Some(Flag::Pick),
+            },
+            Rating {
+                stars: 0,
+                label: Some(ColorLabel::Purple),
+                flag: Some(Flag::Reject),
+            },
+        ];
+        for rating in ratings {
+            let packet = rating.write_to(xmp::EMPTY_PACKET);
+            assert_eq!(Rating::from_xmp(&Xmp::parse(&packet)), rating);
+        }
+    }
+
+    #[test]
+    fn keeps_what_other_tools_wrote() {
+        let packet = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmp:Rating="2">
+   <xmp:Label>Blue</xmp:Label>
+   <dc:subject><rdf:Bag><rdf:li>beach</rdf:li></rdf:Bag></dc:subject>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+        let rating = Rating::from_xmp(&Xmp::parse(packet));
+        assert_eq!(
+            rating,
+            Rating {
+                stars: 2,
+                label: Some(ColorLabel::Blue),
+                flag: None,
+            }
+        );
+
+        let rating = rating.toggled_stars(4).toggled_label(ColorLabel::Blue);
+        let packet = rating.write_to(packet);
+        let xmp = Xmp::parse(&packet);
+        assert_eq!(Rating::from_xmp(&xmp), rating);
+        assert_eq!(xmp.keywords, ["beach"]);
+        assert_eq!(packet.matches("xmlns:xmp=").count(), 1);
+    }
+
+    #[test]
+    fn rejecting_clears_the_stars() {
+        let rating = Rating {
+            stars: 3,
+            label: Some(ColorLabel::Red),
+            flag: None,
+        }
+        .with_flag(Some(Flag::Reject));
+        assert_eq!(rating.stars, 0);
+        let packet = rating.write_to(xmp::EMPTY_PACKET);
+        assert_eq!(Rating::from_xmp(&Xmp::parse(&packet)), rating);
+
+        let rating = rating.toggled_stars(2);
+        assert_eq!(rating.stars, 2);
+        assert_eq!(rating.flag, None);
+    }
+
+    #[test]
+    fn reads_starred_rejects_without_their_stars() {
+        let xmp = Xmp {
+            rating: Some(4),
+            good: Some(false),
+            ..Default::default()
+        };
+        let rating = Rating::from_xmp(&xmp);
+        assert_eq!(rating.flag, Some(Flag::Reject));
+        assert_eq!(rating.stars, 0);
+    }
+
+    #[test]
+    fn gives_each_image_its_own_sidecar() {
+        assert_eq!(
+            sidecar_path(Path::new("/photos/IMG_1.jpg")),
+            Path::new("/photos/IMG_1.jpg.xmp")
+        );
+        assert_ne!(
+            sidecar_path(Path::new("IMG_1.jpg")),
+            sidecar_path(Path::new("IMG_1.png"))
+        );
+    }
+
+    #[test]
+    fn reads_rejects_as_lightroom_writes_them() {
+        let xmp = Xmp {
+            rating: Some(-1),
+            ..Default::default()
+        };
+        assert_eq!(Rating::from_xmp(&xmp).flag, Some(Flag::Reject));
+        assert_eq!(Rating::from_xmp(&xmp).value(), -1);
+    }
+}
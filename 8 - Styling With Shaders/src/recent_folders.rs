@@ -0,0 +1,71 @@
+use crate::config::{self, ConfigError, Table, Value};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+const MAX_RECENT_FOLDERS: usize = 10;
+
+#[derive(Clone, Debug, Default)]
+pub struct RecentFolders {
+    folders: Vec<PathBuf>,
+}
+
+impl RecentFolders {
+    pub fn load() -> Result<Self, ConfigError> {
+        let Some(path) = file_path() else {
+            return Ok(Self::default());
+        };
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        let table = config::read_table(&path)?;
+        let folders = table
+            .get("folders")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(PathBuf::from)
+            .take(MAX_RECENT_FOLDERS)
+            .collect();
+        Ok(Self { folders })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = file_path() else {
+            return Ok(());
+        };
+
+        let mut table = Table::new();
+        table.insert(
+            "folders".into(),
+            Value::Array(
+                self.folders
+                    .iter()
+                    .map(|folder| {
+                        Value::String(folder.to_string_lossy().into_owned())
+                    })
+                    .collect(),
+            ),
+        );
+        config::write_table(&path, &table)
+    }
+
+    pub fn folders(&self) -> &[PathBuf] {
+        &self.folders
+    }
+
+    pub fn push(&mut self, folder: &Path) {
+        let folder = folder.canonicalize().unwrap_or_else(|_| folder.into());
+        self.folders
+            .retain(|recent_folder| *recent_folder != folder);
+        self.folders.insert(0, folder);
+        self.folders.truncate(MAX_RECENT_FOLDERS);
+    }
+}
+
+fn file_path() -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join("recent_folders.toml"))
+}
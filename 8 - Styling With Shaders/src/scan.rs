@@ -0,0 +1,236 @@
+use crate::{
+    image_format::ImageFormat,
+    rating::{self, Rating, Ratings},
+};
+use std::{
+    collections::HashSet,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScanOptions {
+    pub recursive: bool,
+}
+
+#[derive(Debug)]
+pub struct ScanError {
+    pub path: PathBuf,
+    pub error: io::Error,
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    pub image_paths: Vec<PathBuf>,
+    /// The ratings of those images that have an XMP sidecar.
+    pub ratings: Ratings,
+    pub errors: Vec<ScanError>,
+}
+
+pub fn scan_dir(dir: &Path, options: ScanOptions) -> ScanResult {
+    let mut result = ScanResult::default();
+    let mut visited_dirs = HashSet::new();
+    let mut sidecar_paths = HashSet::new();
+    scan_dir_into(
+        dir,
+        options,
+        &mut visited_dirs,
+        &mut sidecar_paths,
+        &mut result,
+    );
+    result.image_paths.sort();
+
+    // Sidecars are only read for images that were seen to have one, so
+    // folders without them cost no extra file system calls.
+    for path in &result.image_paths {
+        let sidecar_path = rating::sidecar_path(path);
+        if !sidecar_paths.contains(&sidecar_path) {
+            continue;
+        }
+        match Rating::load(path) {
+            Ok(rating) => result.ratings.set(path, rating),
+            Err(error) => result.errors.push(ScanError {
+                path: sidecar_path,
+                error,
+            }),
+        }
+    }
+    result
+}
+
+fn scan_dir_into(
+    dir: &Path,
+    options: ScanOptions,
+    visited_dirs: &mut HashSet<PathBuf>,
+    sidecar_paths: &mut HashSet<PathBuf>,
+    result: &mut ScanResult,
+) {
+    // Symlinked directories can point back up the tree, so we remember every
+    // directory by its canonical path and never enter the same one twice.
+    match dir.canonicalize() {
+        Ok(canonical_dir) => {
+            if !visited_dirs.insert(canonical_dir) {
+                return;
+            }
+        }
+        Err(error) => {
+            result.errors.push(ScanError {
+                path: dir.to_path_buf(),
+                error,
+            });
+            return;
+        }
+    }
+
+    let entries = match dir.read_dir() {
+        Ok(entries) => entries,
+        Err(error) => {
+            result.errors.push(ScanError {
+                path: dir.to_path_buf(),
+                error,
+            });
+            return;
+        }
+    };
+
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(error) => {
+                result.errors.push(ScanError {
+                    path: dir.to_path_buf(),
+                    error,
+                });
+                continue;
+            }
+        };
+
+        if is_hidden(&path) {
+            continue;
+        }
+
+        if path.is_dir() {
+            if options.recursive {
+                scan_dir_into(
+                    &path,
+                    options,
+                    visited_dirs,
+                    sidecar_paths,
+                    result,
+                );
+            }
+            continue;
+        }
+
+        if !path.is_file() {
+            continue;
+        }
+
+        if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("xmp"))
+        {
+            sidecar_paths.insert(path);
+            continue;
+        }
+
+        match ImageFormat::from_path(&path) {
+            Ok(Some(_)) => result.image_paths.push(path),
+            Ok(None) => {}
+            Err(error) => result.errors.push(ScanError { path, error }),
+        }
+    }
+}
+
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const PNG: &[u8] = include_bytes!("../fixtures/scan/pixel.png");
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "scan-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn finds_images_by_their_signature() {
+        let dir = temp_dir("signature");
+        fs::write(dir.join("b.png"), PNG).unwrap();
+        // A PNG by any other name is still a PNG.
+        fs::write(dir.join("a.jpg"), PNG).unwrap();
+        fs::write(dir.join("fake.png"), b"not an image").unwrap();
+        fs::write(dir.join("empty.jpg"), b"").unwrap();
+        fs::write(dir.join(".hidden.png"), PNG).unwrap();
+
+        let result = scan_dir(&dir, ScanOptions::default());
+        assert_eq!(result.image_paths, [dir.join("a.jpg"), dir.join("b.png")]);
+        assert!(result.errors.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn enters_subfolders_only_when_recursive() {
+        let dir = temp_dir("recursive");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::create_dir_all(dir.join(".hidden")).unwrap();
+        fs::write(dir.join("sub/a.png"), PNG).unwrap();
+        fs::write(dir.join(".hidden/b.png"), PNG).unwrap();
+
+        let result = scan_dir(&dir, ScanOptions::default());
+        assert!(result.image_paths.is_empty());
+        let result = scan_dir(&dir, ScanOptions { recursive: true });
+        assert_eq!(result.image_paths, [dir.join("sub/a.png")]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn enters_each_folder_once() {
+        let dir = temp_dir("loop");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/a.png"), PNG).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("sub/loop")).unwrap();
+
+        let result = scan_dir(&dir, ScanOptions { recursive: true });
+        assert_eq!(result.image_paths, [dir.join("sub/a.png")]);
+        assert!(result.errors.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reads_the_ratings_of_images_with_a_sidecar() {
+        let dir = temp_dir("ratings");
+        fs::write(dir.join("a.png"), PNG).unwrap();
+        fs::write(dir.join("b.png"), PNG).unwrap();
+        let rating = Rating {
+            stars: 4,
+            ..Rating::default()
+        };
+        rating.save(&dir.join("a.png")).unwrap();
+
+        let result = scan_dir(&dir, ScanOptions::default());
+        assert_eq!(result.ratings.get(&dir.join("a.png")), rating);
+        assert_eq!(result.ratings.get(&dir.join("b.png")), Rating::default());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
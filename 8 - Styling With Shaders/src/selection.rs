@@ -0,0 +1,168 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// The set of selected images. Images are tracked by path rather than by
+/// index, so the selection stays valid when the images are reordered or
+/// laid out differently.
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    paths: HashSet<PathBuf>,
+    anchor: Option<PathBuf>,
+}
+
+impl Selection {
+    pub fn contains(&self, path: &Path) -> bool {
+        self.paths.contains(path)
+    }
+
+    pub fn paths(&self) -> &HashSet<PathBuf> {
+        &self.paths
+    }
+
+    pub fn clear(&mut self) {
+        self.paths.clear();
+        self.anchor = None;
+    }
+
+    pub fn select_only(&mut self, path: &Path) {
+        self.paths.clear();
+        self.paths.insert(path.to_path_buf());
+        self.anchor = Some(path.to_path_buf());
+    }
+
+    pub fn toggle(&mut self, path: &Path) {
+        if !self.paths.remove(path) {
+            self.paths.insert(path.to_path_buf());
+        }
+        self.anchor = Some(path.to_path_buf());
+    }
+
+    pub fn set_anchor(&mut self, path: &Path) {
+        self.anchor = Some(path.to_path_buf());
+    }
+
+    pub fn select_all(&mut self, image_paths: &[PathBuf]) {
+        self.paths.extend(image_paths.iter().cloned());
+    }
+
+    /// Selects the images between the anchor, which is the image last
+    /// clicked without Shift held, and `image_idx`, inclusive. The range
+    /// replaces the current selection unless `extend` is set. Without an
+    /// anchor, the range starts and ends at `image_idx`.
+    pub fn select_range(
+        &mut self,
+        image_paths: &[PathBuf],
+        image_idx: usize,
+        extend: bool,
+    ) {
+        let anchor_idx = self
+            .anchor
+            .as_ref()
+            .and_then(|anchor| image_paths.iter().position(|p| p == anchor))
+            .unwrap_or(image_idx);
+        if !extend {
+            self.paths.clear();
+        }
+        let range = anchor_idx.min(image_idx)..=anchor_idx.max(image_idx);
+        self.paths.extend(image_paths[range].iter().cloned());
+        if self.anchor.is_none() {
+            self.anchor = image_paths.get(image_idx).cloned();
+        }
+    }
+
+    /// Replaces the selection with `paths`, keeping the anchor.
+    pub fn set(&mut self, paths: HashSet<PathBuf>) {
+        self.paths = paths;
+    }
+
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        if self.paths.remove(from) {
+            self.paths.insert(to.to_path_buf());
+        }
+        if self.anchor.as_deref() == Some(from) {
+            self.anchor = Some(to.to_path_buf());
+        }
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&Path) -> bool) {
+        self.paths.retain(|path| f(path));
+        if self.anchor.as_deref().is_some_and(|anchor| !f(anchor)) {
+            self.anchor = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(PathBuf::from).collect()
+    }
+
+    fn selected(selection: &Selection, image_paths: &[PathBuf]) -> Vec<usize> {
+        (0..image_paths.len())
+            .filter(|&image_idx| selection.contains(&image_paths[image_idx]))
+            .collect()
+    }
+
+    #[test]
+    fn selects_ranges_from_the_anchor() {
+        let image_paths = paths(&["a", "b", "c", "d", "e"]);
+        let mut selection = Selection::default();
+        selection.select_only(&image_paths[1]);
+        selection.select_range(&image_paths, 3, false);
+        assert_eq!(selected(&selection, &image_paths), [1, 2, 3]);
+
+        // The anchor stays put, so the range can be flipped around it.
+        selection.select_range(&image_paths, 0, false);
+        assert_eq!(selected(&selection, &image_paths), [0, 1]);
+
+        selection.toggle(&image_paths[4]);
+        selection.select_range(&image_paths, 3, true);
+        assert_eq!(selected(&selection, &image_paths), [0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn starts_a_range_without_an_anchor_at_the_image() {
+        let image_paths = paths(&["a", "b", "c"]);
+        let mut selection = Selection::default();
+        selection.select_range(&image_paths, 1, false);
+        assert_eq!(selected(&selection, &image_paths), [1]);
+        selection.select_range(&image_paths, 2, false);
+        assert_eq!(selected(&selection, &image_paths), [1, 2]);
+    }
+
+    #[test]
+    fn toggles_images() {
+        let image_paths = paths(&["a", "b"]);
+        let mut selection = Selection::default();
+        selection.toggle(&image_paths[0]);
+        selection.toggle(&image_paths[1]);
+        selection.toggle(&image_paths[0]);
+        assert_eq!(selected(&selection, &image_paths), [1]);
+    }
+
+    #[test]
+    fn follows_renamed_and_removed_images() {
+        let image_paths = paths(&["a", "b", "c"]);
+        let mut selection = Selection::default();
+        selection.select_only(&image_paths[0]);
+        selection.toggle(&image_paths[1]);
+        selection.rename(Path::new("b"), Path::new("z"));
+        assert!(selection.contains(Path::new("z")));
+        assert!(!selection.contains(Path::new("b")));
+
+        // The anchor went along with the rename.
+        let image_paths = paths(&["a", "z", "c"]);
+        selection.select_range(&image_paths, 2, false);
+        assert_eq!(selected(&selection, &image_paths), [1, 2]);
+
+        selection.retain(|path| path != Path::new("z"));
+        assert_eq!(selected(&selection, &image_paths), [2]);
+        selection.select_range(&image_paths, 0, false);
+        assert_eq!(selected(&selection, &image_paths), [0]);
+    }
+}
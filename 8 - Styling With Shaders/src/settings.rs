@@ -0,0 +1,120 @@
+use crate::{
+    config::{self, ConfigError, Table, Value},
+    grid_layout::GridLayout,
+    sort::{Grouping, SortKey, SortOptions, SortOrder},
+    zoom::ZoomMode,
+};
+use std::{io, path::PathBuf};
+
+const DEFAULT_THUMBNAIL_SIZE: f64 = 256.0;
+
+/// Preferences changed from within the app, as opposed to the `Config`,
+/// which is only ever read. They are saved whenever they change.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub thumbnail_size: f64,
+    pub grid_layout: GridLayout,
+    pub sort: SortOptions,
+    pub zoom_mode: ZoomMode,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            thumbnail_size: DEFAULT_THUMBNAIL_SIZE,
+            grid_layout: GridLayout::default(),
+            sort: SortOptions::default(),
+            zoom_mode: ZoomMode::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut settings = Self::default();
+        let Some(path) = file_path() else {
+            return Ok(settings);
+        };
+        if !path.is_file() {
+            return Ok(settings);
+        }
+
+        let table = config::read_table(&path)?;
+        if let Some(thumbnail_size) =
+            table.get("thumbnail_size").and_then(config::as_number)
+        {
+            settings.thumbnail_size = thumbnail_size;
+        }
+        if let Some(grid_layout) = table
+            .get("grid_layout")
+            .and_then(Value::as_str)
+            .and_then(GridLayout::from_name)
+        {
+            settings.grid_layout = grid_layout;
+        }
+        if let Some(key) = table
+            .get("sort_key")
+            .and_then(Value::as_str)
+            .and_then(SortKey::from_name)
+        {
+            settings.sort.key = key;
+        }
+        if let Some(order) = table
+            .get("sort_order")
+            .and_then(Value::as_str)
+            .and_then(SortOrder::from_name)
+        {
+            settings.sort.order = order;
+        }
+        if let Some(grouping) = table
+            .get("grouping")
+            .and_then(Value::as_str)
+            .and_then(Grouping::from_name)
+        {
+            settings.sort.grouping = grouping;
+        }
+        if let Some(zoom_mode) = table
+            .get("zoom_mode")
+            .and_then(Value::as_str)
+            .and_then(ZoomMode::from_name)
+        {
+            settings.zoom_mode = zoom_mode;
+        }
+        Ok(settings)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = file_path() else {
+            return Ok(());
+        };
+
+        let mut table = Table::new();
+        table
+            .insert("thumbnail_size".into(), Value::Float(self.thumbnail_size));
+        table.insert(
+            "grid_layout".into(),
+            Value::String(self.grid_layout.name().into()),
+        );
+        table.insert(
+            "sort_key".into(),
+            Value::String(self.sort.key.name().into()),
+        );
+        table.insert(
+            "sort_order".into(),
+            Value::String(self.sort.order.name().into()),
+        );
+        table.insert(
+            "grouping".into(),
+            Value::String(self.sort.grouping.name().into()),
+        );
+        table.insert(
+            "zoom_mode".into(),
+            Value::String(self.zoom_mode.name().into()),
+        );
+        config::write_table(&path, &table)
+    }
+}
+
+fn file_path() -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join("settings.toml"))
+}
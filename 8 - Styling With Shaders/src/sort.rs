@@ -0,0 +1,434 @@
+use crate::{
+    image_info::{ImageInfo, ImageInfos},
+    rating::Ratings,
+};
+use std::{
+    cmp::Ordering,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortKey {
+    /// File names, with runs of digits compared by their numeric value, so
+    /// `IMG_9` comes before `IMG_10`.
+    #[default]
+    Name,
+    Modified,
+    Size,
+    DateTaken,
+    /// The number of pixels.
+    Dimensions,
+    /// Stars, with rejected images below unrated ones.
+    Rating,
+}
+
+impl SortKey {
+    pub const ALL: [Self; 6] = [
+        Self::Name,
+        Self::Modified,
+        Self::Size,
+        Self::DateTaken,
+        Self::Dimensions,
+        Self::Rating,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Modified => "modified",
+            Self::Size => "size",
+            Self::DateTaken => "date_taken",
+            Self::Dimensions => "dimensions",
+            Self::Rating => "rating",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Name => "Name",
+            Self::Modified => "Date Modified",
+            Self::Size => "File Size",
+            Self::DateTaken => "Date Taken",
+            Self::Dimensions => "Dimensions",
+            Self::Rating => "Rating",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|key| key.name() == name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+impl SortOrder {
+    pub const ALL: [Self; 2] = [Self::Ascending, Self::Descending];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ascending => "ascending",
+            Self::Descending => "descending",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Ascending => "Ascending",
+            Self::Descending => "Descending",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|order| order.name() == name)
+    }
+
+    fn apply(&self, ordering: Ordering) -> Ordering {
+        match self {
+            Self::Ascending => ordering,
+            Self::Descending => ordering.reverse(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Grouping {
+    #[default]
+    None,
+    /// By the day the photo was taken, or else the day it was last modified.
+    Day,
+    Folder,
+}
+
+impl Grouping {
+    pub const ALL: [Self; 3] = [Self::None, Self::Day, Self::Folder];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Day => "day",
+            Self::Folder => "folder",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::None => "No Groups",
+            Self::Day => "By Day",
+            Self::Folder => "By Folder",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|grouping| grouping.name() == name)
+    }
+
+    /// The group an image belongs to, or `None` if it can't be told yet, or
+    /// at all. Images without a group are gathered in a group of their own.
+    pub fn key(
+        &self,
+        path: &Path,
+        info: Option<&ImageInfo>,
+        image_dir: &Path,
+    ) -> Option<String> {
+        match self {
+            Self::None => None,
+            Self::Day => info?.day(),
+            Self::Folder => {
+                let dir = path.parent()?;
+                let dir = dir.strip_prefix(image_dir).unwrap_or(dir);
+                Some(dir.display().to_string())
+            }
+        }
+    }
+
+    /// The title shown above a group with the given key.
+    pub fn title(&self, key: Option<&str>, image_dir: &Path) -> String {
+        match (self, key) {
+            (Self::Folder, Some("")) => image_dir
+                .file_name()
+                .unwrap_or(image_dir.as_os_str())
+                .to_string_lossy()
+                .into_owned(),
+            (_, Some(key)) => key.to_string(),
+            (Self::Day, None) => "Unknown Date".to_string(),
+            (_, None) => "Other".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SortOptions {
+    pub key: SortKey,
+    pub order: SortOrder,
+    pub grouping: Grouping,
+}
+
+impl SortOptions {
+    /// Returns whether sorting depends on image infos, so the images need to
+    /// be sorted again once those arrive.
+    pub fn needs_infos(&self) -> bool {
+        !matches!(self.key, SortKey::Name | SortKey::Rating)
+            || self.grouping == Grouping::Day
+    }
+
+    /// Sorts `image_paths` by group first, then by key. Groups follow the
+    /// same order as the images within them. Images whose key is not known
+    /// come last, whatever the order, and ties are broken by name.
+    pub fn sort(
+        &self,
+        image_paths: &mut Vec<PathBuf>,
+        infos: &ImageInfos,
+        ratings: &Ratings,
+        image_dir: &Path,
+    ) {
+        let mut keyed_paths: Vec<(Option<String>, PathBuf)> = image_paths
+            .drain(..)
+            .map(|path| {
+                let info = infos.get(&path);
+                (self.grouping.key(&path, info, image_dir), path)
+            })
+            .collect();
+        keyed_paths.sort_by(|(group_a, a), (group_b, b)| {
+            cmp_known(group_a.as_ref(), group_b.as_ref(), self.order)
+                .then_with(|| self.cmp_images(a, b, infos, ratings))
+        });
+        image_paths.extend(keyed_paths.into_iter().map(|(_, path)| path));
+    }
+
+    fn cmp_images(
+        &self,
+        a: &Path,
+        b: &Path,
+        infos: &ImageInfos,
+        ratings: &Ratings,
+    ) -> Ordering {
+        let (info_a, info_b) = (infos.get(a), infos.get(b));
+        let by_name = || self.order.apply(cmp_file_names(a, b));
+        match self.key {
+            SortKey::Name => by_name(),
+            SortKey::Modified => cmp_known(
+                info_a.and_then(|info| info.modified),
+                info_b.and_then(|info| info.modified),
+                self.order,
+            )
+            .then_with(by_name),
+            SortKey::Size => cmp_known(
+                info_a.map(|info| info.len),
+                info_b.map(|info| info.len),
+                self.order,
+            )
+            .then_with(by_name),
+            SortKey::DateTaken => cmp_known(
+                info_a.and_then(ImageInfo::date_taken),
+                info_b.and_then(ImageInfo::date_taken),
+                self.order,
+            )
+            .then_with(by_name),
+            SortKey::Dimensions => cmp_known(
+                info_a.and_then(|info| info.size).map(|size| size.pixels()),
+                info_b.and_then(|info| info.size).map(|size| size.pixels()),
+                self.order,
+            )
+            .then_with(by_name),
+            SortKey::Rating => self
+                .order
+                .apply(ratings.get(a).value().cmp(&ratings.get(b).value()))
+                .then_with(by_name),
+        }
+    }
+}
+
+/// Compares two optional keys in the given order, with missing keys last.
+fn cmp_known<T: Ord>(a: Option<T>, b: Option<T>, order: SortOrder) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => order.apply(a.cmp(&b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn cmp_file_names(a: &Path, b: &Path) -> Ordering {
+    let name_a = a.file_name().unwrap_or_default().to_string_lossy();
+    let name_b = b.file_name().unwrap_or_default().to_string_lossy();
+    natural_cmp(&name_a, &name_b).then_with(|| a.cmp(b))
+}
+
+/// Compares strings case-insensitively, with runs of digits compared by
+/// their numeric value rather than digit by digit.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(char_a), Some(char_b))
+                if char_a.is_ascii_digit() && char_b.is_ascii_digit() =>
+            {
+                let digits_a = take_digits(&mut a);
+                let digits_b = take_digits(&mut b);
+                // Leading zeros don't change the value, but they do break
+                // ties, so `01` comes after `1`.
+                let value_a = digits_a.trim_start_matches('0');
+                let value_b = digits_b.trim_start_matches('0');
+                let ordering = value_a
+                    .len()
+                    .cmp(&value_b.len())
+                    .then_with(|| value_a.cmp(value_b))
+                    .then_with(|| digits_a.len().cmp(&digits_b.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(char_a), Some(char_b)) => {
+                let ordering = char_a.to_lowercase().cmp(char_b.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn take_digits(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(char) = chars.next_if(char::is_ascii_digit) {
+        digits.push(char);
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rating::{Flag, Rating};
+
+    fn sorted(
+        options: SortOptions,
+        names: &[&str],
+        ratings: &Ratings,
+    ) -> Vec<String> {
+        let mut image_paths = names.iter().map(PathBuf::from).collect();
+        let infos = ImageInfos::default();
+        options.sort(&mut image_paths, &infos, ratings, Path::new(""));
+        image_paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn compares_numbers_by_value() {
+        let mut names = [
+            "IMG_10.jpg",
+            "img_9.jpg",
+            "IMG_009.jpg",
+            "IMG_9a.jpg",
+            "IMG.jpg",
+            "IMG_100.jpg",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            [
+                "IMG.jpg",
+                "img_9.jpg",
+                // Leading zeros break the tie before the rest of the name.
+                "IMG_9a.jpg",
+                "IMG_009.jpg",
+                "IMG_10.jpg",
+                "IMG_100.jpg"
+            ]
+        );
+        assert_eq!(natural_cmp("a1b2", "A1B2"), Ordering::Equal);
+        assert_eq!(natural_cmp("a", "a1"), Ordering::Less);
+    }
+
+    #[test]
+    fn puts_missing_keys_last_in_either_order() {
+        for order in SortOrder::ALL {
+            assert_eq!(cmp_known(Some(1), None, order), Ordering::Less);
+            assert_eq!(cmp_known(None, Some(1), order), Ordering::Greater);
+            assert_eq!(cmp_known::<u32>(None, None, order), Ordering::Equal);
+        }
+        assert_eq!(
+            cmp_known(Some(1), Some(2), SortOrder::Ascending),
+            Ordering::Less
+        );
+        assert_eq!(
+            cmp_known(Some(1), Some(2), SortOrder::Descending),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn sorts_by_name_in_either_order() {
+        let names = ["b10.jpg", "a.jpg", "b9.jpg"];
+        let options = SortOptions::default();
+        assert_eq!(
+            sorted(options, &names, &Ratings::default()),
+            ["a.jpg", "b9.jpg", "b10.jpg"]
+        );
+        let options = SortOptions {
+            order: SortOrder::Descending,
+            ..options
+        };
+        assert_eq!(
+            sorted(options, &names, &Ratings::default()),
+            ["b10.jpg", "b9.jpg", "a.jpg"]
+        );
+    }
+
+    #[test]
+    fn sorts_rejected_images_below_unrated_ones() {
+        let mut ratings = Ratings::default();
+        ratings.set(
+            Path::new("a.jpg"),
+            Rating::default().with_flag(Some(Flag::Reject)),
+        );
+        ratings.set(Path::new("c.jpg"), Rating::default().toggled_stars(2));
+        let options = SortOptions {
+            key: SortKey::Rating,
+            ..SortOptions::default()
+        };
+        assert_eq!(
+            sorted(options, &["a.jpg", "b.jpg", "c.jpg", "d.jpg"], &ratings),
+            ["a.jpg", "b.jpg", "d.jpg", "c.jpg"]
+        );
+    }
+
+    #[test]
+    fn groups_by_folder() {
+        let options = SortOptions {
+            grouping: Grouping::Folder,
+            ..SortOptions::default()
+        };
+        assert_eq!(
+            sorted(
+                options,
+                &["b/1.jpg", "a/2.jpg", "b/0.jpg", "a/1.jpg"],
+                &Ratings::default()
+            ),
+            ["a/1.jpg", "a/2.jpg", "b/0.jpg", "b/1.jpg"]
+        );
+        assert_eq!(
+            Grouping::Folder.title(Some(""), Path::new("/photos")),
+            "photos"
+        );
+        assert_eq!(
+            Grouping::Day.title(None, Path::new("/photos")),
+            "Unknown Date"
+        );
+    }
+}
@@ -0,0 +1,491 @@
+use crate::{
+    config::{self, ConfigError, Table, Value},
+    thumbnail_cache::Fnv1a,
+};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// How much of the start and of the end of a file goes into its content
+/// hash. Hashing whole files would make tagging large batches slow, and
+/// photos that differ tend to differ in both their headers and their last
+/// scanlines.
+const HASHED_LEN: u64 = 64 * 1024;
+
+/// Identifies the content of a file, so it can be recognized after it was
+/// renamed or moved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct ContentId {
+    len: u64,
+    hash: u64,
+}
+
+impl ContentId {
+    fn read(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let mut hash = Fnv1a::default();
+        hash.write(&len.to_le_bytes());
+        let mut bytes = Vec::new();
+        (&mut file).take(HASHED_LEN).read_to_end(&mut bytes)?;
+        hash.write(&bytes);
+        if len > HASHED_LEN {
+            bytes.clear();
+            file.seek(SeekFrom::Start(len.saturating_sub(HASHED_LEN)))?;
+            file.read_to_end(&mut bytes)?;
+            hash.write(&bytes);
+        }
+        Ok(Self {
+            len,
+            hash: hash.finish(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    content_id: ContentId,
+    tags: Vec<String>,
+}
+
+/// Keywords attached to images, across all folders. Tags are looked up by
+/// path, and every tagged image also has the hash of its content recorded,
+/// so its tags can find it again after it was renamed or moved behind the
+/// app's back. The store is kept in `tags.toml` in the config directory.
+#[derive(Clone, Debug, Default)]
+pub struct TagStore {
+    entries: HashMap<PathBuf, Entry>,
+    /// Set when `tags.toml` couldn't be read nor set aside, so that saving
+    /// doesn't overwrite the tags in it.
+    is_read_only: bool,
+}
+
+impl TagStore {
+    pub fn load() -> Result<Self, ConfigError> {
+        let Some(path) = file_path() else {
+            return Ok(Self::default());
+        };
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        Ok(Self::from_table(&config::read_table(&path)?))
+    }
+
+    /// An empty store that refuses to save, for when `tags.toml` couldn't
+    /// be read nor set aside.
+    pub fn read_only() -> Self {
+        Self {
+            entries: HashMap::new(),
+            is_read_only: true,
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if self.is_read_only {
+            return Err(io::Error::other(
+                "tags.toml couldn't be read, so it is left as it is",
+            ));
+        }
+        let Some(path) = file_path() else {
+            return Ok(());
+        };
+        config::write_table(&path, &self.to_table())
+    }
+
+    /// Reads entries written by `to_table`, from an `[[images]]` array of
+    /// tables with a `path`, `len`, `hash` and `tags` each. Entries missing
+    /// any of those are skipped.
+    fn from_table(table: &Table) -> Self {
+        let mut entries = HashMap::new();
+        let images = table.get("images").and_then(Value::as_array);
+        for image in images.into_iter().flatten().filter_map(Value::as_table) {
+            let (Some(path), Some(len), Some(hash), Some(tags)) = (
+                image.get("path").and_then(Value::as_str),
+                image.get("len").and_then(Value::as_integer),
+                image
+                    .get("hash")
+                    .and_then(Value::as_str)
+                    .and_then(|hash| u64::from_str_radix(hash, 16).ok()),
+                image.get("tags").and_then(Value::as_array),
+            ) else {
+                continue;
+            };
+            let tags: Vec<String> = tags
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect();
+            if tags.is_empty() {
+                continue;
+            }
+            entries.insert(
+                PathBuf::from(path),
+                Entry {
+                    content_id: ContentId {
+                        len: len as u64,
+                        hash,
+                    },
+                    tags,
+                },
+            );
+        }
+        Self {
+            entries,
+            is_read_only: false,
+        }
+    }
+
+    fn to_table(&self) -> Table {
+        let mut paths: Vec<&PathBuf> = self.entries.keys().collect();
+        paths.sort();
+        let images = paths
+            .into_iter()
+            .map(|path| {
+                let entry = &self.entries[path];
+                let mut image = Table::new();
+                image.insert(
+                    "path".into(),
+                    Value::String(path.to_string_lossy().into_owned()),
+                );
+                image.insert(
+                    "len".into(),
+                    Value::Integer(entry.content_id.len as i64),
+                );
+                image.insert(
+                    "hash".into(),
+                    Value::String(format!("{:016x}", entry.content_id.hash)),
+                );
+                image.insert(
+                    "tags".into(),
+                    Value::Array(
+                        entry.tags.iter().cloned().map(Value::String).collect(),
+                    ),
+                );
+                Value::Table(image)
+            })
+            .collect();
+        let mut table = Table::new();
+        table.insert("images".into(), Value::Array(images));
+        table
+    }
+
+    pub fn tags(&self, path: &Path) -> &[String] {
+        self.entries
+            .get(path)
+            .map_or(&[], |entry| entry.tags.as_slice())
+    }
+
+    /// The tags that start with `prefix`, ignoring case, most used first.
+    /// Tags that only differ in case count as one, suggested as the first
+    /// of their spellings in sort order.
+    pub fn suggestions(&self, prefix: &str, max_count: usize) -> Vec<String> {
+        let prefix = fold_case(prefix);
+        let mut counts: HashMap<String, (&str, usize)> = HashMap::new();
+        for entry in self.entries.values() {
+            for tag in &entry.tags {
+                let folded_tag = fold_case(tag);
+                if folded_tag.starts_with(&prefix) {
+                    let (spelling, count) =
+                        counts.entry(folded_tag).or_insert((tag, 0));
+                    *spelling = (*spelling).min(tag.as_str());
+                    *count += 1;
+                }
+            }
+        }
+        let mut suggestions: Vec<(&str, usize)> =
+            counts.into_values().collect();
+        suggestions.sort_by(|(tag_a, count_a), (tag_b, count_b)| {
+            count_b.cmp(count_a).then_with(|| tag_a.cmp(tag_b))
+        });
+        suggestions
+            .into_iter()
+            .take(max_count)
+            .map(|(tag, _)| tag.to_string())
+            .collect()
+    }
+
+    /// Adds `tags` to every image in `paths`. Images that can't be read are
+    /// left untagged, and the first error is returned once the others were
+    /// tagged.
+    pub fn add_tags(
+        &mut self,
+        paths: &[PathBuf],
+        tags: &[String],
+    ) -> io::Result<()> {
+        let mut result = Ok(());
+        for path in paths {
+            if !self.entries.contains_key(path) {
+                match ContentId::read(path) {
+                    Ok(content_id) => {
+                        let entry = Entry {
+                            content_id,
+                            tags: Vec::new(),
+                        };
+                        self.entries.insert(path.clone(), entry);
+                    }
+                    Err(error) => {
+                        if result.is_ok() {
+                            result = Err(error);
+                        }
+                        continue;
+                    }
+                }
+            }
+            let Some(entry) = self.entries.get_mut(path) else {
+                continue;
+            };
+            for tag in tags {
+                if !entry.tags.iter().any(|other| is_same_tag(other, tag)) {
+                    entry.tags.push(tag.clone());
+                }
+            }
+        }
+        result
+    }
+
+    pub fn remove_tags(&mut self, paths: &[PathBuf], tags: &[String]) {
+        for path in paths {
+            if let Some(entry) = self.entries.get_mut(path) {
+                entry.tags.retain(|tag| {
+                    !tags.iter().any(|other| is_same_tag(other, tag))
+                });
+                if entry.tags.is_empty() {
+                    self.entries.remove(path);
+                }
+            }
+        }
+    }
+
+    /// Moves the tags of an image that was renamed. Returns whether it had
+    /// any.
+    pub fn rename(&mut self, from: &Path, to: &Path) -> bool {
+        let Some(entry) = self.entries.remove(from) else {
+            return false;
+        };
+        self.entries.insert(to.to_path_buf(), entry);
+        true
+    }
+
+    /// Gives tags whose image is gone to an untagged image in `image_paths`
+    /// with the same content, which is most likely the same image under
+    /// another name. Only images as long as a missing one are hashed.
+    /// Returns whether any tags were moved.
+    pub fn reconcile(&mut self, image_paths: &[PathBuf]) -> bool {
+        let mut missing_paths: HashMap<ContentId, PathBuf> = self
+            .entries
+            .iter()
+            .filter(|(path, _)| !path.exists())
+            .map(|(path, entry)| (entry.content_id, path.clone()))
+            .collect();
+        if missing_paths.is_empty() {
+            return false;
+        }
+
+        let mut is_changed = false;
+        for path in image_paths {
+            if self.entries.contains_key(path) {
+                continue;
+            }
+            let Ok(metadata) = fs::metadata(path) else {
+                continue;
+            };
+            if !missing_paths.keys().any(|id| id.len == metadata.len()) {
+                continue;
+            }
+            if let Ok(content_id) = ContentId::read(path)
+                && let Some(missing_path) = missing_paths.remove(&content_id)
+            {
+                is_changed |= self.rename(&missing_path, path);
+            }
+        }
+        is_changed
+    }
+
+    /// The images in a collection that still exist, in no particular order.
+    pub fn query(&self, collection: &Collection) -> Vec<PathBuf> {
+        self.entries
+            .iter()
+            .filter(|(path, entry)| {
+                collection.matches(&entry.tags) && path.is_file()
+            })
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+}
+
+/// A virtual folder of the images whose tags match a query. The query is a
+/// comma separated list of tags that images must have, where tags prefixed
+/// with `-` must not be there, as in `beach, 2025, -family`. Matching is
+/// case insensitive.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Collection {
+    included_tags: Vec<String>,
+    excluded_tags: Vec<String>,
+}
+
+impl Collection {
+    pub fn parse(query: &str) -> Self {
+        let mut collection = Self::default();
+        for tag in parse_tags(query) {
+            match tag.strip_prefix('-') {
+                Some(tag) => {
+                    let tag = tag.trim();
+                    if !tag.is_empty() {
+                        collection.excluded_tags.push(fold_case(tag));
+                    }
+                }
+                None => collection.included_tags.push(fold_case(&tag)),
+            }
+        }
+        collection
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.included_tags.is_empty() && self.excluded_tags.is_empty()
+    }
+
+    fn matches(&self, tags: &[String]) -> bool {
+        let has_tag =
+            |wanted: &String| tags.iter().any(|tag| fold_case(tag) == *wanted);
+        self.included_tags.iter().all(has_tag)
+            && !self.excluded_tags.iter().any(has_tag)
+    }
+}
+
+/// Tags are told apart ignoring case, everywhere: when they are added or
+/// removed, suggested, and matched by collections.
+fn fold_case(tag: &str) -> String {
+    tag.to_lowercase()
+}
+
+fn is_same_tag(a: &str, b: &str) -> bool {
+    fold_case(a) == fold_case(b)
+}
+
+/// Splits a comma separated list of tags, dropping empty ones.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Renames an unreadable `tags.toml` to `tags.toml.bak`, or to
+/// `tags.toml.1.bak` and so on if that is taken, so that saving a new store
+/// doesn't destroy the tags in it. Returns where it went.
+pub fn set_aside_unreadable() -> io::Result<PathBuf> {
+    let path = file_path().ok_or(io::ErrorKind::NotFound)?;
+    let backup_path = (0..)
+        .map(|idx| match idx {
+            0 => path.with_extension("toml.bak"),
+            idx => path.with_extension(format!("toml.{}.bak", idx)),
+        })
+        .find(|backup_path| !backup_path.exists())
+        .unwrap();
+    fs::rename(&path, &backup_path)?;
+    Ok(backup_path)
+}
+
+fn file_path() -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join("tags.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(hash: u64, tags: &[&str]) -> Entry {
+        Entry {
+            content_id: ContentId { len: 1, hash },
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let mut store = TagStore::default();
+        store
+            .entries
+            .insert("/photos/a.jpg".into(), entry(u64::MAX, &["beach"]));
+        store.entries.insert(
+            "/photos/b \"c\".jpg".into(),
+            entry(7, &["beach", "Family"]),
+        );
+        let source = store.to_table().to_string();
+        assert!(source.starts_with("[[images]]\n"));
+        let table: Table = source.parse().unwrap();
+        assert_eq!(TagStore::from_table(&table).entries, store.entries);
+    }
+
+    #[test]
+    fn matches_collections() {
+        let tags = ["Beach".to_string(), "2025".to_string()];
+        assert!(Collection::parse("beach").matches(&tags));
+        assert!(Collection::parse(" beach , 2025 ").matches(&tags));
+        assert!(!Collection::parse("beach, family").matches(&tags));
+        assert!(!Collection::parse("beach, -2025").matches(&tags));
+        assert!(Collection::parse("-family").matches(&tags));
+        assert!(Collection::parse(" , -").is_empty());
+    }
+
+    #[test]
+    fn suggests_most_used_tags_first() {
+        let mut store = TagStore::default();
+        store
+            .entries
+            .insert("a.jpg".into(), entry(1, &["beach", "bees"]));
+        store.entries.insert("b.jpg".into(), entry(2, &["bees"]));
+        store.entries.insert("c.jpg".into(), entry(3, &["city"]));
+        assert_eq!(store.suggestions("B", 5), ["bees", "beach"]);
+        assert_eq!(store.suggestions("", 2), ["bees", "beach"]);
+    }
+
+    #[test]
+    fn folds_case_the_same_everywhere() {
+        let mut store = TagStore::default();
+        store.entries.insert("a.jpg".into(), entry(1, &["Beach"]));
+        store.entries.insert("b.jpg".into(), entry(2, &["beach"]));
+        store.entries.insert("c.jpg".into(), entry(3, &["city"]));
+        assert_eq!(store.suggestions("", 5), ["Beach", "city"]);
+
+        store.remove_tags(&["a.jpg".into()], &["ÉTÉ".into()]);
+        store.entries.insert("d.jpg".into(), entry(4, &["été"]));
+        store.remove_tags(&["d.jpg".into()], &["ÉTÉ".into()]);
+        assert!(store.tags(Path::new("d.jpg")).is_empty());
+        assert!(Collection::parse("ÉTÉ").matches(&["été".to_string()]));
+    }
+
+    #[test]
+    fn read_only_store_refuses_to_save() {
+        assert!(TagStore::read_only().save().is_err());
+    }
+
+    #[test]
+    fn finds_renamed_images_by_content() {
+        let dir = std::env::temp_dir()
+            .join(format!("tags-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let old_path = dir.join("old.jpg");
+        let new_path = dir.join("new.jpg");
+        let other_path = dir.join("other.jpg");
+        fs::write(&old_path, b"one image").unwrap();
+        fs::write(&other_path, b"another image").unwrap();
+
+        let mut store = TagStore::default();
+        store
+            .add_tags(std::slice::from_ref(&old_path), &["beach".into()])
+            .unwrap();
+        fs::rename(&old_path, &new_path).unwrap();
+        let image_paths = [new_path.clone(), other_path.clone()];
+        assert!(store.reconcile(&image_paths));
+        assert_eq!(store.tags(&new_path), ["beach"]);
+        assert!(store.tags(&old_path).is_empty());
+        assert!(store.tags(&other_path).is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+use crate::image_info::ImageSize;
+use makepad_widgets::*;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+struct Entry<T> {
+    texture: T,
+    size: ImageSize,
+    bytes: u64,
+    last_used: u64,
+}
+
+/// Full-size slideshow textures, capped by their total size in bytes rather
+/// than by count, since a single large photo can outweigh dozens of small
+/// ones. The least recently used textures are evicted first. Anything else
+/// can be cached in place of textures, which are only made with a `Cx`.
+pub struct TextureCache<T = Texture> {
+    max_bytes: u64,
+    total_bytes: u64,
+    entries: HashMap<PathBuf, Entry<T>>,
+    clock: u64,
+}
+
+impl<T: Clone> TextureCache<T> {
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            total_bytes: 0,
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    pub fn get(&mut self, path: &Path) -> Option<(T, ImageSize)> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(path)?;
+        entry.last_used = self.clock;
+        Some((entry.texture.clone(), entry.size))
+    }
+
+    /// Inserts a texture, then evicts the least recently used ones until the
+    /// cache fits in its budget again. The texture at `keep` is never
+    /// evicted, so the image on screen stays cached even if it alone exceeds
+    /// the budget.
+    pub fn insert(
+        &mut self,
+        path: PathBuf,
+        texture: T,
+        size: ImageSize,
+        keep: Option<&Path>,
+    ) {
+        self.remove(&path);
+        let bytes = (size.pixels() * 4) as u64;
+        self.clock += 1;
+        self.total_bytes += bytes;
+        self.entries.insert(
+            path,
+            Entry {
+                texture,
+                size,
+                bytes,
+                last_used: self.clock,
+            },
+        );
+
+        while self.total_bytes > self.max_bytes {
+            let lru_path = self
+                .entries
+                .iter()
+                .filter(|(path, _)| Some(path.as_path()) != keep)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(path, _)| path.clone());
+            match lru_path {
+                Some(lru_path) => self.remove(&lru_path),
+                None => break,
+            }
+        }
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        if let Some(entry) = self.entries.remove(path) {
+            self.total_bytes -= entry.bytes;
+        }
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&Path) -> bool) {
+        let mut removed_bytes = 0;
+        self.entries.retain(|path, entry| {
+            let keep = f(path);
+            if !keep {
+                removed_bytes += entry.bytes;
+            }
+            keep
+        });
+        self.total_bytes -= removed_bytes;
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.total_bytes = 0;
+    }
+}
+
+impl<T: Clone> Default for TextureCache<T> {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 10 by 10 image, which takes up 400 bytes.
+    const SIZE: ImageSize = ImageSize {
+        width: 10,
+        height: 10,
+    };
+
+    fn insert(cache: &mut TextureCache<u32>, name: &str, keep: Option<&str>) {
+        cache.insert(name.into(), 0, SIZE, keep.map(Path::new));
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let mut cache = TextureCache::new(800);
+        insert(&mut cache, "a", None);
+        insert(&mut cache, "b", None);
+        assert!(cache.get(Path::new("a")).is_some());
+        insert(&mut cache, "c", None);
+
+        assert!(cache.contains(Path::new("a")));
+        assert!(!cache.contains(Path::new("b")));
+        assert!(cache.contains(Path::new("c")));
+        assert_eq!(cache.total_bytes, 800);
+    }
+
+    #[test]
+    fn keeps_the_image_on_screen() {
+        let mut cache = TextureCache::new(300);
+        insert(&mut cache, "a", Some("a"));
+        assert!(cache.contains(Path::new("a")));
+        insert(&mut cache, "b", Some("a"));
+        assert!(cache.contains(Path::new("a")));
+        assert!(!cache.contains(Path::new("b")));
+    }
+
+    #[test]
+    fn counts_replaced_and_removed_textures() {
+        let mut cache = TextureCache::new(1000);
+        insert(&mut cache, "a", None);
+        insert(&mut cache, "a", None);
+        insert(&mut cache, "b", None);
+        assert_eq!(cache.total_bytes, 800);
+        cache.retain(|path| path != Path::new("a"));
+        assert_eq!(cache.total_bytes, 400);
+        cache.remove(Path::new("b"));
+        assert_eq!(cache.total_bytes, 0);
+    }
+}
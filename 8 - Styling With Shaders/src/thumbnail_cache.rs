@@ -0,0 +1,433 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Bumped whenever thumbnails are made differently, so stale ones are made
+// again: version 2 turns images upright.
+const MAGIC: &[u8; 8] = b"IVTHUMB2";
+const HEADER_LEN: usize = MAGIC.len() + 8;
+const EXTENSION: &str = "thumb";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Thumbnail {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Thumbnail {
+    /// Shrinks an image so that it fits within a `max_size` square, averaging
+    /// each block of source pixels into one thumbnail pixel. Images that
+    /// already fit are copied as is.
+    pub fn downsample(
+        width: usize,
+        height: usize,
+        pixels: &[u32],
+        max_size: usize,
+    ) -> Self {
+        let max_size = max_size.max(1);
+        if width <= max_size && height <= max_size {
+            return Self {
+                width,
+                height,
+                pixels: pixels.to_vec(),
+            };
+        }
+
+        let scale = width.max(height) as f64 / max_size as f64;
+        let thumbnail_width = ((width as f64 / scale).round() as usize).max(1);
+        let thumbnail_height =
+            ((height as f64 / scale).round() as usize).max(1);
+
+        let mut thumbnail_pixels =
+            Vec::with_capacity(thumbnail_width * thumbnail_height);
+        for y in 0..thumbnail_height {
+            let y0 = y * height / thumbnail_height;
+            let y1 = ((y + 1) * height / thumbnail_height).max(y0 + 1);
+            for x in 0..thumbnail_width {
+                let x0 = x * width / thumbnail_width;
+                let x1 = ((x + 1) * width / thumbnail_width).max(x0 + 1);
+
+                let mut sums = [0u64; 4];
+                for row in pixels[y0 * width..y1 * width].chunks_exact(width) {
+                    for pixel in &row[x0..x1] {
+                        for (channel, sum) in sums.iter_mut().enumerate() {
+                            *sum += u64::from((pixel >> (channel * 8)) & 0xff);
+                        }
+                    }
+                }
+
+                let count = ((x1 - x0) * (y1 - y0)) as u64;
+                let pixel = sums
+                    .iter()
+                    .enumerate()
+                    .map(|(channel, sum)| {
+                        (((sum + count / 2) / count) as u32) << (channel * 8)
+                    })
+                    .fold(0, |pixel, channel| pixel | channel);
+                thumbnail_pixels.push(pixel);
+            }
+        }
+
+        Self {
+            width: thumbnail_width,
+            height: thumbnail_height,
+            pixels: thumbnail_pixels,
+        }
+    }
+
+    /// Reads a thumbnail written by `write`, bumping the file's mtime so
+    /// that the hit is remembered across restarts.
+    pub fn read(path: &Path) -> Option<Self> {
+        let thumbnail = Self::from_bytes(&fs::read(path).ok()?)?;
+        if let Ok(file) = File::options().write(true).open(path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(thumbnail)
+    }
+
+    /// Writes the thumbnail to `path`, returning its length in bytes.
+    pub fn write(&self, path: &Path) -> io::Result<u64> {
+        // Write to a temporary file first so a crash mid-write never leaves
+        // a truncated thumbnail behind under the real name.
+        let bytes = self.to_bytes();
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, &bytes)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(bytes.len() as u64)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.pixels.len() * 4);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&(self.width as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.height as u32).to_le_bytes());
+        for pixel in &self.pixels {
+            bytes.extend_from_slice(&pixel.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let header = bytes.get(..HEADER_LEN)?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return None;
+        }
+        let width = read_u32(&header[MAGIC.len()..])? as usize;
+        let height = read_u32(&header[MAGIC.len() + 4..])? as usize;
+
+        let body = &bytes[HEADER_LEN..];
+        if body.len() != width.checked_mul(height)?.checked_mul(4)? {
+            return None;
+        }
+        let pixels = body.chunks_exact(4).filter_map(read_u32).collect();
+        Some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?))
+}
+
+/// Identifies a thumbnail of a specific file at a specific size. The key
+/// includes the file's modification time and length, so editing the original
+/// makes its old thumbnails unreachable instead of stale.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ThumbnailKey(u64);
+
+impl ThumbnailKey {
+    pub fn new(path: &Path, size: usize) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos());
+
+        let mut hash = Fnv1a::default();
+        hash.write(path.as_os_str().as_encoded_bytes());
+        hash.write(&(size as u64).to_le_bytes());
+        hash.write(&mtime.to_le_bytes());
+        hash.write(&metadata.len().to_le_bytes());
+        Ok(Self(hash.finish()))
+    }
+
+    fn file_name(&self) -> String {
+        format!("{:016x}.{}", self.0, EXTENSION)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv1a {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+struct Entry {
+    len: u64,
+    last_used: u64,
+}
+
+/// An on-disk thumbnail store capped at `max_bytes`. When the cap is
+/// exceeded the least recently used thumbnails are deleted first. Recency
+/// survives restarts because hits also bump the cache file's mtime.
+pub struct ThumbnailCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    total_bytes: u64,
+    entries: HashMap<String, Entry>,
+    lru: BTreeMap<u64, String>,
+    clock: u64,
+}
+
+impl ThumbnailCache {
+    pub fn open(dir: PathBuf, max_bytes: u64) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+
+        let mut files = Vec::new();
+        for entry in dir.read_dir()? {
+            let entry = entry?;
+            let path = entry.path();
+            if path
+                .extension()
+                .is_none_or(|extension| extension != EXTENSION)
+            {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            let mtime = metadata.modified().unwrap_or(UNIX_EPOCH);
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            files.push((mtime, file_name, metadata.len()));
+        }
+        files.sort();
+
+        let mut cache = Self {
+            dir,
+            max_bytes,
+            total_bytes: 0,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            clock: 0,
+        };
+        for (_, file_name, len) in files {
+            cache.insert_entry(file_name, len);
+        }
+        cache.evict();
+        Ok(cache)
+    }
+
+    /// Reads the thumbnail of `key` if the cache has it.
+    pub fn get(&mut self, key: ThumbnailKey) -> Option<Thumbnail> {
+        let path = self.locate(key)?;
+        match Thumbnail::read(&path) {
+            Some(thumbnail) => {
+                self.mark_used(key);
+                Some(thumbnail)
+            }
+            None => {
+                self.forget(key);
+                None
+            }
+        }
+    }
+
+    pub fn put(
+        &mut self,
+        key: ThumbnailKey,
+        thumbnail: &Thumbnail,
+    ) -> io::Result<()> {
+        let len = thumbnail.write(&self.path(key))?;
+        self.insert(key, len);
+        Ok(())
+    }
+
+    /// Where the thumbnail of `key` is stored, if the cache has it.
+    ///
+    /// `locate`, `mark_used`, `forget` and `insert` only touch the index, so
+    /// a cache that is shared between threads can be locked just for them
+    /// and the thumbnails read and written in between without the lock.
+    pub fn locate(&self, key: ThumbnailKey) -> Option<PathBuf> {
+        let file_name = key.file_name();
+        self.entries
+            .contains_key(&file_name)
+            .then(|| self.dir.join(file_name))
+    }
+
+    /// Where the thumbnail of `key` is to be written before it is inserted.
+    pub fn path(&self, key: ThumbnailKey) -> PathBuf {
+        self.dir.join(key.file_name())
+    }
+
+    /// Records a hit, making `key` the most recently used thumbnail.
+    pub fn mark_used(&mut self, key: ThumbnailKey) {
+        let file_name = key.file_name();
+        let Some(entry) = self.entries.get_mut(&file_name) else {
+            return;
+        };
+        self.lru.remove(&entry.last_used);
+        self.clock += 1;
+        entry.last_used = self.clock;
+        self.lru.insert(self.clock, file_name);
+    }
+
+    /// Drops a thumbnail that turned out to be unreadable.
+    pub fn forget(&mut self, key: ThumbnailKey) {
+        let file_name = key.file_name();
+        self.remove_entry(&file_name);
+        let _ = fs::remove_file(self.dir.join(file_name));
+    }
+
+    /// Adds a thumbnail of `len` bytes that was written to `path(key)`,
+    /// evicting others if that takes the cache over its cap.
+    pub fn insert(&mut self, key: ThumbnailKey, len: u64) {
+        let file_name = key.file_name();
+        self.remove_entry(&file_name);
+        self.insert_entry(file_name, len);
+        self.evict();
+    }
+
+    fn insert_entry(&mut self, file_name: String, len: u64) {
+        self.clock += 1;
+        self.total_bytes += len;
+        self.lru.insert(self.clock, file_name.clone());
+        self.entries.insert(
+            file_name,
+            Entry {
+                len,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    fn remove_entry(&mut self, file_name: &str) {
+        if let Some(entry) = self.entries.remove(file_name) {
+            self.total_bytes -= entry.len;
+            self.lru.remove(&entry.last_used);
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.total_bytes > self.max_bytes {
+            let Some((_, file_name)) = self.lru.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&file_name) {
+                self.total_bytes -= entry.len;
+            }
+            let _ = fs::remove_file(self.dir.join(&file_name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "thumbnail-cache-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn thumbnail(pixel: u32) -> Thumbnail {
+        Thumbnail {
+            width: 1,
+            height: 1,
+            pixels: vec![pixel],
+        }
+    }
+
+    #[test]
+    fn averages_blocks_of_pixels() {
+        let pixels = [0x00000000, 0x04080c10, 0xff000000, 0xff000002];
+        let thumbnail = Thumbnail::downsample(4, 1, &pixels, 2);
+        assert_eq!(thumbnail.width, 2);
+        assert_eq!(thumbnail.height, 1);
+        assert_eq!(thumbnail.pixels, [0x02040608, 0xff000001]);
+        assert_eq!(Thumbnail::downsample(4, 1, &pixels, 4).pixels, pixels);
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let thumbnail = Thumbnail {
+            width: 2,
+            height: 1,
+            pixels: vec![0x11223344, 0x55667788],
+        };
+        let bytes = thumbnail.to_bytes();
+        assert_eq!(Thumbnail::from_bytes(&bytes), Some(thumbnail));
+        assert_eq!(Thumbnail::from_bytes(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(Thumbnail::from_bytes(b"IVTHUMB1"), None);
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let dir = temp_dir("lru");
+        let len = thumbnail(0).to_bytes().len() as u64;
+        let mut cache = ThumbnailCache::open(dir.clone(), 2 * len).unwrap();
+        cache.put(ThumbnailKey(1), &thumbnail(1)).unwrap();
+        cache.put(ThumbnailKey(2), &thumbnail(2)).unwrap();
+        assert_eq!(cache.get(ThumbnailKey(1)), Some(thumbnail(1)));
+        cache.put(ThumbnailKey(3), &thumbnail(3)).unwrap();
+
+        assert_eq!(cache.get(ThumbnailKey(2)), None);
+        assert!(!dir.join(ThumbnailKey(2).file_name()).exists());
+        assert_eq!(cache.get(ThumbnailKey(1)), Some(thumbnail(1)));
+        assert_eq!(cache.get(ThumbnailKey(3)), Some(thumbnail(3)));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn drops_corrupt_entries() {
+        let dir = temp_dir("corrupt");
+        let mut cache = ThumbnailCache::open(dir.clone(), 1024).unwrap();
+        cache.put(ThumbnailKey(1), &thumbnail(1)).unwrap();
+        let path = dir.join(ThumbnailKey(1).file_name());
+        fs::write(&path, b"IVTHUMB2 truncated").unwrap();
+
+        assert_eq!(cache.get(ThumbnailKey(1)), None);
+        assert!(!path.exists());
+        assert_eq!(cache.total_bytes, 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_entries_across_restarts() {
+        let dir = temp_dir("reopen");
+        let mut cache = ThumbnailCache::open(dir.clone(), 1024).unwrap();
+        cache.put(ThumbnailKey(1), &thumbnail(1)).unwrap();
+        fs::write(dir.join("unrelated.txt"), b"").unwrap();
+
+        let mut cache = ThumbnailCache::open(dir.clone(), 1024).unwrap();
+        assert_eq!(cache.get(ThumbnailKey(1)), Some(thumbnail(1)));
+        assert_eq!(cache.entries.len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
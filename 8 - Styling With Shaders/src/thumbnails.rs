@@ -0,0 +1,298 @@
+use crate::{
+    config, decode,
+    decode_pool::{DecodePool, JobKey, JobKind, Priority},
+    thumbnail_cache::{Thumbnail, ThumbnailCache, ThumbnailKey},
+};
+use makepad_widgets::*;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+const MAX_TEXTURES: usize = 1024;
+
+#[derive(Debug)]
+pub enum ThumbnailAction {
+    Loaded {
+        generation: u64,
+        path: PathBuf,
+        thumbnail: Thumbnail,
+    },
+    Failed {
+        generation: u64,
+        path: PathBuf,
+        error: String,
+    },
+}
+
+type SharedCache = Arc<OnceLock<Option<Mutex<ThumbnailCache>>>>;
+
+/// Grid thumbnails, decoded and downscaled by the decode pool and kept in a
+/// disk cache so each original is only ever decoded once.
+pub struct Thumbnails {
+    size: usize,
+    cache_max_bytes: u64,
+    cache: SharedCache,
+    decode_pool: DecodePool,
+    generation: Arc<AtomicU64>,
+    textures: HashMap<PathBuf, Texture>,
+    texture_order: VecDeque<PathBuf>,
+    pending: HashSet<PathBuf>,
+    failed: HashSet<PathBuf>,
+    visible: HashSet<PathBuf>,
+}
+
+impl Thumbnails {
+    pub fn new(
+        decode_pool: DecodePool,
+        size: usize,
+        cache_max_bytes: u64,
+    ) -> Self {
+        Self {
+            size,
+            cache_max_bytes,
+            cache: SharedCache::default(),
+            decode_pool,
+            generation: Arc::new(AtomicU64::new(0)),
+            textures: HashMap::new(),
+            texture_order: VecDeque::new(),
+            pending: HashSet::new(),
+            failed: HashSet::new(),
+            visible: HashSet::new(),
+        }
+    }
+
+    /// Changes the size thumbnails are decoded at, dropping the thumbnails
+    /// loaded at the previous size.
+    pub fn set_size(&mut self, size: usize) {
+        if size != self.size {
+            self.size = size;
+            self.clear();
+        }
+    }
+
+    pub fn cached_texture(&self, path: &Path) -> Option<Texture> {
+        self.textures.get(path).cloned()
+    }
+
+    /// Starts tracking which thumbnails are requested while drawing the grid.
+    /// Call `end_frame` once the grid is drawn to cancel the rest.
+    pub fn begin_frame(&mut self) {
+        self.visible.clear();
+    }
+
+    /// Cancels queued thumbnail jobs for images that were not requested since
+    /// `begin_frame`, i.e. that have been scrolled out of view.
+    pub fn end_frame(&mut self) {
+        let pending = &self.pending;
+        let visible = &self.visible;
+        let cancelled = self.decode_pool.cancel(|key| {
+            key.kind == JobKind::Thumbnail
+                && pending.contains(&key.path)
+                && !visible.contains(&key.path)
+        });
+        for key in cancelled {
+            self.pending.remove(&key.path);
+        }
+    }
+
+    /// Returns the thumbnail for `path` if it is ready, or else queues it for
+    /// loading and returns `None`. A `ThumbnailAction` is posted once it is.
+    pub fn texture(&mut self, cx: &mut Cx, path: &Path) -> Option<Texture> {
+        if let Some(texture) = self.textures.get(path) {
+            return Some(texture.clone());
+        }
+        self.visible.insert(path.to_path_buf());
+        if self.pending.contains(path) || self.failed.contains(path) {
+            return None;
+        }
+
+        let generation = self.generation.load(Ordering::Relaxed);
+        let cache = self.cache.clone();
+        let cache_max_bytes = self.cache_max_bytes;
+        let size = self.size;
+        let job_path = path.to_path_buf();
+        let key = JobKey {
+            path: path.to_path_buf(),
+            kind: JobKind::Thumbnail,
+        };
+        self.decode_pool
+            .submit(cx, key, Priority::Visible, move || {
+                let cache = cache.get_or_init(|| open_cache(cache_max_bytes));
+                let action =
+                    match load_thumbnail(cache.as_ref(), &job_path, size) {
+                        Ok(thumbnail) => ThumbnailAction::Loaded {
+                            generation,
+                            path: job_path,
+                            thumbnail,
+                        },
+                        Err(error) => ThumbnailAction::Failed {
+                            generation,
+                            path: job_path,
+                            error,
+                        },
+                    };
+                Cx::post_action(action);
+            });
+        self.pending.insert(path.to_path_buf());
+        None
+    }
+
+    /// Drops every thumbnail and cancels the jobs that are still queued, e.g.
+    /// when switching to another folder. Results of jobs that were already
+    /// running are ignored when they arrive.
+    pub fn clear(&mut self) {
+        self.decode_pool
+            .cancel(|key| key.kind == JobKind::Thumbnail);
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        self.textures.clear();
+        self.texture_order.clear();
+        self.pending.clear();
+        self.failed.clear();
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.textures.remove(path);
+        self.failed.remove(path);
+    }
+
+    pub fn handle_action(
+        &mut self,
+        cx: &mut Cx,
+        action: &ThumbnailAction,
+    ) -> bool {
+        let generation = self.generation.load(Ordering::Relaxed);
+        match action {
+            ThumbnailAction::Loaded {
+                generation: action_generation,
+                path,
+                thumbnail,
+            } if *action_generation == generation => {
+                self.pending.remove(path);
+                let texture = decode::texture_from_pixels(
+                    cx,
+                    thumbnail.width,
+                    thumbnail.height,
+                    thumbnail.pixels.clone(),
+                );
+                self.insert_texture(path.clone(), texture);
+                true
+            }
+            ThumbnailAction::Failed {
+                generation: action_generation,
+                path,
+                error,
+            } if *action_generation == generation => {
+                error!(
+                    "Failed to load thumbnail for {}: {}",
+                    path.display(),
+                    error
+                );
+                self.pending.remove(path);
+                self.failed.insert(path.clone());
+                false
+            }
+            _ => false,
+        }
+    }
+
+    fn insert_texture(&mut self, path: PathBuf, texture: Texture) {
+        if self.textures.insert(path.clone(), texture).is_none() {
+            self.texture_order.push_back(path);
+        }
+        while self.texture_order.len() > MAX_TEXTURES {
+            if let Some(path) = self.texture_order.pop_front() {
+                self.textures.remove(&path);
+            }
+        }
+    }
+}
+
+fn open_cache(cache_max_bytes: u64) -> Option<Mutex<ThumbnailCache>> {
+    let dir = config::cache_dir()?.join("thumbnails");
+    ThumbnailCache::open(dir, cache_max_bytes)
+        .inspect_err(|error| {
+            error!("Failed to open thumbnail cache: {}", error)
+        })
+        .ok()
+        .map(Mutex::new)
+}
+
+fn load_thumbnail(
+    cache: Option<&Mutex<ThumbnailCache>>,
+    path: &Path,
+    size: usize,
+) -> Result<Thumbnail, String> {
+    // Only the original's metadata is needed to build the key, so cache hits
+    // never open the original file.
+    let key =
+        ThumbnailKey::new(path, size).map_err(|error| error.to_string())?;
+    // The cache is shared by every decode thread, so it is only locked to
+    // look thumbnails up and record them, never while reading or writing
+    // them.
+    let cache_path = cache.and_then(|cache| cache.lock().unwrap().locate(key));
+    if let Some(cache) = cache
+        && let Some(cache_path) = cache_path
+    {
+        match Thumbnail::read(&cache_path) {
+            Some(thumbnail) => {
+                cache.lock().unwrap().mark_used(key);
+                return Ok(thumbnail);
+            }
+            None => cache.lock().unwrap().forget(key),
+        }
+    }
+
+    let image = decode::decode_file(path).map_err(|error| error.to_string())?;
+    let thumbnail =
+        Thumbnail::downsample(image.width, image.height, &image.pixels, size);
+    if let Some(cache) = cache {
+        let cache_path = cache.lock().unwrap().path(key);
+        match thumbnail.write(&cache_path) {
+            Ok(len) => cache.lock().unwrap().insert(key, len),
+            Err(error) => error!(
+                "Failed to cache thumbnail for {}: {}",
+                path.display(),
+                error
+            ),
+        }
+    }
+    Ok(thumbnail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn loads_thumbnails_through_the_cache() {
+        let dir = std::env::temp_dir()
+            .join(format!("thumbnails-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // Hits never decode the original, so it doesn't have to be an image.
+        let path = dir.join("a.jpg");
+        fs::write(&path, b"not an image").unwrap();
+        let cache =
+            Mutex::new(ThumbnailCache::open(dir.join("cache"), 1024).unwrap());
+        let key = ThumbnailKey::new(&path, 8).unwrap();
+        let thumbnail = Thumbnail {
+            width: 1,
+            height: 1,
+            pixels: vec![0x12345678],
+        };
+        cache.lock().unwrap().put(key, &thumbnail).unwrap();
+        assert_eq!(load_thumbnail(Some(&cache), &path, 8), Ok(thumbnail));
+
+        let cache_path = cache.lock().unwrap().locate(key).unwrap();
+        fs::write(&cache_path, b"IVTHUMB2 truncated").unwrap();
+        assert!(load_thumbnail(Some(&cache), &path, 8).is_err());
+        assert_eq!(cache.lock().unwrap().locate(key), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
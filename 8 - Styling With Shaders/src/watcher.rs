@@ -0,0 +1,296 @@
+use crate::{
+    image_format::ImageFormat,
+    rating::Ratings,
+    scan::{self, ScanOptions},
+};
+use makepad_widgets::*;
+use notify::{
+    Event as NotifyEvent, EventKind, RecommendedWatcher, RecursiveMode,
+    Watcher,
+    event::{ModifyKind, RenameMode},
+};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Debug)]
+pub enum DirWatcherAction {
+    Changed {
+        dir: PathBuf,
+        image_paths: Vec<PathBuf>,
+        ratings: Ratings,
+        renames: Vec<(PathBuf, PathBuf)>,
+        /// Images that may have been overwritten in place, so anything read
+        /// from them before is stale.
+        modified_paths: HashSet<PathBuf>,
+    },
+    /// Only XMP sidecars changed, so only the ratings of their images have
+    /// to be read again.
+    SidecarsChanged {
+        dir: PathBuf,
+        sidecar_paths: HashSet<PathBuf>,
+    },
+}
+
+/// Watches a directory and posts a `DirWatcherAction::Changed` with a fresh
+/// scan whenever images in it are created, removed, renamed or modified.
+/// Watching stops when the `DirWatcher` is dropped.
+pub struct DirWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl DirWatcher {
+    pub fn new(
+        cx: &mut Cx,
+        dir: &Path,
+        options: ScanOptions,
+    ) -> notify::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        let mode = if options.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher.watch(dir, mode)?;
+
+        let dir = dir.to_path_buf();
+        cx.spawn_thread(move || watch(receiver, dir, options));
+
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+fn watch(
+    receiver: Receiver<notify::Result<NotifyEvent>>,
+    dir: PathBuf,
+    options: ScanOptions,
+) {
+    // The sender lives inside the notify watcher, so this loop ends once the
+    // `DirWatcher` that owns it is dropped.
+    while let Ok(event) = receiver.recv() {
+        let mut changes = Changes::default();
+        changes.collect(event);
+
+        // Bursts of events, like copying a whole folder in, are coalesced
+        // into a single rescan.
+        loop {
+            match receiver.recv_timeout(DEBOUNCE) {
+                Ok(event) => changes.collect(event),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        if changes.needs_rescan {
+            let result = scan::scan_dir(&dir, options);
+            Cx::post_action(DirWatcherAction::Changed {
+                dir: dir.clone(),
+                image_paths: result.image_paths,
+                ratings: result.ratings,
+                renames: changes.renames,
+                modified_paths: changes.modified_paths,
+            });
+        } else if !changes.sidecar_paths.is_empty() {
+            Cx::post_action(DirWatcherAction::SidecarsChanged {
+                dir: dir.clone(),
+                sidecar_paths: changes.sidecar_paths,
+            });
+        }
+    }
+}
+
+/// What a burst of events changed.
+#[derive(Debug, Default)]
+struct Changes {
+    needs_rescan: bool,
+    renames: Vec<(PathBuf, PathBuf)>,
+    /// The source of a rename reported in two halves, waiting for its
+    /// destination, along with the tracker that pairs them up.
+    rename_from: Option<(Option<usize>, PathBuf)>,
+    modified_paths: HashSet<PathBuf>,
+    sidecar_paths: HashSet<PathBuf>,
+}
+
+impl Changes {
+    fn collect(&mut self, event: notify::Result<NotifyEvent>) {
+        let Ok(event) = event else {
+            self.needs_rescan = true;
+            return;
+        };
+
+        match event.kind {
+            // Scanning opens every file to check its signature, so access
+            // events must be ignored or each rescan would trigger the next
+            // one.
+            EventKind::Access(_) | EventKind::Other => return,
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                if let [from, to] = event.paths.as_slice() {
+                    self.add_rename(from, to);
+                }
+            }
+            // Some backends report the two halves of a rename separately,
+            // and some report them as a whole as well.
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                if let [from] = event.paths.as_slice() {
+                    self.rename_from = Some((event.tracker(), from.clone()));
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                if let [to] = event.paths.as_slice()
+                    && let Some((tracker, from)) = self.rename_from.take()
+                    && tracker == event.tracker()
+                {
+                    self.add_rename(&from, to);
+                }
+            }
+            _ => {}
+        }
+
+        // Files saved through a temporary file replace the image with a
+        // rename onto it, so the destination of a rename may have changed
+        // as well.
+        let is_modified = matches!(
+            event.kind,
+            EventKind::Create(_)
+                | EventKind::Modify(ModifyKind::Any | ModifyKind::Data(_))
+        );
+        let rename_to = match event.kind {
+            EventKind::Modify(ModifyKind::Name(
+                RenameMode::To | RenameMode::Both,
+            )) => event.paths.last(),
+            _ => None,
+        };
+        for path in &event.paths {
+            match PathKind::of(path) {
+                PathKind::Ignored => {}
+                PathKind::Sidecar => {
+                    self.sidecar_paths.insert(path.clone());
+                }
+                PathKind::Scanned => {
+                    self.needs_rescan = true;
+                    if is_modified || rename_to == Some(path) {
+                        self.modified_paths.insert(path.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    fn add_rename(&mut self, from: &Path, to: &Path) {
+        let rename = (from.to_path_buf(), to.to_path_buf());
+        if !self.renames.contains(&rename) {
+            self.renames.push(rename);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PathKind {
+    /// Can't change what a scan finds, like the recipes of adjustments and
+    /// the temporary files edits are saved through.
+    Ignored,
+    /// An XMP sidecar, which only holds the rating of its image.
+    Sidecar,
+    Scanned,
+}
+
+impl PathKind {
+    fn of(path: &Path) -> Self {
+        let has_extension = |wanted: &str| {
+            path.extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case(wanted))
+        };
+        if scan::is_hidden(path) || has_extension("recipe") {
+            Self::Ignored
+        } else if has_extension("xmp") {
+            Self::Sidecar
+        } else if path.is_file()
+            && matches!(ImageFormat::from_path(path), Ok(None))
+        {
+            // A file that exists but is not an image. Paths that are gone
+            // can't be told apart from images that were removed.
+            Self::Ignored
+        } else {
+            Self::Scanned
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: EventKind, paths: &[&str], tracker: usize) -> NotifyEvent {
+        paths
+            .iter()
+            .fold(NotifyEvent::new(kind), |event, path| {
+                event.add_path(PathBuf::from(path))
+            })
+            .set_tracker(tracker)
+    }
+
+    #[test]
+    fn pairs_renames_reported_in_halves() {
+        let name = |mode| EventKind::Modify(ModifyKind::Name(mode));
+        let mut changes = Changes::default();
+        changes.collect(Ok(event(name(RenameMode::From), &["/a.jpg"], 1)));
+        changes.collect(Ok(event(name(RenameMode::To), &["/b.jpg"], 1)));
+        changes.collect(Ok(event(
+            name(RenameMode::Both),
+            &["/a.jpg", "/b.jpg"],
+            1,
+        )));
+        changes.collect(Ok(event(name(RenameMode::From), &["/c.jpg"], 2)));
+        changes.collect(Ok(event(name(RenameMode::To), &["/d.jpg"], 3)));
+        assert_eq!(changes.renames, [("/a.jpg".into(), "/b.jpg".into())]);
+        assert!(changes.needs_rescan);
+    }
+
+    #[test]
+    fn ignores_the_files_the_app_writes_itself() {
+        let create = EventKind::Create(notify::event::CreateKind::File);
+        let mut changes = Changes::default();
+        changes.collect(Ok(event(create, &["/photos/a.jpg.recipe"], 0)));
+        changes.collect(Ok(event(create, &["/photos/.a.jpg.tmp"], 0)));
+        assert!(!changes.needs_rescan);
+        assert!(changes.sidecar_paths.is_empty());
+
+        changes.collect(Ok(event(create, &["/photos/a.jpg.xmp"], 0)));
+        assert!(!changes.needs_rescan);
+        assert!(
+            changes
+                .sidecar_paths
+                .contains(Path::new("/photos/a.jpg.xmp"))
+        );
+
+        changes.collect(Ok(event(create, &["/photos/a.jpg"], 0)));
+        assert!(changes.needs_rescan);
+    }
+
+    #[test]
+    fn reports_images_modified_in_place() {
+        let data = EventKind::Modify(ModifyKind::Data(
+            notify::event::DataChange::Content,
+        ));
+        let both = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+        let remove = EventKind::Remove(notify::event::RemoveKind::File);
+        let mut changes = Changes::default();
+        changes.collect(Ok(event(data, &["/photos/a.jpg"], 0)));
+        changes.collect(Ok(event(
+            both,
+            &["/photos/.b.tmp", "/photos/b.jpg"],
+            0,
+        )));
+        changes.collect(Ok(event(remove, &["/photos/c.jpg"], 0)));
+        assert_eq!(
+            changes.modified_paths,
+            HashSet::from(["/photos/a.jpg".into(), "/photos/b.jpg".into()])
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use std::ops::Range;
+
+pub const NS_XMP: &str = "http://ns.adobe.com/xap/1.0/";
+pub const NS_XMP_DM: &str = "http://ns.adobe.com/xmp/1.0/DynamicMedia/";
+
+/// A packet with nothing in it, to add properties to.
+pub const EMPTY_PACKET: &str = "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">
+ <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">
+  <rdf:Description rdf:about=\"\"/>
+ </rdf:RDF>
+</x:xmpmeta>
+";
+
+/// The parts of an XMP packet the app cares about. XMP is RDF in XML, which
+/// is not parsed in full: properties are looked up by their qualified names,
+/// which works for the packets cameras and photo editors write.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Xmp {
+    pub keywords: Vec<String>,
+    pub caption: Option<String>,
+    /// `xmp:Rating`, from 0 to 5 stars, or -1 for rejected.
+    pub rating: Option<i32>,
+    /// `xmp:Label`, the name of a color label such as `Red`.
+    pub label: Option<String>,
+    /// `xmpDM:good`, whether the shot was marked as a keeper or not.
+    pub good: Option<bool>,
+}
+
+impl Xmp {
+    /// Parses the payload of a JPEG APP1 segment, which starts with the XMP
+    /// namespace as identifier.
+    pub fn from_app1(bytes: &[u8]) -> Option<Self> {
+        let packet = bytes.strip_prefix(b"http://ns.adobe.com/xap/1.0/\0")?;
+        Some(Self::parse(&String::from_utf8_lossy(packet)))
+    }
+
+    pub fn parse(packet: &str) -> Self {
+        Self {
+            keywords: element(packet, "dc:subject")
+                .map(list_items)
+                .unwrap_or_default(),
+            caption: element(packet, "dc:description").and_then(
+                |description| list_items(description).into_iter().next(),
+            ),
+            rating: property(packet, "xmp:Rating")
+                .and_then(|rating| rating.parse().ok()),
+            label: property(packet, "xmp:Label")
+                .filter(|label| !label.is_empty()),
+            good: property(packet, "xmpDM:good").and_then(|good| {
+                match good.as_str() {
+                    "True" | "true" => Some(true),
+                    "False" | "false" => Some(false),
+                    _ => None,
+                }
+            }),
+        }
+    }
+}
+
+/// Sets a simple property of `packet`, or removes it if `value` is `None`,
+/// leaving the rest of the packet as it is. The property is written as an
+/// attribute of the first `rdf:Description`, whose namespace is declared
+/// there if it isn't already.
+pub fn set_property(
+    packet: &str,
+    name: &str,
+    namespace: &str,
+    value: Option<&str>,
+) -> String {
+    let mut packet = remove_property(packet, name);
+    let Some(value) = value else {
+        return packet;
+    };
+    let Some(start) = find_start_tag(&packet, "rdf:Description") else {
+        return packet;
+    };
+    let mut attributes = String::new();
+    let prefix = name.split_once(':').map_or("", |(prefix, _)| prefix);
+    if !packet.contains(&format!("xmlns:{}=", prefix)) {
+        attributes.push_str(&format!(" xmlns:{}=\"{}\"", prefix, namespace));
+    }
+    attributes.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+    packet.insert_str(start + "<rdf:Description".len(), &attributes);
+    packet
+}
+
+/// Returns the value of a simple property, written either as an element or
+/// as an attribute.
+fn property(xml: &str, name: &str) -> Option<String> {
+    element(xml, name)
+        .map(|value| unescape(value.trim()))
+        .or_else(|| attribute(xml, name).map(|(_, value)| unescape(value)))
+}
+
+/// Finds the first attribute with the given name, returning its value along
+/// with the range it covers, including the whitespace before it.
+fn attribute<'a>(xml: &'a str, name: &str) -> Option<(Range<usize>, &'a str)> {
+    let pattern = format!("{}=", name);
+    let mut offset = 0;
+    while let Some(idx) = xml[offset..].find(&pattern) {
+        let start = offset + idx;
+        offset = start + pattern.len();
+        if !xml[..start].ends_with(char::is_whitespace) {
+            continue;
+        }
+        let rest = &xml[offset..];
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let len = rest[1..].find(quote)?;
+        let start = xml[..start].trim_end_matches(char::is_whitespace).len();
+        let end = offset + len + 2;
+        return Some((start..end, &rest[1..1 + len]));
+    }
+    None
+}
+
+/// Removes every attribute and element with the given name.
+pub fn remove_property(xml: &str, name: &str) -> String {
+    let mut xml = xml.to_string();
+    while let Some((range, _)) = attribute(&xml, name) {
+        xml.replace_range(range, "");
+    }
+    while let Some(start) = find_start_tag(&xml, name) {
+        let Some(tag_end) = xml[start..].find('>') else {
+            break;
+        };
+        let end = if xml[start..start + tag_end].ends_with('/') {
+            start + tag_end + 1
+        } else {
+            let close_tag = format!("</{}>", name);
+            let Some(idx) = xml[start..].find(&close_tag) else {
+                break;
+            };
+            start + idx + close_tag.len()
+        };
+        xml.replace_range(start..end, "");
+    }
+    xml
+}
+
+/// Returns the content of the first element with the given name.
+fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = find_start_tag(xml, name)?;
+    let content = &xml[start..];
+    let content = &content[content.find('>')? + 1..];
+    let end = content.find(&format!("</{}>", name))?;
+    Some(&content[..end])
+}
+
+/// Finds `<name` followed by whitespace or the end of the tag, so `dc:sub`
+/// doesn't match `<dc:subject>`.
+fn find_start_tag(xml: &str, name: &str) -> Option<usize> {
+    let tag = format!("<{}", name);
+    let mut offset = 0;
+    while let Some(idx) = xml[offset..].find(&tag) {
+        let start = offset + idx;
+        let next = xml[start + tag.len()..].chars().next();
+        if next.is_some_and(|c| c == '>' || c == '/' || c.is_whitespace()) {
+            return Some(start);
+        }
+        offset = start + tag.len();
+    }
+    None
+}
+
+/// The text of the `rdf:li` items of an `rdf:Bag`, `rdf:Seq` or `rdf:Alt`.
+fn list_items(xml: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = find_start_tag(rest, "rdf:li") {
+        rest = &rest[start..];
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        // An empty element, `<rdf:li/>`.
+        if rest[..tag_end].ends_with('/') {
+            rest = &rest[tag_end + 1..];
+            continue;
+        }
+        rest = &rest[tag_end + 1..];
+        let Some(end) = rest.find("</rdf:li>") else {
+            break;
+        };
+        let item = unescape(rest[..end].trim());
+        if !item.is_empty() {
+            items.push(item);
+        }
+        rest = &rest[end..];
+    }
+    items
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,181 @@
+use makepad_widgets::*;
+
+/// The most the slideshow zooms in, in screen pixels per image pixel.
+const MAX_ZOOM: f64 = 32.0;
+
+/// What happens to the zoom of an image when the slideshow moves on to
+/// another one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ZoomMode {
+    /// Every image starts out fitted to the view.
+    #[default]
+    Reset,
+    /// Every image comes back zoomed the way it was left.
+    PerImage,
+}
+
+impl ZoomMode {
+    pub const ALL: [Self; 2] = [Self::Reset, Self::PerImage];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Reset => "reset",
+            Self::PerImage => "per_image",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Reset => "Reset Zoom",
+            Self::PerImage => "Keep Zoom",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+/// The sizes zooming depends on: the image in image pixels, and the view it
+/// is shown in in logical pixels, along with the DPI factor that relates
+/// those to screen pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub image_size: DVec2,
+    pub view_size: DVec2,
+    pub dpi_factor: f64,
+}
+
+impl Viewport {
+    fn fit_scale(&self) -> f64 {
+        (self.view_size.x / self.image_size.x)
+            .min(self.view_size.y / self.image_size.y)
+    }
+
+    /// The scale at which every image pixel covers one screen pixel.
+    pub fn actual_size_scale(&self) -> f64 {
+        1.0 / self.dpi_factor
+    }
+
+    /// The center of the view, relative to the view.
+    pub fn center(&self) -> DVec2 {
+        dvec2(self.view_size.x / 2.0, self.view_size.y / 2.0)
+    }
+}
+
+/// How the slideshow image is zoomed and panned.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Zoom {
+    /// Scaled to fit the view, whatever its size.
+    #[default]
+    Fit,
+    /// Scaled by `scale` logical pixels per image pixel, with the point
+    /// `center` of the image, in image pixels, at the center of the view.
+    Fixed { scale: f64, center: DVec2 },
+}
+
+impl Zoom {
+    /// The rect the image is drawn in, relative to the view.
+    pub fn image_rect(&self, viewport: Viewport) -> Rect {
+        let (scale, center) = match *self {
+            Self::Fit => (
+                viewport.fit_scale(),
+                dvec2(viewport.image_size.x / 2.0, viewport.image_size.y / 2.0),
+            ),
+            Self::Fixed { scale, center } => (scale, center),
+        };
+        let view_center = viewport.center();
+        Rect {
+            pos: dvec2(
+                view_center.x - center.x * scale,
+                view_center.y - center.y * scale,
+            ),
+            size: dvec2(
+                viewport.image_size.x * scale,
+                viewport.image_size.y * scale,
+            ),
+        }
+    }
+
+    fn scale(&self, viewport: Viewport) -> f64 {
+        match *self {
+            Self::Fit => viewport.fit_scale(),
+            Self::Fixed { scale, .. } => scale,
+        }
+    }
+
+    /// Zooms by `factor`, keeping the image point under `point` in the view
+    /// where it is. Zooming out as far as the image fits the view fits it.
+    pub fn zoom_at(
+        &self,
+        factor: f64,
+        point: DVec2,
+        viewport: Viewport,
+    ) -> Self {
+        let max_scale = MAX_ZOOM * viewport.actual_size_scale();
+        let scale = (self.scale(viewport) * factor).min(max_scale);
+        if scale <= viewport.fit_scale() {
+            return Self::Fit;
+        }
+        self.scale_at(scale, point, viewport)
+    }
+
+    /// Zooms to `scale`, keeping the image point under `point` in the view
+    /// where it is.
+    pub fn scale_at(
+        &self,
+        scale: f64,
+        point: DVec2,
+        viewport: Viewport,
+    ) -> Self {
+        let rect = self.image_rect(viewport);
+        let old_scale = self.scale(viewport);
+        let view_center = viewport.center();
+        let center = dvec2(
+            (point.x - rect.pos.x) / old_scale
+                - (point.x - view_center.x) / scale,
+            (point.y - rect.pos.y) / old_scale
+                - (point.y - view_center.y) / scale,
+        );
+        Self::Fixed { scale, center }.clamped(viewport)
+    }
+
+    /// Moves the image by `delta` logical pixels.
+    pub fn pan(&self, delta: DVec2, viewport: Viewport) -> Self {
+        match *self {
+            Self::Fit => Self::Fit,
+            Self::Fixed { scale, center } => Self::Fixed {
+                scale,
+                center: dvec2(
+                    center.x - delta.x / scale,
+                    center.y - delta.y / scale,
+                ),
+            }
+            .clamped(viewport),
+        }
+    }
+
+    /// Keeps the image from being panned out of view: along each axis, an
+    /// image smaller than the view stays centered, and a larger one can't
+    /// be panned past its edges.
+    pub fn clamped(&self, viewport: Viewport) -> Self {
+        let Self::Fixed { scale, center } = *self else {
+            return *self;
+        };
+        let clamp = |center: f64, image_size: f64, view_size: f64| {
+            let half_view_size = view_size / 2.0 / scale;
+            if image_size <= 2.0 * half_view_size {
+                image_size / 2.0
+            } else {
+                center.clamp(half_view_size, image_size - half_view_size)
+            }
+        };
+        Self::Fixed {
+            scale,
+            center: dvec2(
+                clamp(center.x, viewport.image_size.x, viewport.view_size.x),
+                clamp(center.y, viewport.image_size.y, viewport.view_size.y),
+            ),
+        }
+    }
+}
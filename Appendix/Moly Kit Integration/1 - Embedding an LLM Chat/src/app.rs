@@ -4,6 +4,20 @@ use moly_kit::{
 };
 use std::path::{Path, PathBuf};
 
+/// The images of the earlier lessons, relative to this lesson's folder.
+const IMAGES_PATH: &str = "../../../images";
+
+/// The image directory, from the first command-line argument, the
+/// `IMAGE_VIEWER_DIR` environment variable, or else `IMAGES_PATH`. Unlike
+/// the image viewer of the main lessons, this doesn't read `config.toml`.
+fn images_dir() -> PathBuf {
+    std::env::args_os()
+        .nth(1)
+        .or_else(|| std::env::var_os("IMAGE_VIEWER_DIR"))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(IMAGES_PATH))
+}
+
 live_design! {
     use link::widgets::*;
     use moly_kit::widgets::chat::Chat;
@@ -308,7 +322,7 @@ impl LiveRegister for App {
 
 impl LiveHook for App {
     fn after_new_from_doc(&mut self, cx: &mut Cx) {
-        self.load_image_paths(cx, "../../../images".as_ref());
+        self.load_image_paths(cx, &images_dir());
         self.configure_slideshow_chat(cx);
     }
 }
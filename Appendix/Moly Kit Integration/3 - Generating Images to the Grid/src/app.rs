@@ -7,8 +7,19 @@ use std::path::{Path, PathBuf};
 
 use crate::slideshow_client::SlideshowClient;
 
+/// The images of the earlier lessons, relative to this lesson's folder.
 const IMAGES_PATH: &str = "../../../images";
 
+/// The image directory, chosen as in the previous lessons. Generated images
+/// are saved there too.
+fn images_dir() -> PathBuf {
+    std::env::args_os()
+        .nth(1)
+        .or_else(|| std::env::var_os("IMAGE_VIEWER_DIR"))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(IMAGES_PATH))
+}
+
 live_design! {
     use link::widgets::*;
     use moly_kit::widgets::chat::Chat;
@@ -497,7 +508,7 @@ impl App {
                                         ).unwrap().as_secs();
 
                                         let filename = format!("generated_image_{now}.png");
-                                        let path = Path::new(IMAGES_PATH).join(&filename);
+                                        let path = images_dir().join(&filename);
 
                                         println!("Saving generated image to {path:?}");
 
@@ -534,7 +545,7 @@ impl LiveRegister for App {
 
 impl LiveHook for App {
     fn after_new_from_doc(&mut self, cx: &mut Cx) {
-        self.load_image_paths(cx, IMAGES_PATH.as_ref());
+        self.load_image_paths(cx, &images_dir());
         self.configure_slideshow_chat(cx);
         self.configure_image_browser_chat(cx);
     }